@@ -1,6 +1,6 @@
 use makemake::prelude::*;
 
-fn main() {
+fn main() -> makemake::Result<()> {
     let mut makefile = Makefile::new();
 
     makefile.comment("Generated by makemake!");
@@ -37,5 +37,6 @@ fn main() {
         .dep(obj)
         .cmd(expr!("rm -rf", makefile.deps_var()));
 
-    print!("{}", makefile.build());
+    print!("{}", makefile.build()?);
+    Ok(())
 }
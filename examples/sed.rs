@@ -1,6 +1,6 @@
 use makemake::prelude::*;
 
-fn main() {
+fn main() -> makemake::Result<()> {
     let mut makefile = Makefile::new();
 
     makefile.comment("Generated by makemake!");
@@ -17,5 +17,6 @@ fn main() {
             e.assign(sed, "sed -i");
         });
 
-    print!("{}", makefile.build());
+    print!("{}", makefile.build()?);
+    Ok(())
 }
@@ -0,0 +1,218 @@
+//! Procedural macros for [`makemake`](https://crates.io/crates/makemake),
+//! which re-exports them.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Expr, Ident, LitStr, Token
+};
+
+/// An argument to [`mk!`], either `expr` or `name = expr`.
+struct Argument {
+    name: Option<Ident>,
+    value: Expr
+}
+
+impl Parse for Argument {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = if input.peek(Ident) && input.peek2(Token![=]) {
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            Some(name)
+        } else {
+            None
+        };
+        Ok(Self {
+            name,
+            value: input.parse()?
+        })
+    }
+}
+
+struct Input {
+    format: LitStr,
+    args: Vec<Argument>
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let format = input.parse()?;
+        let args = if input.is_empty() {
+            vec![]
+        } else {
+            input.parse::<Token![,]>()?;
+            Punctuated::<Argument, Token![,]>::parse_terminated(input)?
+                .into_iter()
+                .collect()
+        };
+        Ok(Self { format, args })
+    }
+}
+
+/// A piece of the format string.
+enum Piece {
+    Literal(String),
+    Positional(usize),
+    Named(String)
+}
+
+fn parse_format(format: &LitStr) -> syn::Result<Vec<Piece>> {
+    let error = |message: &str| syn::Error::new(format.span(), message);
+    let text = format.value();
+    let mut pieces = vec![];
+    let mut literal = String::new();
+    let mut next_positional = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => return Err(error("unmatched `}` in format string")),
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => {
+                            return Err(error("unmatched `{` in format string"))
+                        }
+                    }
+                }
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                let placeholder = placeholder.trim();
+                if placeholder.is_empty() {
+                    pieces.push(Piece::Positional(next_positional));
+                    next_positional += 1;
+                } else if let Ok(index) = placeholder.parse() {
+                    pieces.push(Piece::Positional(index));
+                } else if syn::parse_str::<Ident>(placeholder).is_ok() {
+                    pieces.push(Piece::Named(placeholder.to_string()));
+                } else {
+                    return Err(error(&format!(
+                        "invalid placeholder `{{{}}}`; expected `{{}}`, an \
+                         index, or a name",
+                        placeholder
+                    )));
+                }
+            }
+            c => literal.push(c)
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+fn expand(input: Input) -> syn::Result<TokenStream2> {
+    let pieces = parse_format(&input.format)?;
+    let positional_count = input
+        .args
+        .iter()
+        .take_while(|arg| arg.name.is_none())
+        .count();
+    if let Some(arg) = input.args[positional_count..]
+        .iter()
+        .find(|arg| arg.name.is_none())
+    {
+        return Err(syn::Error::new_spanned(
+            &arg.value,
+            "positional arguments cannot follow named arguments"
+        ));
+    }
+
+    let mut bindings = vec![];
+    let mut used = vec![false; input.args.len()];
+    for (i, arg) in input.args.iter().enumerate() {
+        let binding = format_ident!("__mk_arg{}", i);
+        let value = &arg.value;
+        bindings.push(quote! { let #binding = #value; });
+    }
+
+    let mut parts = vec![];
+    for piece in &pieces {
+        match piece {
+            Piece::Literal(text) => {
+                parts.push(quote! { ::makemake::expr::Expr::from(#text) });
+            }
+            Piece::Positional(index) => {
+                if *index >= positional_count {
+                    return Err(syn::Error::new(
+                        input.format.span(),
+                        format!(
+                            "placeholder {{{}}} refers to a missing positional \
+                             argument; {} given",
+                            index, positional_count
+                        )
+                    ));
+                }
+                used[*index] = true;
+                let binding = format_ident!("__mk_arg{}", index);
+                parts.push(quote! { ::makemake::expr::Expr::from(#binding) });
+            }
+            Piece::Named(name) => {
+                let index = input.args.iter().position(|arg| {
+                    arg.name.as_ref().is_some_and(|arg_name| arg_name == name)
+                });
+                if let Some(index) = index {
+                    used[index] = true;
+                    let binding = format_ident!("__mk_arg{}", index);
+                    parts.push(
+                        quote! { ::makemake::expr::Expr::from(#binding) }
+                    );
+                } else {
+                    let capture = Ident::new(name, Span::call_site());
+                    parts.push(
+                        quote! { ::makemake::expr::Expr::from(#capture) }
+                    );
+                }
+            }
+        }
+    }
+    if let Some(index) = used.iter().position(|used| !used) {
+        return Err(syn::Error::new_spanned(
+            &input.args[index].value,
+            "argument never used in format string"
+        ));
+    }
+
+    let expr = match parts.len() {
+        0 => quote! { ::makemake::expr::Expr::Empty },
+        1 => parts.remove(0),
+        _ => quote! { ::makemake::expr::Expr::Concat(vec![#(#parts),*]) }
+    };
+    Ok(quote! {{
+        #(#bindings)*
+        #expr
+    }})
+}
+
+/// `mk!("{cc} {cflags} -o {} {}", target, deps)` constructs an expression by
+/// interpolating the arguments into the format string, like `format!`.
+/// Placeholders are `{}` for the next positional argument, `{n}` for the `n`th,
+/// and `{name}` for a `name = value` argument or otherwise the variable `name`
+/// in scope. Write `{{` and `}}` for literal braces.
+///
+/// Each argument can be any value whose type implements `Into<Expr>`, and must
+/// be `Copy` (like `Variable`) if it is used more than once. Mismatches between
+/// the placeholders and the arguments are compile-time errors.
+#[proc_macro]
+pub fn mk(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Input);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
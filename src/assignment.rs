@@ -1,25 +1,42 @@
 use crate::{
+    dialect::Dialect,
     emittable::Emittable,
+    error::Error,
     expr::Expr,
     symbol_context::{Resolvable, SymbolContext},
     var::Variable
 };
 
 /// See sections [6.5](https://www.gnu.org/software/make/manual/make.html#Setting)
-/// for information on `Overwrite` and `Underwrite` and section
+/// for information on `Overwrite` and `Underwrite`, section
+/// [6.2](https://www.gnu.org/software/make/manual/make.html#Flavors) for
+/// information on `Simple` and `Immediate`, and section
 /// [6.6](https://www.gnu.org/software/make/manual/make.html#Appending) for
 /// information on `Append`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum AssignmentKind {
     Overwrite,
     Underwrite,
+    Simple,
+    /// `:::=`, which expands the value immediately like `Simple` but keeps the
+    /// variable recursively expanded.
+    Immediate,
     Append
 }
 
 impl Emittable for AssignmentKind {
-    fn emit(&self, _ctx: &mut SymbolContext) -> String {
+    fn emit(&self, ctx: &mut SymbolContext) -> String {
         match &self {
             Self::Overwrite => "=",
             Self::Underwrite => "?=",
+            Self::Simple if ctx.dialect() == Dialect::Posix2024 => "::=",
+            Self::Simple => ":=",
+            Self::Immediate => ":::=",
             Self::Append => "+="
         }
         .to_string()
@@ -28,9 +45,9 @@ impl Emittable for AssignmentKind {
 
 /// An assignment to or an instance of appending a value to a variable
 pub struct Assignment {
-    kind: AssignmentKind,
-    var: Variable,
-    value: Expr
+    pub(crate) kind: AssignmentKind,
+    pub(crate) var: Variable,
+    pub(crate) value: Expr
 }
 
 impl Assignment {
@@ -44,13 +61,42 @@ impl Assignment {
             value: value.into()
         }
     }
+
+    /// The command run by the value of this simply expanded assignment if the
+    /// value is only a `$(shell ...)`, which POSIX make can instead run once
+    /// with `!=`. A recursively expanded assignment runs the command each time
+    /// the variable is expanded, which `!=` cannot express.
+    pub(crate) fn shell_command(
+        &self, ctx: &mut SymbolContext
+    ) -> Option<String> {
+        let Expr::Function(function) = &self.value else {
+            return None;
+        };
+        match (self.kind, function.name(), function.args()) {
+            (AssignmentKind::Simple, "shell", [command]) => {
+                Some(command.emit(ctx))
+            }
+            _ => None
+        }
+    }
 }
 
 impl Emittable for Assignment {
     fn emit(&self, ctx: &mut SymbolContext) -> String {
+        if ctx.dialect() == Dialect::Posix2024 {
+            if let Some(command) = self.shell_command(ctx) {
+                let name = self.var.name(ctx);
+                return format!("{} != {}", name, command);
+            }
+        }
         let kind = self.kind.emit(ctx);
         let value = self.value.emit(ctx);
         let name = self.var.name(ctx);
         format!("{} {} {}", name, kind, value)
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        self.var.validate(ctx, errors);
+        self.value.validate(ctx, errors);
+    }
 }
@@ -0,0 +1,497 @@
+use std::fmt::Write;
+
+use crate::{
+    assignment::AssignmentKind,
+    conditional::Condition,
+    emittable::Emittable,
+    entry::Entry,
+    error::{Error, Result},
+    eval::EvalError,
+    makefile::Makefile,
+    rule::Rule,
+    symbol_context::{Resolvable, SymbolContext},
+    syntax::{self, Fragment}
+};
+
+const BACKEND: &str = "BSD make";
+
+/// The automatic variables of GNU make paired with the local variables of BSD
+/// make that they correspond to.
+const AUTOMATIC_VARIABLES: &[(&str, &str)] = &[
+    ("@", ".TARGET"),
+    ("<", ".IMPSRC"),
+    ("^", ".ALLSRC"),
+    ("+", ".ALLSRC"),
+    ("?", ".OODATE"),
+    ("*", ".PREFIX")
+];
+
+/// Where translated text appears, which determines the characters in it that
+/// must be escaped.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// Anywhere outside a variable expression.
+    Plain,
+    /// Within a string in a `.if` or `.include`.
+    Quoted,
+    /// Within a `:U` or `:M` modifier, which ends at a `:` or `}`.
+    Word,
+    /// Within the old or new string of a `:S` or `:C` modifier.
+    Substitute,
+    /// Within the body of a `:@` modifier.
+    Loop
+}
+
+/// Escapes the literal `text` for `context`.
+fn escape(text: &str, context: Context) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        match (c, context) {
+            ('$', _) => result.push_str("$$"),
+            ('"', Context::Quoted)
+            | (':' | '}' | '\\', Context::Word)
+            | ('/' | '&' | '\\', Context::Substitute) => {
+                result.push('\\');
+                result.push(c);
+            }
+            _ => result.push(c)
+        }
+    }
+    result
+}
+
+/// Escapes `text` for use in a regular expression in a `:C` modifier.
+fn escape_regex(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        if "\\.[]*^$+?(){}|/".contains(c) {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// The text of `fragments` if they contain no references.
+fn literal(fragments: &[Fragment]) -> Option<&str> {
+    match fragments {
+        [] => Some(""),
+        [Fragment::Text(text)] => Some(text),
+        _ => None
+    }
+}
+
+/// Translates the text of a Makefile model into BSD make syntax, collecting
+/// every construct that has no equivalent.
+struct Translator {
+    /// Where the text being translated is found, e.g., `in the rule for `a``.
+    location: String,
+    errors: Vec<Error>
+}
+
+impl Translator {
+    fn unsupported(&mut self, construct: String) {
+        let construct = if self.location.is_empty() {
+            construct
+        } else {
+            format!("{} {}", construct, self.location)
+        };
+        self.errors.push(Error::Unsupported {
+            construct,
+            backend: BACKEND
+        });
+    }
+
+    fn text(&mut self, text: &str, context: Context) -> String {
+        match syntax::parse(text) {
+            Ok(fragments) => self.fragments(&fragments, context),
+            Err(error) => {
+                self.errors.push(Error::Eval(EvalError::Syntax {
+                    text: error.text,
+                    message: error.message
+                }));
+                String::new()
+            }
+        }
+    }
+
+    fn fragments(
+        &mut self, fragments: &[Fragment], context: Context
+    ) -> String {
+        fragments
+            .iter()
+            .map(|fragment| match fragment {
+                Fragment::Text(text) => escape(text, context),
+                fragment => self.expression(fragment)
+            })
+            .collect()
+    }
+
+    /// Translates the reference or call `fragment` to a `${...}` expression.
+    fn expression(&mut self, fragment: &Fragment) -> String {
+        match fragment {
+            Fragment::Text(text) => {
+                format!("${{:U{}}}", escape(text, Context::Word))
+            }
+            Fragment::Reference { name, substitution } => {
+                let mut result = self.reference(name);
+                if let Some((old, new)) = substitution {
+                    let modifier = self.substitution(old, new);
+                    result = chain(&result, &modifier);
+                }
+                result
+            }
+            Fragment::Call { spec, args } => self.call(spec.name, args)
+        }
+    }
+
+    fn reference(&mut self, name: &[Fragment]) -> String {
+        let Some(name) = literal(name) else {
+            return format!("${{{}}}", self.fragments(name, Context::Word));
+        };
+        let (automatic, modifier) = match name.char_indices().nth(1) {
+            Some((i, 'D')) if name.len() == 2 => (&name[..i], ":H"),
+            Some((i, 'F')) if name.len() == 2 => (&name[..i], ":T"),
+            _ => (name, "")
+        };
+        match AUTOMATIC_VARIABLES
+            .iter()
+            .find(|(gnu, _)| *gnu == automatic)
+        {
+            Some((_, bsd)) => format!("${{{}{}}}", bsd, modifier),
+            None if automatic == "|" || automatic == "%" => {
+                self.unsupported(format!("`${}`", name));
+                String::new()
+            }
+            None => format!("${{{}}}", name)
+        }
+    }
+
+    /// The modifier for a substitution reference replacing `old` with `new`.
+    fn substitution(&mut self, old: &[Fragment], new: &[Fragment]) -> String {
+        match (literal(old), literal(new)) {
+            (Some(old), Some(new)) if old.contains('%') => pattern(old, new),
+            (Some(old), None) if old.contains('%') => {
+                self.unsupported(format!("the substitution of `{}`", old));
+                String::new()
+            }
+            _ => format!(
+                ":S/{}$/{}/",
+                self.fragments(old, Context::Substitute),
+                self.fragments(new, Context::Substitute)
+            )
+        }
+    }
+
+    /// Translates a call to the function `name` with `args`.
+    fn call(&mut self, name: &str, args: &[Vec<Fragment>]) -> String {
+        let static_arg = |i: usize| args.get(i).and_then(|arg| literal(arg));
+        let number = |i: usize| {
+            static_arg(i)
+                .and_then(|arg| arg.trim().parse::<usize>().ok())
+                .filter(|n| *n > 0)
+        };
+        let modifier = match (name, args.len()) {
+            ("subst", 3) => match (static_arg(0), static_arg(1)) {
+                (Some(from), Some(to))
+                    if !from.is_empty()
+                        && !from.contains(char::is_whitespace) =>
+                {
+                    Some((
+                        2,
+                        format!(
+                            ":S/{}/{}/g",
+                            escape(from, Context::Substitute),
+                            escape(to, Context::Substitute)
+                        )
+                    ))
+                }
+                _ => None
+            },
+            ("patsubst", 3) => match (static_arg(0), static_arg(1)) {
+                (Some(old), Some(new)) if old.trim().contains('%') => {
+                    Some((2, pattern(old.trim(), new.trim())))
+                }
+                _ => None
+            },
+            ("strip", 1) => Some((0, ":M*".into())),
+            ("filter" | "filter-out", 2) => match static_arg(0)
+                .map(str::trim)
+                .filter(|pattern| {
+                    !pattern.contains(char::is_whitespace)
+                        && !pattern.contains(['*', '?', '['])
+                }) {
+                Some(pattern) => {
+                    let glob = pattern.replacen('%', "*", 1);
+                    let kind = if name == "filter" { 'M' } else { 'N' };
+                    Some((
+                        1,
+                        format!(":{}{}", kind, escape(&glob, Context::Word))
+                    ))
+                }
+                None => None
+            },
+            ("sort", 1) => Some((0, ":O:u".into())),
+            ("word", 2) => number(0).map(|n| (1, format!(":[{}]", n))),
+            ("wordlist", 3) => match (number(0), number(1)) {
+                (Some(start), Some(end)) if start <= end => {
+                    Some((2, format!(":[{}..{}]", start, end)))
+                }
+                _ => None
+            },
+            ("words", 1) => Some((0, ":[#]".into())),
+            ("firstword", 1) => Some((0, ":[1]".into())),
+            ("lastword", 1) => Some((0, ":[-1]".into())),
+            ("dir", 1) => Some((0, ":H:S/$/\\//".into())),
+            ("notdir", 1) => Some((0, ":T".into())),
+            ("basename", 1) => Some((0, ":R".into())),
+            ("addsuffix" | "addprefix", 2) => {
+                let affix = self.fragments(&args[0], Context::Substitute);
+                Some((
+                    1,
+                    if name == "addsuffix" {
+                        format!(":S/$/{}/", affix)
+                    } else {
+                        format!(":S/^/{}/", affix)
+                    }
+                ))
+            }
+            ("realpath", 1) => Some((0, ":tA".into())),
+            ("shell", 1) => Some((0, ":sh".into())),
+            ("foreach", 3) => match static_arg(0).map(str::trim) {
+                Some(var) if !var.is_empty() && !var.contains('@') => {
+                    let body = self.fragments(&args[2], Context::Loop);
+                    if body.contains('@') {
+                        None
+                    } else {
+                        Some((1, format!(":@{}@{}@", var, body)))
+                    }
+                }
+                _ => None
+            },
+            _ => None
+        };
+        match modifier {
+            Some((operand, modifier)) => {
+                let operand = self.operand(&args[operand]);
+                chain(&operand, &modifier)
+            }
+            None => {
+                self.unsupported(format!("`$({})`", name));
+                String::new()
+            }
+        }
+    }
+
+    /// The `${...}` expression whose value is the text of `fragments`, which
+    /// modifiers can be chained onto.
+    fn operand(&mut self, fragments: &[Fragment]) -> String {
+        match fragments {
+            [fragment @ (Fragment::Reference { .. } | Fragment::Call { .. })] => {
+                self.expression(fragment)
+            }
+            fragments => {
+                format!("${{:U{}}}", self.fragments(fragments, Context::Word))
+            }
+        }
+    }
+
+    fn condition(
+        &mut self, condition: &Condition, ctx: &mut SymbolContext
+    ) -> String {
+        match condition {
+            Condition::Eq(lhs, rhs) => {
+                let lhs = self.text(&lhs.emit(ctx), Context::Quoted);
+                let rhs = self.text(&rhs.emit(ctx), Context::Quoted);
+                format!("\"{}\" == \"{}\"", lhs, rhs)
+            }
+            Condition::Def(_) | Condition::Undef(_) => {
+                let line = condition.emit(ctx);
+                let (keyword, name) =
+                    line.split_once(' ').expect("condition has an argument");
+                let name = self.text(name, Context::Word);
+                let negation = if keyword == "ifdef" { "!" } else { "" };
+                format!("{}empty({})", negation, name)
+            }
+        }
+    }
+
+    fn entry(
+        &mut self, entry: &Entry, ctx: &mut SymbolContext, out: &mut String
+    ) {
+        match entry {
+            Entry::Comment(comment) => {
+                writeln!(out, "{}", comment.emit(ctx)).unwrap()
+            }
+            Entry::Newline(_) => writeln!(out).unwrap(),
+            Entry::Assignment(assignment) => {
+                let name = assignment.var.name(ctx).to_string();
+                self.location = format!("in the value of `{}`", name);
+                let value =
+                    self.text(&assignment.value.emit(ctx), Context::Plain);
+                let kind = match assignment.kind {
+                    AssignmentKind::Overwrite => "=",
+                    AssignmentKind::Underwrite => "?=",
+                    AssignmentKind::Simple | AssignmentKind::Immediate => ":=",
+                    AssignmentKind::Append => "+="
+                };
+                writeln!(out, "{} {} {}", name, kind, value).unwrap();
+            }
+            Entry::Include(include) => {
+                let path = include.path().emit(ctx);
+                self.location = format!("in the include of `{}`", path);
+                let path = self.text(&path, Context::Quoted);
+                let directive = if include.is_optional() {
+                    ".-include"
+                } else {
+                    ".include"
+                };
+                writeln!(out, "{} \"{}\"", directive, path).unwrap();
+            }
+            Entry::Rule(rule) => self.rule(&rule.rule.borrow(), ctx, out),
+            Entry::Conditional(conditional) => {
+                let conditional = conditional.conditional.borrow();
+                for (i, branch) in conditional.branches.iter().enumerate() {
+                    match &branch.condition {
+                        Some(condition) => {
+                            self.location = format!(
+                                "in the conditional `{}`",
+                                condition.emit(ctx)
+                            );
+                            let keyword = if i == 0 { ".if" } else { ".elif" };
+                            let condition = self.condition(condition, ctx);
+                            writeln!(out, "{} {}", keyword, condition).unwrap();
+                        }
+                        None => writeln!(out, ".else").unwrap()
+                    }
+                    for entry in &branch.contents {
+                        self.entry(entry, ctx, out);
+                    }
+                }
+                writeln!(out, ".endif").unwrap();
+            }
+            Entry::Namespace(namespace) => {
+                for entry in &namespace.contents {
+                    self.entry(entry, ctx, out);
+                }
+            }
+        }
+    }
+
+    fn rule(&mut self, rule: &Rule, ctx: &mut SymbolContext, out: &mut String) {
+        let target = rule.target.emit(ctx);
+        self.location = format!("in the rule for `{}`", target);
+        if rule.static_pattern.is_some() {
+            self.unsupported("the static pattern".into());
+        }
+        for dependency in &rule.order_only_dependencies {
+            self.unsupported(format!(
+                "the order-only prerequisite `{}`",
+                dependency.emit(ctx)
+            ));
+        }
+        let dependencies = rule
+            .dependencies
+            .iter()
+            .map(|dependency| self.text(&dependency.emit(ctx), Context::Plain))
+            .collect::<Vec<_>>();
+        let target = if target.contains('%') {
+            // A pattern rule from one suffix to another is a suffix rule.
+            match (target.split_once('%'), dependencies.as_slice()) {
+                (Some(("", to)), [dependency])
+                    if to.starts_with('.')
+                        && !to.contains(['%', '/', '$'])
+                        && dependency.strip_prefix('%').is_some_and(
+                            |from| {
+                                from.starts_with('.')
+                                    && !from.contains(['%', '/', '$'])
+                            }
+                        ) =>
+                {
+                    let from = &dependency[1..];
+                    writeln!(out, ".SUFFIXES: {} {}", from, to).unwrap();
+                    writeln!(out, "{}{}:", from, to).unwrap();
+                    self.commands(rule, ctx, out);
+                    return;
+                }
+                _ => {
+                    self.location.clear();
+                    self.unsupported(format!(
+                        "the pattern rule for `{}`",
+                        target
+                    ));
+                    return;
+                }
+            }
+        } else {
+            self.text(&target, Context::Plain)
+        };
+        if rule.is_phony {
+            writeln!(out, ".PHONY: {}", target).unwrap();
+        }
+        write!(out, "{}:", target).unwrap();
+        for dependency in dependencies {
+            write!(out, " {}", dependency).unwrap();
+        }
+        writeln!(out).unwrap();
+        self.commands(rule, ctx, out);
+    }
+
+    fn commands(
+        &mut self, rule: &Rule, ctx: &mut SymbolContext, out: &mut String
+    ) {
+        for command in &rule.commands {
+            for line in rule.emit_command(command, ctx) {
+                let line = self.text(&line, Context::Plain);
+                writeln!(out, "\t{}", line.replace('\n', "\\\n")).unwrap();
+            }
+        }
+    }
+}
+
+/// Appends `modifier` to the `${...}` `expression`.
+fn chain(expression: &str, modifier: &str) -> String {
+    let inner = expression
+        .strip_suffix('}')
+        .expect("expression is enclosed in braces");
+    format!("{}{}}}", inner, modifier)
+}
+
+/// The `:C` modifier replacing each word matching the GNU make pattern `old`
+/// with `new`, in which the first `%` in each stands for the same text.
+fn pattern(old: &str, new: &str) -> String {
+    let (prefix, suffix) = old.split_once('%').expect("pattern contains `%`");
+    let replacement = match new.split_once('%') {
+        Some((before, after)) => format!(
+            "{}\\1{}",
+            escape(before, Context::Substitute),
+            escape(after, Context::Substitute)
+        ),
+        None => escape(new, Context::Substitute)
+    };
+    format!(
+        ":C/^{}(.*){}$/{}/",
+        escape_regex(prefix),
+        escape_regex(suffix),
+        replacement
+    )
+}
+
+pub(crate) fn render(makefile: &Makefile) -> Result<String> {
+    makefile.with_response_files(|| {
+        makefile.validate()?;
+        let mut translator = Translator {
+            location: String::new(),
+            errors: vec![]
+        };
+        let mut result = String::new();
+        let mut ctx = makefile.symbols_mut();
+        for entry in &makefile.contents {
+            translator.entry(entry, &mut ctx, &mut result);
+        }
+        match Error::combine(translator.errors) {
+            Some(error) => Err(error),
+            None => Ok(result)
+        }
+    })
+}
@@ -0,0 +1,331 @@
+//! A builder for the Makefile of a C or C++ project, which derives the
+//! variables and rules every such project repeats.
+
+use crate::{
+    emitter::Emitter,
+    expr::Expr,
+    function::{Function, Substitution}
+};
+
+/// The extensions of C++ source files, which are compiled with `$(CXX)`
+/// instead of `$(CC)`.
+pub const CXX_EXTENSIONS: &[&str] = &["cc", "cpp", "cxx", "c++", "C"];
+
+/// What a [`CProject`] builds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputKind {
+    Executable,
+    /// A static library, `lib<name>.a`, archived with `$(AR)`.
+    StaticLibrary,
+    /// A shared library, `lib<name>.so`, with its soname set.
+    SharedLibrary
+}
+
+/// A C or C++ project, which [`CProject::emit`] turns into variables and
+/// rules:
+/// - `BUILD_TYPE`, which is `debug` unless set, e.g., to `release`, and picks
+///   the optimization and debugging flags;
+/// - `BUILD_DIR`, under which each object file is placed at the path of its
+///   source, along with the dependency file the compiler writes with `-MMD -MP`
+///   so that changing a header remakes the objects including it;
+/// - the phony `all`, which makes the output, and `clean`, which removes every
+///   file made.
+///
+/// The sources are compiled with `$(CC)` and `$(CXX)`, and static libraries
+/// are archived with `$(AR)`, all of which make provides.
+#[derive(Clone)]
+pub struct CProject {
+    name: String,
+    kind: OutputKind,
+    sources: Vec<String>,
+    include_dirs: Vec<String>,
+    defines: Vec<String>,
+    libs: Vec<String>,
+    build_dir: String,
+    soname_version: Option<String>
+}
+
+impl CProject {
+    /// Constructs a project building the executable `name`.
+    pub fn executable<S: AsRef<str>>(name: S) -> Self {
+        Self::new(name, OutputKind::Executable)
+    }
+
+    /// Constructs a project building the static library `lib<name>.a`.
+    pub fn static_library<S: AsRef<str>>(name: S) -> Self {
+        Self::new(name, OutputKind::StaticLibrary)
+    }
+
+    /// Constructs a project building the shared library `lib<name>.so`.
+    pub fn shared_library<S: AsRef<str>>(name: S) -> Self {
+        Self::new(name, OutputKind::SharedLibrary)
+    }
+
+    /// Constructs a project building `name` as `kind`.
+    pub fn new<S: AsRef<str>>(name: S, kind: OutputKind) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+            kind,
+            sources: vec![],
+            include_dirs: vec![],
+            defines: vec![],
+            libs: vec![],
+            build_dir: "build".into(),
+            soname_version: None
+        }
+    }
+
+    /// Adds the source file `path`, which may contain wildcards such as
+    /// `src/*.c`.
+    pub fn source<S: AsRef<str>>(mut self, path: S) -> Self {
+        self.sources.push(path.as_ref().to_string());
+        self
+    }
+
+    /// Adds each source file in `paths`, as with [`CProject::source`].
+    pub fn sources<S: AsRef<str>, I: IntoIterator<Item = S>>(
+        mut self, paths: I
+    ) -> Self {
+        self.sources
+            .extend(paths.into_iter().map(|path| path.as_ref().to_string()));
+        self
+    }
+
+    /// Searches `dir` for included headers.
+    pub fn include_dir<S: AsRef<str>>(mut self, dir: S) -> Self {
+        self.include_dirs.push(dir.as_ref().to_string());
+        self
+    }
+
+    /// Defines the macro `define`, which is either a name or `NAME=value`.
+    pub fn define<S: AsRef<str>>(mut self, define: S) -> Self {
+        self.defines.push(define.as_ref().to_string());
+        self
+    }
+
+    /// Links with the library `lib`, e.g., `m` for `-lm`.
+    pub fn lib<S: AsRef<str>>(mut self, lib: S) -> Self {
+        self.libs.push(lib.as_ref().to_string());
+        self
+    }
+
+    /// Builds under `dir`, which is `build` by default, in a subdirectory for
+    /// each `BUILD_TYPE`.
+    pub fn build_dir<S: AsRef<str>>(mut self, dir: S) -> Self {
+        self.build_dir = dir.as_ref().to_string();
+        self
+    }
+
+    /// Names a shared library `lib<name>.so.<version>` and sets its soname to
+    /// that, so that programs linked with it require that major `version`.
+    pub fn soname_version<S: AsRef<str>>(mut self, version: S) -> Self {
+        self.soname_version = Some(version.as_ref().to_string());
+        self
+    }
+
+    /// What this project builds.
+    pub fn kind(&self) -> OutputKind {
+        self.kind
+    }
+
+    /// The source files of this project, as given.
+    pub fn source_paths(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// The extensions of the source files, in the order they first appear.
+    pub fn extensions(&self) -> Vec<&str> {
+        let mut extensions = vec![];
+        for source in &self.sources {
+            if let Some((_, extension)) = source.rsplit_once('.') {
+                if !extensions.contains(&extension) {
+                    extensions.push(extension);
+                }
+            }
+        }
+        extensions
+    }
+
+    /// Whether any source file is C++, so that the output is linked with
+    /// `$(CXX)`.
+    pub fn is_cxx(&self) -> bool {
+        self.extensions()
+            .iter()
+            .any(|extension| CXX_EXTENSIONS.contains(extension))
+    }
+
+    /// The file name of the output.
+    pub fn output_name(&self) -> String {
+        match self.kind {
+            OutputKind::Executable => self.name.clone(),
+            OutputKind::StaticLibrary => format!("lib{}.a", self.name),
+            OutputKind::SharedLibrary => match &self.soname_version {
+                Some(version) => format!("lib{}.so.{}", self.name, version),
+                None => format!("lib{}.so", self.name)
+            }
+        }
+    }
+
+    /// The preprocessor flags given by the include directories and defines.
+    pub fn preprocessor_flags(&self) -> Vec<String> {
+        self.include_dirs
+            .iter()
+            .map(|dir| format!("-I{}", dir))
+            .chain(self.defines.iter().map(|define| format!("-D{}", define)))
+            .collect()
+    }
+
+    /// Adds the variables and rules building this project to `emitter`.
+    pub fn emit<E: Emitter>(&self, emitter: &mut E) {
+        let extensions = self.extensions();
+        let is_cxx = self.is_cxx();
+        let uses_c = extensions
+            .iter()
+            .any(|extension| !CXX_EXTENSIONS.contains(extension));
+
+        let build_type =
+            emitter.assign_without_overwrite("BUILD_TYPE", "debug");
+        let build_dir = emitter.assign_without_overwrite(
+            "BUILD_DIR",
+            Expr::from(format!("{}/", self.build_dir)).concat(build_type)
+        );
+
+        let mut flag_vars = vec![];
+        if uses_c || !is_cxx {
+            flag_vars.push(emitter.var("CFLAGS"));
+        }
+        if is_cxx {
+            flag_vars.push(emitter.var("CXXFLAGS"));
+        }
+        emitter
+            .branch_tree()
+            .when_eq(build_type, "release", |branch| {
+                for var in &flag_vars {
+                    branch.append(*var, "-O2 -DNDEBUG");
+                }
+            })
+            .otherwise(|branch| {
+                for var in &flag_vars {
+                    branch.append(*var, "-g -O0");
+                }
+            });
+        if self.kind == OutputKind::SharedLibrary {
+            for var in &flag_vars {
+                emitter.append(*var, "-fPIC");
+            }
+        }
+        let mut preprocessor_flags = self.preprocessor_flags();
+        preprocessor_flags.extend(["-MMD".to_string(), "-MP".to_string()]);
+        let cppflags = emitter.append("CPPFLAGS", preprocessor_flags.join(" "));
+        let ldflags = emitter.var("LDFLAGS");
+        let ldlibs = if self.libs.is_empty() {
+            emitter.var("LDLIBS")
+        } else {
+            let libs = self
+                .libs
+                .iter()
+                .map(|lib| format!("-l{}", lib))
+                .collect::<Vec<_>>();
+            emitter.append("LDLIBS", libs.join(" "))
+        };
+
+        let sources = self
+            .sources
+            .iter()
+            .map(|source| {
+                if source.contains(['*', '?', '[']) {
+                    Expr::from(Function::wildcard([Expr::from(source)]))
+                } else {
+                    Expr::from(source)
+                }
+            })
+            .reduce(Expr::then)
+            .unwrap_or_default();
+        let src = emitter.assign("SRC", sources);
+        let object = |extension: &str| {
+            (
+                format!("%.{}", extension),
+                Expr::from(build_dir).concat("/%.o")
+            )
+        };
+        let objects = match extensions.as_slice() {
+            [extension] => {
+                let (pattern, replacement) = object(extension);
+                Expr::from(Substitution::pattern(src, pattern, replacement))
+            }
+            _ => extensions
+                .iter()
+                .map(|extension| {
+                    let (pattern, replacement) = object(extension);
+                    Expr::from(Function::patsubst(
+                        pattern.clone(),
+                        replacement,
+                        Function::filter([Expr::from(pattern)], src)
+                    ))
+                })
+                .reduce(Expr::then)
+                .unwrap_or_default()
+        };
+        let obj = emitter.assign("OBJ", objects);
+        let deps = emitter.assign("DEPS", Substitution::new(obj, ".o", ".d"));
+        let target = emitter.assign(
+            "TARGET",
+            Expr::from(build_dir).concat(format!("/{}", self.output_name()))
+        );
+
+        emitter.newline();
+        emitter.rule("all").phony().dep(target);
+        let target_var = emitter.target_var();
+        let first_dep_var = emitter.first_dep_var();
+        let deps_var = emitter.deps_var();
+        let linker = if is_cxx { "$(CXX)" } else { "$(CC)" };
+        let link = |flags: Option<String>| {
+            flags
+                .into_iter()
+                .fold(Expr::from(linker), Expr::then)
+                .then(ldflags)
+                .then("-o")
+                .then(target_var)
+                .then(deps_var)
+                .then(ldlibs)
+        };
+        let command = match self.kind {
+            OutputKind::Executable => link(None),
+            OutputKind::StaticLibrary => {
+                Expr::from("$(AR) rcs").then(target_var).then(deps_var)
+            }
+            OutputKind::SharedLibrary => link(Some(format!(
+                "-shared -Wl,-soname,{}",
+                self.output_name()
+            )))
+        };
+        emitter.rule(target).dep(obj).cmd(command);
+        for extension in &extensions {
+            let (compiler, flags) = if CXX_EXTENSIONS.contains(extension) {
+                ("$(CXX)", flag_vars[flag_vars.len() - 1])
+            } else {
+                ("$(CC)", flag_vars[0])
+            };
+            let (pattern, object) = object(extension);
+            emitter
+                .rule(object)
+                .dep(pattern)
+                .compiles()
+                .cmd("@mkdir -p $(@D)")
+                .cmd(
+                    Expr::from(compiler)
+                        .then(cppflags)
+                        .then(flags)
+                        .then("-c -o")
+                        .then(target_var)
+                        .then(first_dep_var)
+                );
+        }
+        emitter
+            .rule("clean")
+            .phony()
+            .cmd(Expr::from("rm -f").then(target).then(obj).then(deps));
+        emitter.newline();
+        emitter.include_optional(deps);
+    }
+}
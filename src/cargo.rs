@@ -0,0 +1,309 @@
+//! Rules that drive a Cargo workspace from a Makefile, e.g., the Rust parts of
+//! a repository that is otherwise built with make.
+//!
+//! For each crate in the workspace, [`Workspace::emit`] adds the phony targets
+//! - `build-<crate>` and `test-<crate>`;
+//! - `build-<crate>-<feature>` for each feature other than `default`;
+//! - `test-<crate>-<test>` for each integration test;
+//! - `run-<bin>` for each binary and `example-<example>` for each example,
+//!   enabling the features each requires, or `run-<crate>-<bin>` and
+//!   `example-<crate>-<example>` if another crate has one of the same name.
+//!
+//! Each runs `$(CARGO)`, which is `cargo` unless already set.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf}
+};
+
+use cargo_toml::{Manifest, Product};
+
+use crate::{
+    emitter::Emitter,
+    error::{Error, Result},
+    expr::Expr
+};
+
+/// A crate in a Cargo workspace and what it builds.
+pub struct Crate {
+    pub name: String,
+    /// The directory of the crate, relative to the root of the workspace.
+    pub path: PathBuf,
+    pub bins: Vec<Target>,
+    pub examples: Vec<Target>,
+    pub tests: Vec<Target>,
+    /// The features of the crate other than `default`.
+    pub features: Vec<String>
+}
+
+/// A binary, example, or test of a [`Crate`].
+pub struct Target {
+    pub name: String,
+    /// The features that must be enabled to build the target.
+    pub required_features: Vec<String>
+}
+
+/// The crates of a Cargo workspace, or of a single package.
+pub struct Workspace {
+    manifest_path: PathBuf,
+    crates: Vec<Crate>
+}
+
+impl Workspace {
+    /// Reads the workspace whose root `Cargo.toml` is at `manifest_path`,
+    /// along with every member, expanding `*` and `?` in member paths.
+    pub fn load<P: AsRef<Path>>(manifest_path: P) -> Result<Self> {
+        let manifest_path = manifest_path.as_ref();
+        let root = manifest_path.parent().unwrap_or(Path::new(""));
+        let manifest = read_manifest(manifest_path)?;
+        let mut crates = vec![];
+        if manifest.package.is_some() {
+            crates.push(Crate::new(&manifest, PathBuf::new()));
+        }
+        if let Some(workspace) = &manifest.workspace {
+            let excluded = workspace
+                .exclude
+                .iter()
+                .map(|path| normalize(Path::new(path)))
+                .collect::<Vec<_>>();
+            for member in &workspace.members {
+                for path in expand(root, member)? {
+                    let path = normalize(&path);
+                    if path.as_os_str().is_empty()
+                        || excluded.contains(&path)
+                        || crates.iter().any(|krate| krate.path == path)
+                    {
+                        continue;
+                    }
+                    let manifest =
+                        read_manifest(&root.join(&path).join("Cargo.toml"))?;
+                    if manifest.package.is_some() {
+                        crates.push(Crate::new(&manifest, path));
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            manifest_path: manifest_path.to_path_buf(),
+            crates
+        })
+    }
+
+    /// The crates in this workspace, the root package first, if any, and then
+    /// the members in the order listed.
+    pub fn crates(&self) -> &[Crate] {
+        &self.crates
+    }
+
+    /// Adds the phony targets for every crate in this workspace.
+    pub fn emit<E: Emitter>(&self, emitter: &mut E) {
+        let cargo = emitter.assign_without_overwrite("CARGO", "cargo");
+        let manifest_path = self.manifest_path.display();
+        let command = |subcommand: &str, krate: &str, rest: String| {
+            let mut arguments = format!(
+                "{} --manifest-path {} -p {}",
+                subcommand, manifest_path, krate
+            );
+            if !rest.is_empty() {
+                arguments.push(' ');
+                arguments.push_str(&rest);
+            }
+            Expr::from(cargo).then(arguments)
+        };
+        let shared_bins =
+            shared_names(self.crates.iter().map(|krate| &krate.bins));
+        let shared_examples =
+            shared_names(self.crates.iter().map(|krate| &krate.examples));
+        let qualified = |prefix: &str,
+                         krate: &str,
+                         target: &str,
+                         shared: &HashSet<&str>| {
+            if shared.contains(target) {
+                format!("{}-{}-{}", prefix, krate, target)
+            } else {
+                format!("{}-{}", prefix, target)
+            }
+        };
+        for krate in &self.crates {
+            let name = &krate.name;
+            emitter.newline();
+            emitter.comment(format!("Crate `{}`", name));
+            emitter.rule(format!("build-{}", name)).phony().cmd(command(
+                "build",
+                name,
+                String::new()
+            ));
+            for feature in &krate.features {
+                emitter
+                    .rule(format!("build-{}-{}", name, feature))
+                    .phony()
+                    .cmd(command(
+                        "build",
+                        name,
+                        format!("--features {}", feature)
+                    ));
+            }
+            emitter.rule(format!("test-{}", name)).phony().cmd(command(
+                "test",
+                name,
+                String::new()
+            ));
+            for test in &krate.tests {
+                emitter
+                    .rule(format!("test-{}-{}", name, test.name))
+                    .phony()
+                    .cmd(command("test", name, test.arguments("--test")));
+            }
+            for bin in &krate.bins {
+                emitter
+                    .rule(qualified("run", name, &bin.name, &shared_bins))
+                    .phony()
+                    .cmd(command("run", name, bin.arguments("--bin")));
+            }
+            for example in &krate.examples {
+                emitter
+                    .rule(qualified(
+                        "example",
+                        name,
+                        &example.name,
+                        &shared_examples
+                    ))
+                    .phony()
+                    .cmd(command("run", name, example.arguments("--example")));
+            }
+        }
+    }
+}
+
+/// The names of the targets that more than one of `lists` has.
+fn shared_names<'a>(
+    lists: impl Iterator<Item = &'a Vec<Target>>
+) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    let mut shared = HashSet::new();
+    for list in lists {
+        let names = list
+            .iter()
+            .map(|target| target.name.as_str())
+            .collect::<HashSet<_>>();
+        for name in names {
+            if !seen.insert(name) {
+                shared.insert(name);
+            }
+        }
+    }
+    shared
+}
+
+impl Crate {
+    fn new(manifest: &Manifest, path: PathBuf) -> Self {
+        let targets = |products: &[Product]| {
+            products
+                .iter()
+                .filter_map(|product| {
+                    Some(Target {
+                        name: product.name.clone()?,
+                        required_features: product.required_features.clone()
+                    })
+                })
+                .collect()
+        };
+        Self {
+            name: manifest.package().name().to_string(),
+            path,
+            bins: targets(&manifest.bin),
+            examples: targets(&manifest.example),
+            tests: targets(&manifest.test),
+            features: manifest
+                .features
+                .keys()
+                .filter(|feature| *feature != "default")
+                .cloned()
+                .collect()
+        }
+    }
+}
+
+impl Target {
+    /// The arguments to Cargo selecting this target with `flag`.
+    fn arguments(&self, flag: &str) -> String {
+        if self.required_features.is_empty() {
+            format!("{} {}", flag, self.name)
+        } else {
+            format!(
+                "{} {} --features {}",
+                flag,
+                self.name,
+                self.required_features.join(",")
+            )
+        }
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<Manifest> {
+    Manifest::from_path(path).map_err(|error| Error::Cargo {
+        path: path.display().to_string(),
+        error
+    })
+}
+
+/// `path` without `.` components or a trailing separator.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != std::path::Component::CurDir)
+        .collect()
+}
+
+/// The directories relative to `root` matched by the member path `pattern`,
+/// in which `*` and `?` match within a single component, sorted by name.
+fn expand(root: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !component.contains(['*', '?']) {
+            for path in &mut paths {
+                path.push(component.as_ref());
+            }
+            continue;
+        }
+        let mut matches = vec![];
+        for path in &paths {
+            let mut entries = fs::read_dir(root.join(path))?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| matches_wildcard(&component, name))
+                .collect::<Vec<_>>();
+            entries.sort();
+            matches.extend(entries.into_iter().map(|name| path.join(name)));
+        }
+        paths = matches;
+    }
+    Ok(paths
+        .into_iter()
+        .filter(|path| root.join(path).join("Cargo.toml").is_file())
+        .collect())
+}
+
+/// Whether `name` matches `pattern`, in which `*` matches any text and `?`
+/// matches any one character.
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    // Whether the first `j` characters of `name` match the pattern so far.
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for c in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match c {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
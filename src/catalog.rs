@@ -0,0 +1,90 @@
+use crate::version::Version;
+
+/// The signature of a builtin GNU make function. See section
+/// [8](https://www.gnu.org/software/make/manual/make.html#Functions).
+#[derive(PartialEq, Eq, Debug)]
+pub struct FunctionSpec {
+    /// The name by which the function is called.
+    pub name: &'static str,
+    /// The minimum number of comma-separated arguments.
+    pub min_args: usize,
+    /// The maximum number of comma-separated arguments, or `None` if it takes
+    /// any number. A function with a maximum of one takes the rest of the
+    /// call, commas included, as its only argument.
+    pub max_args: Option<usize>,
+    /// The first version of GNU make providing the function.
+    pub since: Version
+}
+
+impl FunctionSpec {
+    const fn new(
+        name: &'static str, min_args: usize, max_args: Option<usize>,
+        since: Version
+    ) -> Self {
+        Self {
+            name,
+            min_args,
+            max_args,
+            since
+        }
+    }
+
+    /// Whether the function can be called with `count` arguments.
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min_args && self.max_args.is_none_or(|max| count <= max)
+    }
+}
+
+const V3_78: Version = Version::OLDEST;
+const V3_80: Version = Version::new(3, 80);
+const V3_81: Version = Version::new(3, 81);
+const V4_0: Version = Version::new(4, 0);
+const V4_4: Version = Version::new(4, 4);
+
+/// Every builtin function of GNU make 4.4.
+pub const FUNCTIONS: &[FunctionSpec] = &[
+    FunctionSpec::new("subst", 3, Some(3), V3_78),
+    FunctionSpec::new("patsubst", 3, Some(3), V3_78),
+    FunctionSpec::new("strip", 0, Some(1), V3_78),
+    FunctionSpec::new("findstring", 2, Some(2), V3_78),
+    FunctionSpec::new("filter", 2, Some(2), V3_78),
+    FunctionSpec::new("filter-out", 2, Some(2), V3_78),
+    FunctionSpec::new("sort", 0, Some(1), V3_78),
+    FunctionSpec::new("word", 2, Some(2), V3_78),
+    FunctionSpec::new("wordlist", 3, Some(3), V3_78),
+    FunctionSpec::new("words", 0, Some(1), V3_78),
+    FunctionSpec::new("firstword", 0, Some(1), V3_78),
+    FunctionSpec::new("lastword", 0, Some(1), V3_81),
+    FunctionSpec::new("dir", 0, Some(1), V3_78),
+    FunctionSpec::new("notdir", 0, Some(1), V3_78),
+    FunctionSpec::new("suffix", 0, Some(1), V3_78),
+    FunctionSpec::new("basename", 0, Some(1), V3_78),
+    FunctionSpec::new("addsuffix", 2, Some(2), V3_78),
+    FunctionSpec::new("addprefix", 2, Some(2), V3_78),
+    FunctionSpec::new("join", 2, Some(2), V3_78),
+    FunctionSpec::new("wildcard", 0, Some(1), V3_78),
+    FunctionSpec::new("realpath", 0, Some(1), V3_81),
+    FunctionSpec::new("abspath", 0, Some(1), V3_81),
+    FunctionSpec::new("error", 0, Some(1), V3_78),
+    FunctionSpec::new("warning", 0, Some(1), V3_78),
+    FunctionSpec::new("info", 0, Some(1), V3_81),
+    FunctionSpec::new("shell", 0, Some(1), V3_78),
+    FunctionSpec::new("origin", 0, Some(1), V3_78),
+    FunctionSpec::new("flavor", 0, Some(1), V3_81),
+    FunctionSpec::new("let", 3, Some(3), V4_4),
+    FunctionSpec::new("foreach", 3, Some(3), V3_78),
+    FunctionSpec::new("file", 1, Some(2), V4_0),
+    FunctionSpec::new("call", 1, None, V3_78),
+    FunctionSpec::new("value", 0, Some(1), V3_80),
+    FunctionSpec::new("eval", 0, Some(1), V3_80),
+    FunctionSpec::new("if", 2, Some(3), V3_78),
+    FunctionSpec::new("or", 1, None, V3_81),
+    FunctionSpec::new("and", 1, None, V3_81),
+    FunctionSpec::new("intcmp", 2, Some(5), V4_4),
+    FunctionSpec::new("guile", 0, Some(1), V4_0)
+];
+
+/// Looks up the builtin function called `name`.
+pub fn lookup(name: &str) -> Option<&'static FunctionSpec> {
+    FUNCTIONS.iter().find(|spec| spec.name == name)
+}
@@ -0,0 +1,664 @@
+//! Compilation databases, `compile_commands.json`, which tools like clangd
+//! read to learn how each source file is compiled.
+//!
+//! [`Makefile::compile_commands`] lists a command for each target made by a
+//! rule marked with [`RuleRef::compiles`], such as the rules that
+//! [`CProject`] emits. Pattern rules are instantiated for every target in the
+//! Makefile they match, and variables are expanded with the values the
+//! Makefile assigns them.
+//!
+//! In the other direction, [`to_makefile`] starts a Makefile from the
+//! database of a project built some other way, which [`parse`] reads with the
+//! `json` feature.
+//!
+//! [`RuleRef::compiles`]: crate::rule::RuleRef::compiles
+//! [`CProject`]: crate::c_project::CProject
+
+#[cfg(feature = "json")]
+use std::fs;
+use std::{collections::HashSet, fmt::Write, path::Path};
+
+#[cfg(feature = "json")]
+use crate::error::Error;
+use crate::{
+    assignment::AssignmentKind,
+    c_project::CXX_EXTENSIONS,
+    emitter::Emitter,
+    error::Result,
+    eval::Env,
+    expr::Expr,
+    makefile::Makefile,
+    plan::{AssumeExists, RecipeLine, Rules},
+    var::Variable
+};
+
+/// The variables make defines for compiling and linking before reading a
+/// Makefile, which `?=` therefore does not assign.
+const MAKE_DEFAULTS: &[(&str, &str)] =
+    &[("CC", "cc"), ("CXX", "g++"), ("AR", "ar")];
+
+/// How a single source file is compiled, as an entry in a compilation
+/// database.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CompileCommand {
+    /// The directory the command is run in.
+    pub directory: String,
+    /// The source file, relative to `directory` unless absolute.
+    pub file: String,
+    /// The file the command makes, relative to `directory` unless absolute.
+    pub output: String,
+    /// The command, split into words as the shell would.
+    pub arguments: Vec<String>
+}
+
+/// Options for [`Makefile::compile_commands`].
+pub struct CompileCommandsOptions {
+    directory: String,
+    env: Env
+}
+
+impl CompileCommandsOptions {
+    /// Constructs options for commands run in `directory`, which should be an
+    /// absolute path.
+    pub fn new<S: AsRef<str>>(directory: S) -> Self {
+        Self {
+            directory: directory.as_ref().to_string(),
+            env: Env::new()
+        }
+    }
+
+    /// Uses what `hook` returns for a command as the output of `$(shell)` on
+    /// that command.
+    pub fn with_shell<F: Fn(&str) -> String + 'static>(
+        mut self, hook: F
+    ) -> Self {
+        self.env = self.env.with_shell(hook);
+        self
+    }
+
+    /// Uses the files `hook` returns for a pattern as the files matching that
+    /// pattern in `$(wildcard)`, e.g., for sources given as `src/*.c`.
+    pub fn with_wildcard<F: Fn(&str) -> Vec<String> + 'static>(
+        mut self, hook: F
+    ) -> Self {
+        self.env = self.env.with_wildcard(hook);
+        self
+    }
+}
+
+pub(crate) fn collect(
+    makefile: &Makefile, options: CompileCommandsOptions
+) -> Result<Vec<CompileCommand>> {
+    let mut env = options.env;
+    for (name, value) in MAKE_DEFAULTS {
+        env.assign(name, AssignmentKind::Overwrite, value)?;
+    }
+    let rules = Rules::load_with(makefile, env)?;
+    let mut result = vec![];
+    for resolved in rules.closure(&rules.targets, &AssumeExists)? {
+        if !resolved.compiles || resolved.recipe.is_empty() {
+            continue;
+        }
+        let Some(file) = resolved.prerequisites.first() else {
+            continue;
+        };
+        let lines =
+            rules.expand_recipe(&resolved, &AssumeExists, &HashSet::new())?;
+        let commands = lines
+            .iter()
+            .map(|line| RecipeLine::parse(line).command.replace('\n', " "))
+            .filter(|command| !command.trim().is_empty())
+            .map(|command| split_words(&command))
+            .collect::<Vec<_>>();
+        // The compiling line is the one naming the source, e.g., not the
+        // `mkdir -p $(@D)` before it.
+        let Some(arguments) = commands
+            .iter()
+            .find(|words| words.contains(file))
+            .or(commands.last())
+        else {
+            continue;
+        };
+        result.push(CompileCommand {
+            directory: options.directory.clone(),
+            file: file.clone(),
+            output: resolved.target.clone(),
+            arguments: arguments.clone()
+        });
+    }
+    Ok(result)
+}
+
+/// Splits `command` into words as the shell would, removing quotes and
+/// backslashes.
+fn split_words(command: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|c| *c != '\''));
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => word.push('\\')
+                        },
+                        c => word.push(c)
+                    }
+                }
+            }
+            '\\' => {
+                word.get_or_insert_with(String::new).extend(chars.next());
+            }
+            c => word.get_or_insert_with(String::new).push(c)
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(&mut result, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// The compilation database listing `commands`, as the contents of a
+/// `compile_commands.json`.
+pub fn to_json(commands: &[CompileCommand]) -> String {
+    if commands.is_empty() {
+        return "[]\n".into();
+    }
+    let mut result = String::from("[\n");
+    for (i, command) in commands.iter().enumerate() {
+        let arguments = command
+            .arguments
+            .iter()
+            .map(|argument| json_string(argument))
+            .collect::<Vec<_>>();
+        writeln!(&mut result, "  {{").unwrap();
+        writeln!(
+            &mut result,
+            "    \"directory\": {},",
+            json_string(&command.directory)
+        )
+        .unwrap();
+        writeln!(&mut result, "    \"file\": {},", json_string(&command.file))
+            .unwrap();
+        writeln!(
+            &mut result,
+            "    \"output\": {},",
+            json_string(&command.output)
+        )
+        .unwrap();
+        writeln!(&mut result, "    \"arguments\": [{}]", arguments.join(", "))
+            .unwrap();
+        let separator = if i + 1 < commands.len() { "," } else { "" };
+        writeln!(&mut result, "  }}{}", separator).unwrap();
+    }
+    result.push_str("]\n");
+    result
+}
+
+/// The flags whose value may be the next word, e.g., `-I include`.
+const FLAGS_WITH_VALUES: &[&str] = &[
+    "-I",
+    "-D",
+    "-U",
+    "-include",
+    "-imacros",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-isysroot",
+    "-x",
+    "-Xclang",
+    "-Xpreprocessor",
+    "-arch",
+    "-target"
+];
+
+/// The flags naming what a single command makes, which the rules give
+/// instead.
+const OUTPUT_FLAGS: &[&str] = &["-o", "-MF", "-MT", "-MQ"];
+
+/// The prefixes of the flags that belong in `CPPFLAGS`.
+const PREPROCESSOR_FLAGS: &[&str] = &[
+    "-I",
+    "-D",
+    "-U",
+    "-M",
+    "-include",
+    "-imacros",
+    "-isystem",
+    "-iquote",
+    "-idirafter"
+];
+
+/// The command of a [`CompileCommand`] without what differs between source
+/// files, so that commands which agree on it can share a rule.
+struct Group {
+    compiler: String,
+    is_cxx: bool,
+    /// The flags as make text, each with its value if given as a separate
+    /// word.
+    flags: Vec<String>,
+    /// The source and object files, relative to the common directory.
+    files: Vec<(String, String)>
+}
+
+/// Reads the compilation database `text` from `file`. Entries that give a
+/// `command` instead of `arguments` are split into words as the shell would,
+/// and entries without an `output` are given the file `-o` names, if any.
+#[cfg(feature = "json")]
+pub fn parse(text: &str, file: &str) -> Result<Vec<CompileCommand>> {
+    #[derive(serde::Deserialize)]
+    struct Entry {
+        directory: String,
+        file: String,
+        #[serde(default)]
+        arguments: Option<Vec<String>>,
+        #[serde(default)]
+        command: Option<String>,
+        #[serde(default)]
+        output: Option<String>
+    }
+
+    let entries: Vec<Entry> = serde_json::from_str(text).map_err(|error| {
+        let message = error.to_string();
+        Error::Parse {
+            file: file.to_string(),
+            line: error.line(),
+            column: error.column(),
+            message: message
+                .rsplit_once(" at line ")
+                .map_or(message.as_str(), |(message, _)| message)
+                .to_string()
+        }
+    })?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let arguments = match (entry.arguments, entry.command) {
+                (Some(arguments), _) => arguments,
+                (None, command) => {
+                    split_words(command.as_deref().unwrap_or_default())
+                }
+            };
+            let output = entry.output.unwrap_or_else(|| {
+                output_flag(&arguments).unwrap_or_else(|| {
+                    let stem = Path::new(&entry.file)
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy();
+                    format!("{}.o", stem)
+                })
+            });
+            CompileCommand {
+                directory: entry.directory,
+                file: entry.file,
+                output,
+                arguments
+            }
+        })
+        .collect())
+}
+
+/// Reads the compilation database at `path`, as with [`parse`].
+#[cfg(feature = "json")]
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<CompileCommand>> {
+    let path = path.as_ref();
+    parse(&fs::read_to_string(path)?, &path.display().to_string())
+}
+
+/// The file `-o` names in `arguments`, if any.
+#[cfg(feature = "json")]
+fn output_flag(arguments: &[String]) -> Option<String> {
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if argument == "-o" {
+            return arguments.next().cloned();
+        } else if let Some(output) = argument.strip_prefix("-o") {
+            return Some(output.to_string());
+        }
+    }
+    None
+}
+
+/// Starts a Makefile building the object files in `commands`, relative to the
+/// directory of the first:
+/// - the commands are grouped by compiler and flags, with a pattern rule for
+///   each group, or a static pattern rule if another group would have the same
+///   pattern, or a rule for each file if the object files are not named alike;
+/// - the preprocessor flags every group starts with go in `CPPFLAGS`, and the
+///   other flags every C or C++ group follows them with in `CFLAGS` or
+///   `CXXFLAGS`, leaving the rest in a variable for each group, so that each
+///   command keeps its flags in order;
+/// - the phony `all` makes `$(TARGET)`, named after the directory, by linking
+///   `$(OBJ)`, which is only a stub, as a compilation database does not say how
+///   the objects are linked, and `clean` removes them.
+///
+/// The compiling rules are marked with [`RuleRef::compiles`], so
+/// [`Makefile::compile_commands`] on the result lists the same commands.
+///
+/// [`RuleRef::compiles`]: crate::rule::RuleRef::compiles
+pub fn to_makefile(commands: &[CompileCommand]) -> Makefile {
+    let base = commands
+        .first()
+        .map_or("", |command| command.directory.as_str());
+    let mut groups: Vec<Group> = vec![];
+    for command in commands {
+        let Some((compiler, arguments)) = command.arguments.split_first()
+        else {
+            continue;
+        };
+        let file = relative_to(base, &command.directory, &command.file);
+        let output = relative_to(base, &command.directory, &command.output);
+        let is_cxx = extension(&file)
+            .is_some_and(|extension| CXX_EXTENSIONS.contains(&extension));
+        let sources = [
+            command.file.clone(),
+            Path::new(&command.directory)
+                .join(&command.file)
+                .display()
+                .to_string()
+        ];
+        let flags = flags(arguments, &sources);
+        match groups.iter_mut().find(|group| {
+            group.compiler == *compiler
+                && group.is_cxx == is_cxx
+                && group.flags == flags
+        }) {
+            Some(group) => group.files.push((file, output)),
+            None => groups.push(Group {
+                compiler: compiler.clone(),
+                is_cxx,
+                flags,
+                files: vec![(file, output)]
+            })
+        }
+    }
+
+    let mut makefile = Makefile::new();
+    let mut compilers: Vec<(&str, Variable)> = vec![];
+    let (mut cc, mut cxx) = (None, None);
+    for group in &groups {
+        if compilers.iter().all(|(other, _)| *other != group.compiler) {
+            let (name, first) = if group.is_cxx {
+                ("CXX", &mut cxx)
+            } else {
+                ("CC", &mut cc)
+            };
+            let var = match first {
+                Some(_) => makefile.fresh(name),
+                None => *first.insert(makefile.var(name))
+            };
+            makefile.assign(var, make_text(&group.compiler));
+            compilers.push((&group.compiler, var));
+        }
+    }
+
+    // The flags from `start` that every group begins with, up to the first
+    // not to `include`.
+    let common = |groups: &mut dyn Iterator<Item = &Group>,
+                  start: usize,
+                  include: &dyn Fn(&str) -> bool| {
+        let Some(first) = groups.next() else {
+            return vec![];
+        };
+        let mut common = first.flags[start..]
+            .iter()
+            .take_while(|flag| include(flag))
+            .cloned()
+            .collect::<Vec<_>>();
+        for group in groups {
+            let len = common
+                .iter()
+                .zip(&group.flags[start..])
+                .take_while(|(flag, other)| flag == other)
+                .count();
+            common.truncate(len);
+        }
+        common
+    };
+    let cppflags = common(&mut groups.iter(), 0, &is_preprocessor_flag);
+    let cflags = common(
+        &mut groups.iter().filter(|group| !group.is_cxx),
+        cppflags.len(),
+        &|flag| !is_preprocessor_flag(flag)
+    );
+    let cxxflags = common(
+        &mut groups.iter().filter(|group| group.is_cxx),
+        cppflags.len(),
+        &|flag| !is_preprocessor_flag(flag)
+    );
+    let mut flag_var = |name: &str, flags: &[String]| {
+        if flags.is_empty() {
+            makefile.var(name)
+        } else {
+            makefile.assign(name, flags.join(" "))
+        }
+    };
+    let cppflags_var = flag_var("CPPFLAGS", &cppflags);
+    let cflags_var = flag_var("CFLAGS", &cflags);
+    let cxxflags_var = flag_var("CXXFLAGS", &cxxflags);
+    let ldflags = makefile.var("LDFLAGS");
+    let ldlibs = makefile.var("LDLIBS");
+    let group_flags = groups
+        .iter()
+        .map(|group| {
+            let shared = if group.is_cxx { &cxxflags } else { &cflags };
+            let rest = &group.flags[cppflags.len() + shared.len()..];
+            (!rest.is_empty()).then(|| {
+                let var = makefile.fresh("FLAGS");
+                makefile.assign(var, rest.join(" "))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let objects = groups
+        .iter()
+        .flat_map(|group| &group.files)
+        .map(|(_, object)| make_text(object))
+        .collect::<Vec<_>>();
+    let obj = makefile.assign("OBJ", objects.join(" "));
+    let name = Path::new(base)
+        .file_name()
+        .map_or("a.out".into(), |name| name.to_string_lossy());
+    let target = makefile.assign("TARGET", make_text(&name));
+
+    makefile.newline();
+    makefile.rule("all").phony().dep(target);
+    let linker = cxx
+        .or(compilers.first().map(|(_, var)| *var))
+        .map_or(Expr::from("$(CC)"), Expr::from);
+    makefile.comment(
+        "The compilation database does not record how the objects are linked."
+    );
+    makefile
+        .rule(target)
+        .dep(obj)
+        .cmd(linker.then(ldflags).then("-o $@ $^").then(ldlibs));
+
+    // The pattern of each object file and its source, if every object file in
+    // the group is named like its source.
+    let patterns = groups
+        .iter()
+        .map(|group| {
+            let mut patterns = group.files.iter().map(|(source, object)| {
+                let extension = extension(source)?;
+                let stem = &source[..source.len() - extension.len() - 1];
+                let start = object.rfind(stem)?;
+                Some((
+                    format!(
+                        "{}%{}",
+                        &object[..start],
+                        &object[start + stem.len()..]
+                    ),
+                    format!("%.{}", extension)
+                ))
+            });
+            let first = patterns.next()??;
+            patterns
+                .all(|pattern| pattern.as_ref() == Some(&first))
+                .then_some(first)
+        })
+        .collect::<Vec<_>>();
+    for (i, group) in groups.iter().enumerate() {
+        let compiler = compilers
+            .iter()
+            .find(|(compiler, _)| *compiler == group.compiler)
+            .unwrap()
+            .1;
+        let shared = if group.is_cxx {
+            cxxflags_var
+        } else {
+            cflags_var
+        };
+        let command = || {
+            group_flags[i]
+                .into_iter()
+                .fold(
+                    Expr::from(compiler).then(cppflags_var).then(shared),
+                    Expr::then
+                )
+                .then("-c -o $@ $<")
+        };
+        let makes_dirs =
+            group.files.iter().any(|(_, object)| object.contains('/'));
+        let rules = match &patterns[i] {
+            Some((target, source)) => {
+                let is_shared = patterns
+                    .iter()
+                    .filter(|other| other.as_ref() == patterns[i].as_ref())
+                    .count()
+                    > 1;
+                if is_shared {
+                    let objects = group
+                        .files
+                        .iter()
+                        .map(|(_, object)| make_text(object))
+                        .collect::<Vec<_>>();
+                    vec![makefile
+                        .rule(objects.join(" "))
+                        .static_pattern(make_text(target))
+                        .dep(make_text(source))]
+                } else {
+                    vec![makefile
+                        .rule(make_text(target))
+                        .dep(make_text(source))]
+                }
+            }
+            None => group
+                .files
+                .iter()
+                .map(|(source, object)| {
+                    makefile.rule(make_text(object)).dep(make_text(source))
+                })
+                .collect()
+        };
+        for rule in rules {
+            rule.set_compiles();
+            if makes_dirs {
+                rule.add_cmd("@mkdir -p $(@D)");
+            }
+            rule.add_cmd(command());
+        }
+    }
+    makefile
+        .rule("clean")
+        .phony()
+        .cmd(Expr::from("rm -f").then(target).then(obj));
+    makefile
+}
+
+/// `path`, which is relative to `directory` unless absolute, made relative to
+/// `base` if it is within it.
+fn relative_to(base: &str, directory: &str, path: &str) -> String {
+    let absolute = Path::new(directory).join(path);
+    match absolute.strip_prefix(base) {
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => absolute.display().to_string()
+    }
+}
+
+/// The extension of the file `path`, if any.
+fn extension(path: &str) -> Option<&str> {
+    let name = &path[path.rfind('/').map_or(0, |i| i + 1)..];
+    name.rsplit_once('.').map(|(_, extension)| extension)
+}
+
+/// The flags in `arguments` as make text, each with its value if given as a
+/// separate word, without `-c`, the outputs, and the `sources`.
+fn flags(arguments: &[String], sources: &[String]) -> Vec<String> {
+    let mut flags = vec![];
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if argument == "-c" || sources.contains(argument) {
+            continue;
+        }
+        if OUTPUT_FLAGS.contains(&argument.as_str()) {
+            arguments.next();
+            continue;
+        }
+        if OUTPUT_FLAGS.iter().any(|flag| argument.starts_with(flag)) {
+            continue;
+        }
+        let mut flag = make_text(argument);
+        if FLAGS_WITH_VALUES.contains(&argument.as_str()) {
+            if let Some(value) = arguments.next() {
+                flag.push(' ');
+                flag.push_str(&make_text(value));
+            }
+        }
+        flags.push(flag);
+    }
+    flags
+}
+
+/// Whether the flag, as make text, belongs in `CPPFLAGS`.
+fn is_preprocessor_flag(flag: &str) -> bool {
+    let flag = flag.trim_start_matches('\'');
+    PREPROCESSOR_FLAGS
+        .iter()
+        .any(|prefix| flag.starts_with(prefix))
+}
+
+/// `word` quoted for the shell if needed and escaped for make.
+fn make_text(word: &str) -> String {
+    let is_plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c));
+    let quoted = if is_plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    };
+    quoted.replace('$', "$$").replace('#', "\\#")
+}
@@ -1,8 +1,10 @@
 use std::fmt::Write;
 
 use crate::{
-    emittable::{Emittable, EmittableRef},
+    emittable::Emittable,
     emitter::EmittableContainer,
+    entry::Entry,
+    error::Error,
     expr::Expr,
     rrc::{rrc, RRC},
     symbol_context::{Resolvable, SymbolContext},
@@ -22,18 +24,30 @@ impl Emittable for Condition {
                 format!("ifeq ({}, {})", lhs.emit(ctx), rhs.emit(ctx))
             }
             Condition::Def(var) => {
-                format!("ifdef $({})", var.name(ctx))
+                format!("ifdef {}", var.name(ctx))
             }
             Condition::Undef(var) => {
-                format!("ifndef $({})", var.name(ctx))
+                format!("ifndef {}", var.name(ctx))
+            }
+        }
+    }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        match self {
+            Condition::Eq(lhs, rhs) => {
+                lhs.validate(ctx, errors);
+                rhs.validate(ctx, errors);
+            }
+            Condition::Def(var) | Condition::Undef(var) => {
+                var.validate(ctx, errors);
             }
         }
     }
 }
 
 pub struct Branch {
-    condition: Option<Condition>,
-    contents: Vec<EmittableRef>,
+    pub(crate) condition: Option<Condition>,
+    pub(crate) contents: Vec<Entry>,
     ctx: RRC<SymbolContext>
 }
 
@@ -48,7 +62,7 @@ impl Branch {
 }
 
 impl EmittableContainer for Branch {
-    fn add(&mut self, e: EmittableRef) {
+    fn add(&mut self, e: Entry) {
         self.contents.push(e);
     }
 
@@ -60,7 +74,7 @@ impl EmittableContainer for Branch {
 /// A Makefile conditional, e.g., `ifdef`, containing any number of branches,
 /// the last of which may be the only `else` in the conditional.
 pub(crate) struct Conditional {
-    branches: Vec<Branch>
+    pub(crate) branches: Vec<Branch>
 }
 
 impl Conditional {
@@ -89,18 +103,42 @@ impl Emittable for Conditional {
                 result.push('\n');
             }
             for content in &branch.contents {
+                if content.is_empty() {
+                    continue;
+                }
                 writeln!(&mut result, "{}", content.emit(ctx)).unwrap();
             }
         }
         write!(&mut result, "endif").unwrap();
         result
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        if self.branches.is_empty() {
+            errors.push(Error::EmptyConditional);
+        }
+        for (i, branch) in self.branches.iter().enumerate() {
+            if branch.condition.is_none() {
+                if i == 0 {
+                    errors.push(Error::ElseWithoutCondition);
+                } else if i + 1 < self.branches.len() {
+                    errors.push(Error::BranchAfterElse);
+                }
+            }
+            if let Some(condition) = &branch.condition {
+                condition.validate(ctx, errors);
+            }
+            for content in &branch.contents {
+                content.validate(ctx, errors);
+            }
+        }
+    }
 }
 
 /// Builder for a Makefile conditional.
 #[derive(Clone)]
 pub struct ConditionalRef {
-    conditional: RRC<Conditional>,
+    pub(crate) conditional: RRC<Conditional>,
     ctx: RRC<SymbolContext>
 }
 
@@ -120,7 +158,8 @@ impl ConditionalRef {
         self.build_conditional(Some(Condition::Eq(lhs.into(), rhs.into())), f)
     }
 
-    /// Appends an `ifeq` clause to the conditional.
+    /// Appends an `ifdef` clause, which holds if `var` has a non-empty value,
+    /// to the conditional.
     pub fn when_def<V: Resolvable, F: FnOnce(&mut Branch)>(
         self, var: V, f: F
     ) -> ConditionalRef {
@@ -128,7 +167,8 @@ impl ConditionalRef {
         self.build_conditional(Some(Condition::Def(var)), f)
     }
 
-    /// Appends an `ifundef` clause to the conditional.
+    /// Appends an `ifndef` clause, which holds if `var` has an empty value or
+    /// none, to the conditional.
     pub fn when_undef<V: Resolvable, F: FnOnce(&mut Branch)>(
         self, var: V, f: F
     ) -> ConditionalRef {
@@ -142,7 +182,7 @@ impl ConditionalRef {
     }
 
     /// Adds a branch to the conditional.
-    fn build_conditional<F: FnOnce(&mut Branch)>(
+    pub(crate) fn build_conditional<F: FnOnce(&mut Branch)>(
         self, condition: Option<Condition>, f: F
     ) -> ConditionalRef {
         let mut branch = Branch::new(condition, self.ctx.clone());
@@ -156,4 +196,8 @@ impl Emittable for ConditionalRef {
     fn emit(&self, ctx: &mut SymbolContext) -> String {
         self.conditional.borrow().emit(ctx)
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        self.conditional.borrow().validate(ctx, errors);
+    }
 }
@@ -0,0 +1,153 @@
+use crate::{
+    emittable::Emittable,
+    entry::Entry,
+    error::Error,
+    symbol_context::{Resolvable, SymbolContext},
+    syntax::{self, Fragment}
+};
+
+/// The variant of make that a Makefile is written for.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Dialect {
+    /// GNU make, every feature of which `makemake` can emit.
+    #[default]
+    Gnu,
+    /// make as specified by POSIX.1-2024, which has no functions, no
+    /// conditionals, and no pattern or order-only prerequisites. See
+    /// [the specification](https://pubs.opengroup.org/onlinepubs/9799919799/utilities/make.html).
+    Posix2024
+}
+
+impl Dialect {
+    /// The name of the variant of make, e.g., in error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gnu => "GNU make",
+            Self::Posix2024 => "POSIX make"
+        }
+    }
+}
+
+/// Reports to `errors` a construct for each function called in `text`, which
+/// is found `location`.
+fn check_text(
+    text: &str, location: &str, dialect: Dialect, errors: &mut Vec<Error>
+) {
+    let Ok(fragments) = syntax::parse(text) else {
+        return;
+    };
+    syntax::walk_all(&fragments, &mut |fragment| {
+        if let Fragment::Call { spec, .. } = fragment {
+            errors.push(unsupported(
+                format!("`$({})` {}", spec.name, location),
+                dialect
+            ));
+        }
+    });
+}
+
+fn unsupported(construct: String, dialect: Dialect) -> Error {
+    Error::Unsupported {
+        construct,
+        backend: dialect.name()
+    }
+}
+
+/// Reports to `errors` every construct in `contents` that make in `dialect`
+/// does not support and that cannot be lowered to one it does.
+pub(crate) fn check(
+    contents: &[Entry], dialect: Dialect, ctx: &mut SymbolContext,
+    errors: &mut Vec<Error>
+) {
+    if dialect == Dialect::Gnu {
+        return;
+    }
+    for entry in contents {
+        entry.walk(false, &mut |entry, _| {
+            check_entry(entry, dialect, ctx, errors)
+        });
+    }
+}
+
+fn check_entry(
+    entry: &Entry, dialect: Dialect, ctx: &mut SymbolContext,
+    errors: &mut Vec<Error>
+) {
+    match entry {
+        Entry::Assignment(assignment) => {
+            let name = assignment.var.name(ctx).to_string();
+            let location = format!("in the value of `{}`", name);
+            match assignment.shell_command(ctx) {
+                Some(command) => {
+                    check_text(&command, &location, dialect, errors)
+                }
+                None => check_text(
+                    &assignment.value.emit(ctx),
+                    &location,
+                    dialect,
+                    errors
+                )
+            }
+        }
+        Entry::Include(include) => {
+            let path = include.path().emit(ctx);
+            let location = format!("in the include of `{}`", path);
+            check_text(&path, &location, dialect, errors);
+        }
+        Entry::Rule(rule) => {
+            let rule = rule.rule.borrow();
+            let target = rule.target.emit(ctx);
+            let location = format!("in the rule for `{}`", target);
+            check_text(&target, &location, dialect, errors);
+            if target.contains('%') {
+                errors.push(unsupported(
+                    format!("the pattern rule for `{}`", target),
+                    dialect
+                ));
+            }
+            if rule.static_pattern.is_some() {
+                errors.push(unsupported(
+                    format!("the static pattern {}", location),
+                    dialect
+                ));
+            }
+            for dependency in &rule.order_only_dependencies {
+                errors.push(unsupported(
+                    format!(
+                        "the order-only prerequisite `{}` {}",
+                        dependency.emit(ctx),
+                        location
+                    ),
+                    dialect
+                ));
+            }
+            for dependency in &rule.dependencies {
+                check_text(&dependency.emit(ctx), &location, dialect, errors);
+            }
+            for command in &rule.commands {
+                for line in rule.emit_command(command, ctx) {
+                    check_text(&line, &location, dialect, errors);
+                }
+            }
+        }
+        Entry::Conditional(conditional) => {
+            let conditional = conditional.conditional.borrow();
+            if let Some(condition) = conditional
+                .branches
+                .first()
+                .and_then(|branch| branch.condition.as_ref())
+            {
+                errors.push(unsupported(
+                    format!("the conditional `{}`", condition.emit(ctx)),
+                    dialect
+                ));
+            }
+        }
+        Entry::Comment(_) | Entry::Newline(_) | Entry::Namespace(_) => {}
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     emittable::Emittable,
+    error::Error,
     expr::{EmittableVec, Expr},
     symbol_context::SymbolContext
 };
@@ -33,18 +34,39 @@ pub struct Include {
 
 impl Include {
     /// Constructs a new include directive for path `path_expr`.
-    pub fn new<S: AsRef<str>>(path_expr: S) -> Self {
+    pub fn new<E: Into<Expr>>(path_expr: E) -> Self {
         Self {
-            directive: Directive::new(
-                "include",
-                vec![Expr::from(path_expr.as_ref().to_string())]
-            )
+            directive: Directive::new("include", vec![path_expr.into()])
         }
     }
+
+    /// Constructs a new include directive for path `path_expr` that make
+    /// ignores if the file neither exists nor can be made, e.g., for the
+    /// dependency files a compiler writes the first time it runs.
+    pub fn optional<E: Into<Expr>>(path_expr: E) -> Self {
+        Self {
+            directive: Directive::new("-include", vec![path_expr.into()])
+        }
+    }
+
+    /// Whether make ignores the included file if it neither exists nor can be
+    /// made.
+    pub(crate) fn is_optional(&self) -> bool {
+        self.directive.name == "-include"
+    }
+
+    /// The path of the included file.
+    pub(crate) fn path(&self) -> &Expr {
+        &self.directive.args[0]
+    }
 }
 
 impl Emittable for Include {
     fn emit(&self, ctx: &mut SymbolContext) -> String {
         self.directive.emit(ctx)
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        self.path().validate(ctx, errors);
+    }
 }
@@ -1,10 +1,11 @@
-use crate::symbol_context::SymbolContext;
+use crate::{error::Error, symbol_context::SymbolContext};
 
 /// A value that has a textual representation in a Makefile.
 pub trait Emittable {
     /// Constructs a textual representation of this value in a Makefile.
     fn emit(&self, ctx: &mut SymbolContext) -> String;
-}
 
-/// Any [`Emittable`] value.
-pub(crate) type EmittableRef = Box<dyn Emittable>;
+    /// Reports to `errors` every problem that would make the textual
+    /// representation of this value invalid.
+    fn validate(&self, _ctx: &mut SymbolContext, _errors: &mut Vec<Error>) {}
+}
@@ -2,9 +2,10 @@ use crate::{
     assignment::{Assignment, AssignmentKind},
     conditional::ConditionalRef,
     directive::Include,
-    emittable::EmittableRef,
+    entry::Entry,
     expr::Expr,
     misc::{Comment, Newline},
+    namespace::Namespace,
     rrc::RRC,
     rule::RuleRef,
     symbol_context::{Resolvable, SymbolContext},
@@ -12,14 +13,14 @@ use crate::{
 };
 
 pub(crate) trait EmittableContainer {
-    fn add(&mut self, e: EmittableRef);
+    fn add(&mut self, e: Entry);
     fn ctx(&mut self) -> RRC<SymbolContext>;
 
     fn build_assign<V: Resolvable, E: Into<Expr>>(
         &mut self, kind: AssignmentKind, var: V, value: E
     ) -> Variable {
         let var = var.resolve(&mut self.ctx().borrow_mut());
-        self.add(Box::new(Assignment::new(kind, var, value)));
+        self.add(Entry::Assignment(Assignment::new(kind, var, value)));
         var
     }
 }
@@ -29,6 +30,10 @@ pub trait Emitter {
     /// Creates or retrieves the variable named `name`.
     fn var<S: Resolvable>(&mut self, name: S) -> Variable;
 
+    /// Creates a variable whose name begins with `prefix` but is distinct from
+    /// that of every other variable, e.g., for temporaries.
+    fn fresh<S: AsRef<str>>(&mut self, prefix: S) -> Variable;
+
     /// The special variable for the rule target.
     ///
     /// Requires: must be used within a rule.
@@ -61,19 +66,40 @@ pub trait Emitter {
         &mut self, var: V, value: E
     ) -> Variable;
 
+    /// Assigns `var` to `value` as expanded at this point in the Makefile,
+    /// making `var` a simply expanded variable.
+    fn assign_simple<V: Resolvable, E: Into<Expr>>(
+        &mut self, var: V, value: E
+    ) -> Variable;
+
+    /// Assigns `var` to `value` as expanded at this point in the Makefile,
+    /// keeping `var` a recursively expanded variable, i.e., with `:::=`.
+    fn assign_immediate<V: Resolvable, E: Into<Expr>>(
+        &mut self, var: V, value: E
+    ) -> Variable;
+
     /// Appends `value` to `var`.
     fn append<V: Resolvable, E: Into<Expr>>(
         &mut self, var: V, value: E
     ) -> Variable;
 
     /// Includes the contents of `path_expr`.
-    fn include<S: AsRef<str>>(&mut self, path_expr: S);
+    fn include<E: Into<Expr>>(&mut self, path_expr: E);
+
+    /// Includes the contents of `path_expr` if it exists or can be made.
+    fn include_optional<E: Into<Expr>>(&mut self, path_expr: E);
 
     /// Constructs a new rule, returning a builder.
     fn rule<E: Into<Expr>>(&mut self, target: E) -> RuleRef;
 
     /// Constructs a new conditional, returning a builder.
     fn branch_tree(&mut self) -> ConditionalRef;
+
+    /// Adds the contents built by `f` in a namespace `name`, within which user
+    /// variable names are prefixed by `name`.
+    fn namespace<S: AsRef<str>, F: FnOnce(&mut Namespace)>(
+        &mut self, name: S, f: F
+    );
 }
 
 impl<T: EmittableContainer> Emitter for T {
@@ -81,6 +107,10 @@ impl<T: EmittableContainer> Emitter for T {
         name.resolve(&mut self.ctx().borrow_mut())
     }
 
+    fn fresh<S: AsRef<str>>(&mut self, prefix: S) -> Variable {
+        self.ctx().borrow_mut().fresh(prefix)
+    }
+
     fn target_var(&mut self) -> Variable {
         Variable::target(&mut self.ctx().borrow_mut())
     }
@@ -94,11 +124,11 @@ impl<T: EmittableContainer> Emitter for T {
     }
 
     fn comment<S: AsRef<str>>(&mut self, text: S) {
-        self.add(Box::new(Comment::new(text)));
+        self.add(Entry::Comment(Comment::new(text)));
     }
 
     fn newline(&mut self) {
-        self.add(Box::new(Newline));
+        self.add(Entry::Newline(Newline));
     }
 
     fn assign<V: Resolvable, E: Into<Expr>>(
@@ -113,25 +143,52 @@ impl<T: EmittableContainer> Emitter for T {
         self.build_assign(AssignmentKind::Underwrite, var, value)
     }
 
+    fn assign_simple<V: Resolvable, E: Into<Expr>>(
+        &mut self, var: V, value: E
+    ) -> Variable {
+        self.build_assign(AssignmentKind::Simple, var, value)
+    }
+
+    fn assign_immediate<V: Resolvable, E: Into<Expr>>(
+        &mut self, var: V, value: E
+    ) -> Variable {
+        self.build_assign(AssignmentKind::Immediate, var, value)
+    }
+
     fn append<V: Resolvable, E: Into<Expr>>(
         &mut self, var: V, value: E
     ) -> Variable {
         self.build_assign(AssignmentKind::Append, var, value)
     }
 
-    fn include<S: AsRef<str>>(&mut self, path_expr: S) {
-        self.add(Box::new(Include::new(path_expr)));
+    fn include<E: Into<Expr>>(&mut self, path_expr: E) {
+        self.add(Entry::Include(Include::new(path_expr)));
+    }
+
+    fn include_optional<E: Into<Expr>>(&mut self, path_expr: E) {
+        self.add(Entry::Include(Include::optional(path_expr)));
     }
 
     fn rule<E: Into<Expr>>(&mut self, target: E) -> RuleRef {
         let rule = RuleRef::new(target);
-        self.add(Box::new(rule.clone()));
+        self.add(Entry::Rule(rule.clone()));
         rule
     }
 
     fn branch_tree(&mut self) -> ConditionalRef {
         let conditional = ConditionalRef::new(self.ctx());
-        self.add(Box::new(conditional.clone()));
+        self.add(Entry::Conditional(conditional.clone()));
         conditional
     }
+
+    fn namespace<S: AsRef<str>, F: FnOnce(&mut Namespace)>(
+        &mut self, name: S, f: F
+    ) {
+        let ctx = self.ctx();
+        let mut namespace = Namespace::new(ctx.clone());
+        ctx.borrow_mut().enter_namespace(name);
+        f(&mut namespace);
+        ctx.borrow_mut().exit_namespace();
+        self.add(Entry::Namespace(namespace));
+    }
 }
@@ -0,0 +1,80 @@
+use crate::{
+    assignment::Assignment,
+    conditional::ConditionalRef,
+    directive::Include,
+    emittable::Emittable,
+    error::Error,
+    misc::{Comment, Newline},
+    namespace::Namespace,
+    rule::RuleRef,
+    symbol_context::SymbolContext
+};
+
+/// An entry in a Makefile or in a part of one, such as a conditional branch.
+pub(crate) enum Entry {
+    Comment(Comment),
+    Newline(Newline),
+    Assignment(Assignment),
+    Include(Include),
+    Rule(RuleRef),
+    Conditional(ConditionalRef),
+    Namespace(Namespace)
+}
+
+impl Entry {
+    /// Calls `f` on this entry and every entry nested within it, along with
+    /// whether the entry is within a conditional, given whether this one is.
+    pub(crate) fn walk(
+        &self, conditional: bool, f: &mut impl FnMut(&Entry, bool)
+    ) {
+        f(self, conditional);
+        match self {
+            Self::Conditional(conditional) => {
+                for branch in &conditional.conditional.borrow().branches {
+                    for entry in &branch.contents {
+                        entry.walk(true, f);
+                    }
+                }
+            }
+            Self::Namespace(namespace) => {
+                for entry in &namespace.contents {
+                    entry.walk(conditional, f);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether this entry emits nothing, as a namespace without any entries
+    /// does.
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            Self::Namespace(namespace) => {
+                namespace.contents.iter().all(Entry::is_empty)
+            }
+            _ => false
+        }
+    }
+
+    fn as_emittable(&self) -> &dyn Emittable {
+        match self {
+            Self::Comment(comment) => comment,
+            Self::Newline(newline) => newline,
+            Self::Assignment(assignment) => assignment,
+            Self::Include(include) => include,
+            Self::Rule(rule) => rule,
+            Self::Conditional(conditional) => conditional,
+            Self::Namespace(namespace) => namespace
+        }
+    }
+}
+
+impl Emittable for Entry {
+    fn emit(&self, ctx: &mut SymbolContext) -> String {
+        self.as_emittable().emit(ctx)
+    }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        self.as_emittable().validate(ctx, errors);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    fmt::{self, Display},
+    io
+};
+
+use crate::{eval::EvalError, function::FunctionError};
+
+/// A problem that prevents `makemake` from producing a valid Makefile.
+#[derive(Debug)]
+pub enum Error {
+    /// The variable `name` contains `character`, which make does not allow in
+    /// variable names.
+    InvalidVariableName { name: String, character: char },
+    /// A variable was given an empty name.
+    EmptyVariableName,
+    /// A rule was given a target that is empty or consists only of whitespace.
+    EmptyRuleTarget,
+    /// A conditional has no branches.
+    EmptyConditional,
+    /// The first branch of a conditional is an `else` branch, i.e.,
+    /// [`otherwise`](crate::conditional::ConditionalRef::otherwise) was called
+    /// before any `when_*`.
+    ElseWithoutCondition,
+    /// A conditional has a branch after its `else` branch, i.e., a `when_*` or
+    /// [`otherwise`](crate::conditional::ConditionalRef::otherwise) was called
+    /// after `otherwise`.
+    BranchAfterElse,
+    /// Targets depend on each other in a `cycle`, which begins and ends with
+    /// the same target and lists each target before its prerequisite.
+    CircularDependency { cycle: Vec<String> },
+    /// There is no rule for or prerequisite named `target`.
+    UnknownTarget { target: String },
+    /// The `construct` cannot be translated to `backend`.
+    Unsupported {
+        construct: String,
+        backend: &'static str
+    },
+    /// The text of `file` could not be read as a Makefile description at
+    /// `line` and `column`, both of which count from one.
+    Parse {
+        file: String,
+        line: usize,
+        column: usize,
+        message: String
+    },
+    /// The Cargo manifest at `path` could not be read.
+    Cargo {
+        path: String,
+        error: cargo_toml::Error
+    },
+    /// A builtin function was called incorrectly.
+    Function(FunctionError),
+    /// An expression could not be evaluated.
+    Eval(EvalError),
+    /// Writing the Makefile failed.
+    Io(io::Error),
+    /// Several problems, each of which is reported.
+    Multiple(Vec<Error>)
+}
+
+impl Error {
+    /// Combines `errors` into a single error, or `None` if there are none.
+    pub(crate) fn combine(mut errors: Vec<Error>) -> Option<Error> {
+        match errors.len() {
+            0 => None,
+            1 => errors.pop(),
+            _ => Some(Error::Multiple(errors))
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidVariableName { name, character } => write!(
+                f,
+                "variable name `{}` contains invalid character {:?}",
+                name, character
+            ),
+            Self::EmptyVariableName => write!(f, "variable name is empty"),
+            Self::EmptyRuleTarget => write!(f, "rule target is empty"),
+            Self::EmptyConditional => write!(f, "conditional has no branches"),
+            Self::ElseWithoutCondition => {
+                write!(f, "conditional begins with an `else` branch")
+            }
+            Self::BranchAfterElse => {
+                write!(f, "conditional has a branch after its `else` branch")
+            }
+            Self::CircularDependency { cycle } => {
+                write!(f, "circular dependency `{}`", cycle.join(" -> "))
+            }
+            Self::UnknownTarget { target } => {
+                write!(f, "no target named `{}`", target)
+            }
+            Self::Unsupported { construct, backend } => {
+                write!(f, "{} has no equivalent in {}", construct, backend)
+            }
+            Self::Parse {
+                file,
+                line,
+                column,
+                message
+            } => write!(f, "{}:{}:{}: {}", file, line, column, message),
+            Self::Cargo { path, error } => write!(f, "{}: {}", path, error),
+            Self::Function(error) => error.fmt(f),
+            Self::Eval(error) => error.fmt(f),
+            Self::Io(error) => error.fmt(f),
+            Self::Multiple(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    error.fmt(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Cargo { error, .. } => Some(error),
+            Self::Function(error) => Some(error),
+            Self::Eval(error) => Some(error),
+            Self::Io(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<FunctionError> for Error {
+    fn from(value: FunctionError) -> Self {
+        Self::Function(value)
+    }
+}
+
+impl From<EvalError> for Error {
+    fn from(value: EvalError) -> Self {
+        Self::Eval(value)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// A [`Result`](std::result::Result) whose error is an [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,784 @@
+use std::{
+    cell::RefMut,
+    collections::HashMap,
+    fmt::{self, Display}
+};
+
+use crate::{
+    assignment::AssignmentKind,
+    conditional::Condition,
+    emittable::Emittable,
+    entry::Entry,
+    makefile::Makefile,
+    plan::ReadRule,
+    rrc::RRC,
+    symbol_context::{Resolvable, SymbolContext},
+    syntax::{self, Fragment}
+};
+
+/// A problem that prevents make text from being expanded.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EvalError {
+    /// make would reject `text` while reading it.
+    Syntax { text: String, message: &'static str },
+    /// The recursively expanded variable `name` references itself, or a
+    /// `call` of it recursed too deeply.
+    RecursiveVariable { name: String },
+    /// `$(error message)` was expanded.
+    Error { message: String },
+    /// The builtin function `function` expected a number but was given `text`.
+    InvalidNumber {
+        function: &'static str,
+        text: String
+    },
+    /// The builtin function `function` was expanded without the hook for it.
+    MissingHook { function: &'static str },
+    /// The builtin function `function` cannot be expanded without running
+    /// make.
+    Unsupported { function: &'static str }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { text, message } => {
+                write!(f, "{} in `{}`", message, text)
+            }
+            Self::RecursiveVariable { name } => {
+                write!(f, "recursive variable `{}` references itself", name)
+            }
+            Self::Error { message } => write!(f, "{}", message),
+            Self::InvalidNumber { function, text } => write!(
+                f,
+                "non-numeric argument `{}` to function `{}`",
+                text, function
+            ),
+            Self::MissingHook { function } => {
+                write!(f, "function `{}` needs a hook to be expanded", function)
+            }
+            Self::Unsupported { function } => write!(
+                f,
+                "function `{}` cannot be expanded without running make",
+                function
+            )
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// How a variable is expanded. See section
+/// [6.2](https://www.gnu.org/software/make/manual/make.html#Flavors).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Flavor {
+    /// The value is expanded whenever the variable is.
+    Recursive,
+    /// The value was expanded once, when it was assigned.
+    Simple
+}
+
+#[derive(Clone)]
+struct Value {
+    flavor: Flavor,
+    text: String
+}
+
+type ShellHook = Box<dyn Fn(&str) -> String>;
+/// Lists the files matching a pattern for `$(wildcard)`.
+pub type WildcardHook = Box<dyn Fn(&str) -> Vec<String>>;
+
+/// The variables make text is expanded with, along with the hooks that
+/// `$(shell)` and `$(wildcard)` use in place of running commands and reading
+/// the file system.
+#[derive(Default)]
+pub struct Env {
+    ctx: RRC<SymbolContext>,
+    values: HashMap<String, Value>,
+    shell: Option<ShellHook>,
+    wildcard: Option<WildcardHook>
+}
+
+impl Env {
+    /// Constructs an environment without any variables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an environment with the variables that `makefile` assigns,
+    /// processing its assignments and conditionals in order as make would.
+    /// Included files are not read.
+    pub fn from_makefile(makefile: &Makefile) -> Result<Self, EvalError> {
+        Self::load(makefile, &mut vec![])
+    }
+
+    /// Constructs an environment like [`Env::from_makefile`], adding to
+    /// `rules` every rule make reads with its targets and prerequisites
+    /// expanded as they are when read.
+    pub(crate) fn load(
+        makefile: &Makefile, rules: &mut Vec<ReadRule>
+    ) -> Result<Self, EvalError> {
+        Self::new().read(makefile, rules)
+    }
+
+    /// Like [`Env::load`], but keeping the hooks of this environment.
+    pub(crate) fn read(
+        mut self, makefile: &Makefile, rules: &mut Vec<ReadRule>
+    ) -> Result<Self, EvalError> {
+        self.ctx = makefile.shared_symbols();
+        for entry in &makefile.contents {
+            self.process(entry, rules)?;
+        }
+        Ok(self)
+    }
+
+    /// Uses what `hook` returns for a command as the output of `$(shell)` on
+    /// that command.
+    pub fn with_shell<F: Fn(&str) -> String + 'static>(
+        mut self, hook: F
+    ) -> Self {
+        self.shell = Some(Box::new(hook));
+        self
+    }
+
+    /// Uses the files `hook` returns for a pattern as the files matching that
+    /// pattern in `$(wildcard)`.
+    pub fn with_wildcard<F: Fn(&str) -> Vec<String> + 'static>(
+        mut self, hook: F
+    ) -> Self {
+        self.wildcard = Some(Box::new(hook));
+        self
+    }
+
+    /// Assigns `text` to the variable `name` like `kind`, expanding `text`
+    /// immediately if the variable is simply expanded.
+    pub fn assign<S: AsRef<str>, T: AsRef<str>>(
+        &mut self, name: S, kind: AssignmentKind, text: T
+    ) -> Result<(), EvalError> {
+        let name = name.as_ref();
+        let text = text.as_ref();
+        let existing = self.values.get(name).map(|value| value.flavor);
+        let value = match (kind, existing) {
+            (AssignmentKind::Underwrite, Some(_)) => return Ok(()),
+            (AssignmentKind::Simple, _) => Value {
+                flavor: Flavor::Simple,
+                text: self.expand(text)?
+            },
+            (AssignmentKind::Immediate, _) => Value {
+                flavor: Flavor::Recursive,
+                text: self.expand(text)?.replace('$', "$$")
+            },
+            (AssignmentKind::Append, Some(flavor)) => {
+                let addition = match flavor {
+                    Flavor::Recursive => text.to_string(),
+                    Flavor::Simple => self.expand(text)?
+                };
+                let mut value = self.values[name].clone();
+                if !value.text.is_empty() {
+                    value.text.push(' ');
+                }
+                value.text.push_str(&addition);
+                value
+            }
+            _ => Value {
+                flavor: Flavor::Recursive,
+                text: text.to_string()
+            }
+        };
+        self.values.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// The flavor of the variable `name`, or `None` if it is undefined.
+    pub fn flavor(&self, name: &str) -> Option<Flavor> {
+        self.values.get(name).map(|value| value.flavor)
+    }
+
+    /// What `$(name)` expands to.
+    pub fn get<S: AsRef<str>>(&self, name: S) -> Result<String, EvalError> {
+        Evaluator::new(self).variable(name.as_ref())
+    }
+
+    /// Expands make `text`, such as `$(CC) -c $(SRC)`.
+    pub fn expand<S: AsRef<str>>(&self, text: S) -> Result<String, EvalError> {
+        Evaluator::new(self).expand(text.as_ref())
+    }
+
+    /// Expands `text` with each of `bindings` bound like an automatic
+    /// variable.
+    pub(crate) fn expand_with(
+        &self, text: &str, bindings: &[(String, String)]
+    ) -> Result<String, EvalError> {
+        let mut evaluator = Evaluator::new(self);
+        for (name, value) in bindings {
+            evaluator.bind(name, value.clone());
+        }
+        evaluator.expand(text)
+    }
+
+    pub(crate) fn symbols_mut(&self) -> RefMut<'_, SymbolContext> {
+        self.ctx.borrow_mut()
+    }
+
+    fn process(
+        &mut self, entry: &Entry, rules: &mut Vec<ReadRule>
+    ) -> Result<(), EvalError> {
+        match entry {
+            Entry::Assignment(assignment) => {
+                let (name, text) = {
+                    let mut ctx = self.symbols_mut();
+                    let name = assignment.var.name(&mut ctx).to_string();
+                    (name, assignment.value.emit(&mut ctx))
+                };
+                self.assign(name, assignment.kind, text)?;
+            }
+            Entry::Conditional(conditional) => {
+                for branch in &conditional.conditional.borrow().branches {
+                    if self.holds(branch.condition.as_ref())? {
+                        for entry in &branch.contents {
+                            self.process(entry, rules)?;
+                        }
+                        break;
+                    }
+                }
+            }
+            Entry::Namespace(namespace) => {
+                for entry in &namespace.contents {
+                    self.process(entry, rules)?;
+                }
+            }
+            Entry::Rule(rule) => {
+                let rule = rule.rule.borrow();
+                let (target, pattern, prerequisites, order_only, recipe) = {
+                    let mut ctx = self.symbols_mut();
+                    (
+                        rule.target.emit(&mut ctx),
+                        rule.static_pattern
+                            .as_ref()
+                            .map(|pattern| pattern.emit(&mut ctx)),
+                        rule.dependencies
+                            .iter()
+                            .map(|dependency| dependency.emit(&mut ctx))
+                            .collect::<Vec<_>>(),
+                        rule.order_only_dependencies
+                            .iter()
+                            .map(|dependency| dependency.emit(&mut ctx))
+                            .collect::<Vec<_>>(),
+                        rule.commands
+                            .iter()
+                            .flat_map(|command| {
+                                rule.emit_command(command, &mut ctx)
+                            })
+                            .collect()
+                    )
+                };
+                let words = |texts: Vec<String>| {
+                    texts
+                        .iter()
+                        .map(|text| self.expand(text))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map(|values| {
+                            values
+                                .iter()
+                                .flat_map(|value| value.split_whitespace())
+                                .map(str::to_string)
+                                .collect()
+                        })
+                };
+                let static_pattern = match pattern {
+                    Some(pattern) => {
+                        Some(self.expand(pattern)?.trim().to_string())
+                    }
+                    None => None
+                };
+                rules.push(ReadRule {
+                    targets: words(vec![target])?,
+                    static_pattern,
+                    prerequisites: words(prerequisites)?,
+                    order_only: words(order_only)?,
+                    recipe,
+                    is_phony: rule.is_phony,
+                    compiles: rule.compiles
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether the branch guarded by `condition` is taken.
+    fn holds(&self, condition: Option<&Condition>) -> Result<bool, EvalError> {
+        let Some(condition) = condition else {
+            return Ok(true);
+        };
+        let line = condition.emit(&mut self.symbols_mut());
+        match condition {
+            Condition::Eq(lhs, rhs) => {
+                Ok(lhs.evaluate(self)? == rhs.evaluate(self)?)
+            }
+            Condition::Def(_) | Condition::Undef(_) => {
+                let (keyword, text) =
+                    line.split_once(' ').expect("condition has an argument");
+                let name = self.expand(text)?;
+                let defined = self
+                    .values
+                    .get(name.trim())
+                    .is_some_and(|value| !value.text.is_empty());
+                Ok(defined == (keyword == "ifdef"))
+            }
+        }
+    }
+}
+
+/// Expands make text in an [`Env`], with the variables bound by `foreach`,
+/// `let`, and `call` layered on top.
+struct Evaluator<'a> {
+    env: &'a Env,
+    locals: Vec<(String, Value)>,
+    expanding: Vec<String>,
+    depth: usize
+}
+
+impl<'a> Evaluator<'a> {
+    /// The deepest nesting of `call`s expanded.
+    const MAX_DEPTH: usize = 128;
+
+    fn new(env: &'a Env) -> Self {
+        Self {
+            env,
+            locals: vec![],
+            expanding: vec![],
+            depth: 0
+        }
+    }
+
+    fn expand(&mut self, text: &str) -> Result<String, EvalError> {
+        let fragments =
+            syntax::parse(text).map_err(|error| EvalError::Syntax {
+                text: error.text,
+                message: error.message
+            })?;
+        self.fragments(&fragments)
+    }
+
+    fn fragments(
+        &mut self, fragments: &[Fragment]
+    ) -> Result<String, EvalError> {
+        let mut result = String::new();
+        for fragment in fragments {
+            match fragment {
+                Fragment::Text(text) => result.push_str(text),
+                Fragment::Reference { name, substitution } => {
+                    let name = self.fragments(name)?;
+                    let value = self.variable(&name)?;
+                    match substitution {
+                        Some((old, new)) => {
+                            let old = self.fragments(old)?;
+                            let new = self.fragments(new)?;
+                            result.push_str(&substitute(&old, &new, &value));
+                        }
+                        None => result.push_str(&value)
+                    }
+                }
+                Fragment::Call { spec, args } => {
+                    result.push_str(&self.call(spec.name, args)?)
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, value)| value)
+            .or_else(|| self.env.values.get(name))
+    }
+
+    fn bind(&mut self, name: &str, text: String) {
+        self.locals.push((
+            name.to_string(),
+            Value {
+                flavor: Flavor::Simple,
+                text
+            }
+        ));
+    }
+
+    fn unbind(&mut self, count: usize) {
+        self.locals.truncate(self.locals.len() - count);
+    }
+
+    fn variable(&mut self, name: &str) -> Result<String, EvalError> {
+        let Some(value) = self.lookup(name).cloned() else {
+            return Ok(String::new());
+        };
+        match value.flavor {
+            Flavor::Simple => Ok(value.text),
+            Flavor::Recursive => {
+                if self.expanding.iter().any(|expanding| expanding == name) {
+                    return Err(EvalError::RecursiveVariable {
+                        name: name.to_string()
+                    });
+                }
+                self.expanding.push(name.to_string());
+                let result = self.expand(&value.text);
+                self.expanding.pop();
+                result
+            }
+        }
+    }
+
+    /// Expands argument `i` of `args`, or nothing if it is missing.
+    fn arg(
+        &mut self, args: &[Vec<Fragment>], i: usize
+    ) -> Result<String, EvalError> {
+        match args.get(i) {
+            Some(arg) => self.fragments(arg),
+            None => Ok(String::new())
+        }
+    }
+
+    fn call(
+        &mut self, function: &'static str, args: &[Vec<Fragment>]
+    ) -> Result<String, EvalError> {
+        match function {
+            "if" => {
+                let condition = self.arg(args, 0)?;
+                self.arg(args, if condition.trim().is_empty() { 2 } else { 1 })
+            }
+            "or" => {
+                for arg in args {
+                    let value = self.fragments(arg)?;
+                    if !value.trim().is_empty() {
+                        return Ok(value);
+                    }
+                }
+                Ok(String::new())
+            }
+            "and" => {
+                let mut value = String::new();
+                for arg in args {
+                    value = self.fragments(arg)?;
+                    if value.trim().is_empty() {
+                        return Ok(String::new());
+                    }
+                }
+                Ok(value)
+            }
+            "intcmp" => {
+                let lhs = integer(function, &self.arg(args, 0)?)?;
+                let rhs = integer(function, &self.arg(args, 1)?)?;
+                if args.len() == 2 {
+                    return Ok(if lhs == rhs {
+                        lhs.to_string()
+                    } else {
+                        String::new()
+                    });
+                }
+                let i = match lhs.cmp(&rhs) {
+                    std::cmp::Ordering::Less => 2,
+                    std::cmp::Ordering::Equal => 3,
+                    std::cmp::Ordering::Greater if args.len() > 4 => 4,
+                    std::cmp::Ordering::Greater => 3
+                };
+                self.arg(args, i)
+            }
+            "foreach" => {
+                let name = self.arg(args, 0)?;
+                let list = self.arg(args, 1)?;
+                let mut results = vec![];
+                for word in list.split_whitespace() {
+                    self.bind(name.trim(), word.to_string());
+                    results.push(self.arg(args, 2)?);
+                    self.unbind(1);
+                }
+                Ok(results.join(" "))
+            }
+            "let" => {
+                let names = self.arg(args, 0)?;
+                let list = self.arg(args, 1)?;
+                let names = names.split_whitespace().collect::<Vec<_>>();
+                let words = list.split_whitespace().collect::<Vec<_>>();
+                for (i, name) in names.iter().enumerate() {
+                    let text = if i + 1 == names.len() {
+                        words.get(i..).unwrap_or_default().join(" ")
+                    } else {
+                        words.get(i).copied().unwrap_or_default().to_string()
+                    };
+                    self.bind(name, text);
+                }
+                let result = self.arg(args, 2);
+                self.unbind(names.len());
+                result
+            }
+            "call" => {
+                let name = self.arg(args, 0)?.trim().to_string();
+                let mut params = vec![name.clone()];
+                for i in 1..args.len() {
+                    params.push(self.arg(args, i)?);
+                }
+                // Parameters of an enclosing call beyond those given here
+                // are empty rather than inherited.
+                let count = params.len().max(self.param_count());
+                params.resize(count, String::new());
+                let Some(value) = self.lookup(&name).cloned() else {
+                    return Ok(String::new());
+                };
+                if self.depth >= Self::MAX_DEPTH {
+                    return Err(EvalError::RecursiveVariable { name });
+                }
+                for (i, param) in params.into_iter().enumerate() {
+                    self.bind(&i.to_string(), param);
+                }
+                self.depth += 1;
+                let result = match value.flavor {
+                    Flavor::Recursive => self.expand(&value.text),
+                    Flavor::Simple => Ok(value.text)
+                };
+                self.depth -= 1;
+                self.unbind(count);
+                result
+            }
+            "value" => {
+                let name = self.arg(args, 0)?;
+                Ok(self
+                    .lookup(name.trim())
+                    .map(|value| value.text.clone())
+                    .unwrap_or_default())
+            }
+            "origin" => {
+                let name = self.arg(args, 0)?;
+                let name = name.trim();
+                let origin =
+                    if self.locals.iter().any(|(local, _)| local == name) {
+                        "automatic"
+                    } else if self.env.values.contains_key(name) {
+                        "file"
+                    } else {
+                        "undefined"
+                    };
+                Ok(origin.to_string())
+            }
+            "flavor" => {
+                let name = self.arg(args, 0)?;
+                let flavor = match self.lookup(name.trim()) {
+                    Some(Value {
+                        flavor: Flavor::Recursive,
+                        ..
+                    }) => "recursive",
+                    Some(Value {
+                        flavor: Flavor::Simple,
+                        ..
+                    }) => "simple",
+                    None => "undefined"
+                };
+                Ok(flavor.to_string())
+            }
+            "abspath" => {
+                let paths = self.arg(args, 0)?;
+                let curdir = self.variable("CURDIR")?;
+                Ok(paths
+                    .split_whitespace()
+                    .map(|path| abspath(&curdir, path))
+                    .collect::<Vec<_>>()
+                    .join(" "))
+            }
+            _ => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.fragments(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.text_function(function, &args)
+            }
+        }
+    }
+
+    /// The number of parameters bound by the innermost `call`, including the
+    /// name in `$(0)`.
+    fn param_count(&self) -> usize {
+        self.locals
+            .iter()
+            .filter_map(|(name, _)| name.parse::<usize>().ok())
+            .map(|i| i + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Expands `function`, whose arguments are expanded before it is.
+    fn text_function(
+        &self, function: &'static str, args: &[String]
+    ) -> Result<String, EvalError> {
+        let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or("");
+        let words = |i: usize| arg(i).split_whitespace();
+        let each = |i: usize, f: &dyn Fn(&str) -> String| {
+            words(i).map(f).collect::<Vec<_>>().join(" ")
+        };
+        let result = match function {
+            // GNU make appends the replacement to the text instead.
+            "subst" if arg(0).is_empty() => format!("{}{}", arg(2), arg(1)),
+            "subst" => arg(2).replace(arg(0), arg(1)),
+            "patsubst" => patsubst(arg(0), arg(1), arg(2)),
+            "strip" => words(0).collect::<Vec<_>>().join(" "),
+            "findstring" if arg(1).contains(arg(0)) => arg(0).to_string(),
+            "findstring" => String::new(),
+            "filter" | "filter-out" => {
+                let patterns = words(0).collect::<Vec<_>>();
+                words(1)
+                    .filter(|word| {
+                        patterns.iter().any(|pattern| {
+                            syntax::match_pattern(pattern, word).is_some()
+                        }) == (function == "filter")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            "sort" => {
+                let mut words = words(0).collect::<Vec<_>>();
+                words.sort();
+                words.dedup();
+                words.join(" ")
+            }
+            "word" => {
+                let n = positive(function, arg(0))?;
+                words(1).nth(n - 1).unwrap_or_default().to_string()
+            }
+            "wordlist" => {
+                let start = positive(function, arg(0))?;
+                let end = integer(function, arg(1))?.max(0) as usize;
+                words(2)
+                    .skip(start - 1)
+                    .take((end + 1).saturating_sub(start))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            "words" => words(0).count().to_string(),
+            "firstword" => words(0).next().unwrap_or_default().to_string(),
+            "lastword" => words(0).last().unwrap_or_default().to_string(),
+            "dir" => each(0, &|word| match word.rfind('/') {
+                Some(slash) => word[..=slash].to_string(),
+                None => "./".to_string()
+            }),
+            "notdir" => each(0, &|word| {
+                word[word.rfind('/').map_or(0, |slash| slash + 1)..].to_string()
+            }),
+            "suffix" => words(0)
+                .filter_map(|word| suffix_start(word).map(|dot| &word[dot..]))
+                .collect::<Vec<_>>()
+                .join(" "),
+            "basename" => each(0, &|word| {
+                word[..suffix_start(word).unwrap_or(word.len())].to_string()
+            }),
+            "addsuffix" => each(1, &|word| format!("{}{}", word, arg(0))),
+            "addprefix" => each(1, &|word| format!("{}{}", arg(0), word)),
+            "join" => {
+                let lhs = words(0).collect::<Vec<_>>();
+                let rhs = words(1).collect::<Vec<_>>();
+                (0..lhs.len().max(rhs.len()))
+                    .map(|i| {
+                        format!(
+                            "{}{}",
+                            lhs.get(i).unwrap_or(&""),
+                            rhs.get(i).unwrap_or(&"")
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            "wildcard" => {
+                let hook = self
+                    .env
+                    .wildcard
+                    .as_ref()
+                    .ok_or(EvalError::MissingHook { function })?;
+                words(0).flat_map(hook).collect::<Vec<_>>().join(" ")
+            }
+            "shell" => {
+                let hook = self
+                    .env
+                    .shell
+                    .as_ref()
+                    .ok_or(EvalError::MissingHook { function })?;
+                hook(arg(0)).trim_end_matches('\n').replace('\n', " ")
+            }
+            "error" => {
+                return Err(EvalError::Error {
+                    message: arg(0).to_string()
+                })
+            }
+            "warning" | "info" => String::new(),
+            _ => return Err(EvalError::Unsupported { function })
+        };
+        Ok(result)
+    }
+}
+
+/// Replaces each word in `text` matching `pattern` with `replacement`, in
+/// which a `%` stands for what the `%` in `pattern` matched.
+fn patsubst(pattern: &str, replacement: &str, text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| match syntax::match_pattern(pattern, word) {
+            Some(stem) => match replacement.split_once('%') {
+                Some((prefix, suffix)) if pattern.contains('%') => {
+                    format!("{}{}{}", prefix, stem, suffix)
+                }
+                _ => replacement.to_string()
+            },
+            None => word.to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Applies the substitution of `$(var:old=new)` to the `value` of `var`.
+fn substitute(old: &str, new: &str, value: &str) -> String {
+    if old.contains('%') {
+        patsubst(old, new, value)
+    } else {
+        patsubst(&format!("%{}", old), &format!("%{}", new), value)
+    }
+}
+
+/// The index of the `.` beginning the suffix of the file name `word`.
+fn suffix_start(word: &str) -> Option<usize> {
+    let start = word.rfind('/').map_or(0, |slash| slash + 1);
+    word[start..].rfind('.').map(|dot| start + dot)
+}
+
+/// `path` made absolute relative to `curdir`, with `.` and `..` resolved.
+fn abspath(curdir: &str, path: &str) -> String {
+    let mut components = vec![];
+    let full = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", curdir, path)
+    };
+    for component in full.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component)
+        }
+    }
+    format!("/{}", components.join("/"))
+}
+
+fn integer(function: &'static str, text: &str) -> Result<i64, EvalError> {
+    text.trim().parse().map_err(|_| EvalError::InvalidNumber {
+        function,
+        text: text.to_string()
+    })
+}
+
+fn positive(function: &'static str, text: &str) -> Result<usize, EvalError> {
+    match integer(function, text)? {
+        n if n > 0 => Ok(n as usize),
+        _ => Err(EvalError::InvalidNumber {
+            function,
+            text: text.to_string()
+        })
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    process::Command,
+    sync::mpsc,
+    thread,
+    time::SystemTime
+};
+
+use crate::{
+    error::Result,
+    eval::Env,
+    makefile::Makefile,
+    plan::{FileSystem, RealFileSystem, RecipeLine, Resolved, Rules}
+};
+
+/// What happened to a target when running a Makefile with an [`Executor`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// The target was up to date, so its recipe was not run.
+    UpToDate,
+    /// The recipe of the target was run and succeeded.
+    Built,
+    /// A line of the recipe of the target exited with `exit_code`, which is
+    /// `None` if the shell could not be run or was killed by a signal.
+    Failed { exit_code: Option<i32> },
+    /// The target was not made because a prerequisite could not be.
+    Skipped,
+    /// The target does not exist and there is no rule to make it.
+    NoRule
+}
+
+impl Outcome {
+    /// Whether the target failed to be made.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::Failed { .. } | Self::Skipped | Self::NoRule)
+    }
+}
+
+/// The outcome of every target considered by [`Executor::run`].
+pub struct Report {
+    outcomes: Vec<(String, Outcome)>
+}
+
+impl Report {
+    /// Each target considered paired with its outcome, in the order the
+    /// outcomes were decided.
+    pub fn outcomes(&self) -> &[(String, Outcome)] {
+        &self.outcomes
+    }
+
+    /// The outcome of `target`, if it was considered.
+    pub fn outcome(&self, target: &str) -> Option<&Outcome> {
+        self.outcomes
+            .iter()
+            .find(|(other, _)| other == target)
+            .map(|(_, outcome)| outcome)
+    }
+
+    /// Whether every target considered was made or up to date.
+    pub fn is_success(&self) -> bool {
+        !self
+            .outcomes
+            .iter()
+            .any(|(_, outcome)| outcome.is_failure())
+    }
+}
+
+/// Runs a [`Makefile`] without make, deciding what to rebuild from the
+/// modification times of files and running each recipe line with `sh -c`.
+/// `$(shell)` likewise runs its command with `sh -c`, and `$(wildcard)` lists
+/// the files on disk.
+pub struct Executor<'a> {
+    makefile: &'a Makefile,
+    jobs: usize,
+    keep_going: bool,
+    silent: bool
+}
+
+impl<'a> Executor<'a> {
+    /// Constructs an executor for `makefile` that runs one recipe at a time,
+    /// stops at the first failure, and echoes recipe lines like make.
+    pub fn new(makefile: &'a Makefile) -> Self {
+        Self {
+            makefile,
+            jobs: 1,
+            keep_going: false,
+            silent: false
+        }
+    }
+
+    /// Runs up to `jobs` recipes at once, like `make -j jobs`.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Keeps making the targets that do not depend on a failed one, like
+    /// `make -k`.
+    pub fn keep_going(mut self) -> Self {
+        self.keep_going = true;
+        self
+    }
+
+    /// Does not echo recipe lines, like `make -s`.
+    pub fn silent(mut self) -> Self {
+        self.silent = true;
+        self
+    }
+
+    /// Makes `goals`, or the default goal if there are none, reporting the
+    /// outcome of each target considered. Fails without running anything if
+    /// the Makefile cannot be read or has a circular dependency.
+    pub fn run<S: AsRef<str>>(&self, goals: &[S]) -> Result<Report> {
+        let env = Env::new()
+            .with_shell(run_shell)
+            .with_wildcard(RealFileSystem.wildcard());
+        let rules = Rules::load_with(self.makefile, env)?;
+        let goals: Vec<String> = if goals.is_empty() {
+            rules.default_goal.iter().cloned().collect()
+        } else {
+            goals.iter().map(|goal| goal.as_ref().to_string()).collect()
+        };
+        let targets = rules.closure(&goals, &RealFileSystem)?;
+        Run::new(self, &rules, &targets).finish()
+    }
+}
+
+/// The state of a single [`Executor::run`].
+struct Run<'a> {
+    executor: &'a Executor<'a>,
+    rules: &'a Rules,
+    targets: &'a [Resolved],
+    prerequisites: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+    waiting_on: Vec<usize>,
+    outcomes: Vec<Option<Outcome>>,
+    order: Vec<usize>,
+    remade: HashSet<String>,
+    modified_before: HashMap<usize, Option<SystemTime>>,
+    ready: VecDeque<usize>,
+    stopped: bool
+}
+
+impl<'a> Run<'a> {
+    fn new(
+        executor: &'a Executor<'a>, rules: &'a Rules, targets: &'a [Resolved]
+    ) -> Self {
+        let indices = targets
+            .iter()
+            .enumerate()
+            .map(|(i, resolved)| (resolved.target.as_str(), i))
+            .collect::<HashMap<_, _>>();
+        let mut prerequisites = vec![];
+        let mut dependents = vec![vec![]; targets.len()];
+        for (i, resolved) in targets.iter().enumerate() {
+            let mut edges = vec![];
+            for prerequisite in
+                resolved.prerequisites.iter().chain(&resolved.order_only)
+            {
+                let j = indices[prerequisite.as_str()];
+                if !edges.contains(&j) {
+                    edges.push(j);
+                    dependents[j].push(i);
+                }
+            }
+            prerequisites.push(edges);
+        }
+        Self {
+            executor,
+            rules,
+            targets,
+            waiting_on: prerequisites.iter().map(Vec::len).collect(),
+            ready: (0..targets.len())
+                .filter(|i| prerequisites[*i].is_empty())
+                .collect(),
+            prerequisites,
+            dependents,
+            outcomes: vec![None; targets.len()],
+            order: vec![],
+            remade: HashSet::new(),
+            modified_before: HashMap::new(),
+            stopped: false
+        }
+    }
+
+    fn finish(mut self) -> Result<Report> {
+        let (sender, receiver) = mpsc::channel();
+        let mut running = 0;
+        loop {
+            while running < self.executor.jobs && !self.stopped {
+                let Some(i) = self.ready.pop_front() else {
+                    break;
+                };
+                match self.start(i) {
+                    Some(outcome) => self.decide(i, outcome),
+                    None => {
+                        let lines = self.rules.expand_recipe(
+                            &self.targets[i],
+                            &RealFileSystem,
+                            &self.remade
+                        )?;
+                        let silent = self.executor.silent;
+                        let sender = sender.clone();
+                        thread::spawn(move || {
+                            sender.send((i, run_recipe(&lines, silent))).ok();
+                        });
+                        running += 1;
+                    }
+                }
+            }
+            if running == 0 {
+                if self.ready.is_empty() || self.stopped {
+                    break;
+                }
+                continue;
+            }
+            let (i, outcome) =
+                receiver.recv().expect("a recipe is still running");
+            running -= 1;
+            self.decide(i, outcome);
+        }
+        Ok(Report {
+            outcomes: self
+                .order
+                .iter()
+                .map(|i| {
+                    (
+                        self.targets[*i].target.clone(),
+                        self.outcomes[*i].clone().expect("outcome was decided")
+                    )
+                })
+                .collect()
+        })
+    }
+
+    /// Decides the outcome of target `i` if its recipe need not run.
+    fn start(&mut self, i: usize) -> Option<Outcome> {
+        let resolved = &self.targets[i];
+        let fs = RealFileSystem;
+        if self.prerequisites[i].iter().any(|j| {
+            self.outcomes[*j]
+                .as_ref()
+                .is_some_and(|outcome| outcome.is_failure())
+        }) {
+            return Some(Outcome::Skipped);
+        }
+        if !resolved.has_rule && !self.rules.is_phony(&resolved.target) {
+            return Some(if fs.modified(&resolved.target).is_some() {
+                Outcome::UpToDate
+            } else {
+                Outcome::NoRule
+            });
+        }
+        if self.rules.reasons(resolved, &fs, &self.remade).is_empty() {
+            return Some(Outcome::UpToDate);
+        }
+        if resolved.recipe.is_empty() {
+            return Some(Outcome::Built);
+        }
+        self.modified_before
+            .insert(i, fs.modified(&resolved.target));
+        None
+    }
+
+    fn decide(&mut self, i: usize, outcome: Outcome) {
+        let target = &self.targets[i].target;
+        match &outcome {
+            Outcome::Built => {
+                self.remade.insert(target.clone());
+            }
+            Outcome::Failed { .. }
+                if self.rules.delete_on_error
+                    && !self.rules.is_phony(target) =>
+            {
+                let before = self.modified_before.get(&i).copied().flatten();
+                let after = RealFileSystem.modified(target);
+                if after.is_some() && after != before {
+                    fs::remove_file(target).ok();
+                }
+            }
+            _ => {}
+        }
+        if outcome.is_failure() && !self.executor.keep_going {
+            self.stopped = true;
+        }
+        self.outcomes[i] = Some(outcome);
+        self.order.push(i);
+        for dependent in self.dependents[i].clone() {
+            self.waiting_on[dependent] -= 1;
+            if self.waiting_on[dependent] == 0 {
+                self.ready.push_back(dependent);
+            }
+        }
+    }
+}
+
+/// The output of `command` run with `sh -c`, as `$(shell)` expands to.
+fn run_shell(command: &str) -> String {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+/// Runs each of `lines` with `sh -c`, stopping at the first that fails.
+fn run_recipe(lines: &[String], silent: bool) -> Outcome {
+    for line in lines {
+        let line = RecipeLine::parse(line);
+        if !silent && !line.silent {
+            println!("{}", line.command);
+        }
+        let status = Command::new("sh").arg("-c").arg(line.command).status();
+        match status {
+            Ok(status) if status.success() || line.ignore_errors => {}
+            Ok(status) => {
+                return Outcome::Failed {
+                    exit_code: status.code()
+                }
+            }
+            Err(_) => return Outcome::Failed { exit_code: None }
+        }
+    }
+    Outcome::Built
+}
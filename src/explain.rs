@@ -0,0 +1,81 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    error::Result,
+    eval::Env,
+    makefile::Makefile,
+    plan::{FileSystem, Rules}
+};
+
+/// Why make remakes a target.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Reason {
+    /// The target is phony, so it is always remade.
+    Phony,
+    /// The target does not exist.
+    Missing,
+    /// `prerequisite` is newer than the target.
+    NewerPrerequisite { prerequisite: String },
+    /// `prerequisite` does not exist, so it is made first.
+    MissingPrerequisite { prerequisite: String },
+    /// `prerequisite` is phony, so it is always remade first.
+    PhonyPrerequisite { prerequisite: String },
+    /// `prerequisite` is remade first, which makes it newer.
+    RemadePrerequisite { prerequisite: String }
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Phony => write!(f, "it is phony"),
+            Self::Missing => write!(f, "it does not exist"),
+            Self::NewerPrerequisite { prerequisite } => {
+                write!(f, "prerequisite `{}` is newer", prerequisite)
+            }
+            Self::MissingPrerequisite { prerequisite } => {
+                write!(f, "prerequisite `{}` does not exist", prerequisite)
+            }
+            Self::PhonyPrerequisite { prerequisite } => {
+                write!(f, "prerequisite `{}` is phony", prerequisite)
+            }
+            Self::RemadePrerequisite { prerequisite } => {
+                write!(f, "prerequisite `{}` is remade", prerequisite)
+            }
+        }
+    }
+}
+
+/// A target make remakes and every reason it does.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Explanation {
+    pub target: String,
+    pub reasons: Vec<Reason>
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is remade because ", self.target)?;
+        for (i, reason) in self.reasons.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            reason.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn explain(
+    makefile: &Makefile, goal: &str, fs: &dyn FileSystem
+) -> Result<Vec<Explanation>> {
+    let env = Env::new().with_wildcard(fs.wildcard());
+    let rules = Rules::load_with(makefile, env)?;
+    Ok(rules
+        .dry_run(&[goal.to_string()], fs)?
+        .into_iter()
+        .map(|(resolved, reasons)| Explanation {
+            target: resolved.target,
+            reasons
+        })
+        .collect())
+}
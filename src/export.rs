@@ -0,0 +1,185 @@
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{
+    error::{Error, Result},
+    graph::{EdgeKind, RuleGraph},
+    syntax
+};
+
+/// Options for exporting a [`RuleGraph`] with [`RuleGraph::to_dot`] or
+/// [`RuleGraph::to_mermaid`].
+#[derive(Default)]
+pub struct ExportOptions {
+    collapse_patterns: bool,
+    goal: Option<String>
+}
+
+impl ExportOptions {
+    /// Constructs options that export the whole graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws each target without a recipe of its own that matches a pattern in
+    /// the graph, like `main.o` for `%.o`, as that pattern.
+    pub fn collapse_patterns(mut self) -> Self {
+        self.collapse_patterns = true;
+        self
+    }
+
+    /// Only draws `goal` and what it depends on.
+    pub fn goal<S: AsRef<str>>(mut self, goal: S) -> Self {
+        self.goal = Some(goal.as_ref().to_string());
+        self
+    }
+}
+
+/// A target as drawn.
+struct DrawnNode {
+    name: String,
+    is_phony: bool,
+    is_pattern: bool
+}
+
+/// The graph as drawn, after applying the [`ExportOptions`].
+struct Drawing {
+    nodes: Vec<DrawnNode>,
+    edges: Vec<(usize, usize, EdgeKind)>
+}
+
+impl Drawing {
+    fn new(graph: &RuleGraph, options: &ExportOptions) -> Result<Self> {
+        let targets = match &options.goal {
+            Some(goal) => {
+                if !graph.contains(goal) {
+                    return Err(Error::UnknownTarget {
+                        target: goal.clone()
+                    });
+                }
+                let mut targets = graph.transitive_prerequisites(goal);
+                targets.insert(0, goal);
+                targets
+            }
+            None => graph.targets().collect()
+        };
+        let patterns = graph
+            .targets()
+            .filter(|target| graph.is_pattern(target))
+            .collect::<Vec<_>>();
+        let drawn_name = |target: &str| {
+            if options.collapse_patterns
+                && !graph.is_pattern(target)
+                && !graph.has_recipe(target)
+                && !graph.is_phony(target)
+            {
+                if let Some(pattern) = patterns.iter().find(|pattern| {
+                    syntax::match_pattern(pattern, target).is_some()
+                }) {
+                    return pattern.to_string();
+                }
+            }
+            target.to_string()
+        };
+
+        let mut drawing = Drawing {
+            nodes: vec![],
+            edges: vec![]
+        };
+        let included = targets.iter().copied().collect::<HashSet<_>>();
+        for target in &targets {
+            let from = drawing.node(graph, drawn_name(target));
+            for (prerequisite, kind) in graph.prerequisites(target) {
+                if !included.contains(prerequisite) {
+                    continue;
+                }
+                let to = drawing.node(graph, drawn_name(prerequisite));
+                if from != to && !drawing.edges.contains(&(from, to, kind)) {
+                    drawing.edges.push((from, to, kind));
+                }
+            }
+        }
+        Ok(drawing)
+    }
+
+    fn node(&mut self, graph: &RuleGraph, name: String) -> usize {
+        if let Some(index) =
+            self.nodes.iter().position(|node| node.name == name)
+        {
+            return index;
+        }
+        self.nodes.push(DrawnNode {
+            is_phony: graph.is_phony(&name),
+            is_pattern: graph.is_pattern(&name),
+            name
+        });
+        self.nodes.len() - 1
+    }
+}
+
+impl RuleGraph {
+    /// Renders this graph in the Graphviz DOT language, with an edge from each
+    /// target to each of its prerequisites. Order-only prerequisites are
+    /// dashed, phony targets are boxes, and patterns are notes.
+    pub fn to_dot(&self, options: &ExportOptions) -> Result<String> {
+        let drawing = Drawing::new(self, options)?;
+        let quote = |name: &str| {
+            format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+        };
+        let mut result = String::from("digraph makefile {\n");
+        for node in &drawing.nodes {
+            let shape = if node.is_phony {
+                " [shape=box]"
+            } else if node.is_pattern {
+                " [shape=note]"
+            } else {
+                ""
+            };
+            writeln!(&mut result, "    {}{};", quote(&node.name), shape)
+                .unwrap();
+        }
+        for (from, to, kind) in &drawing.edges {
+            let style = match kind {
+                EdgeKind::Normal => "",
+                EdgeKind::OrderOnly => " [style=dashed]"
+            };
+            writeln!(
+                &mut result,
+                "    {} -> {}{};",
+                quote(&drawing.nodes[*from].name),
+                quote(&drawing.nodes[*to].name),
+                style
+            )
+            .unwrap();
+        }
+        result.push_str("}\n");
+        Ok(result)
+    }
+
+    /// Renders this graph as a Mermaid flowchart, with an arrow from each
+    /// target to each of its prerequisites. Order-only prerequisites are
+    /// dotted, phony targets are stadiums, and patterns are parallelograms.
+    pub fn to_mermaid(&self, options: &ExportOptions) -> Result<String> {
+        let drawing = Drawing::new(self, options)?;
+        let mut result = String::from("flowchart TD\n");
+        for (i, node) in drawing.nodes.iter().enumerate() {
+            let label = node.name.replace('"', "#quot;");
+            let (open, close) = if node.is_phony {
+                ("([", "])")
+            } else if node.is_pattern {
+                ("[/", "/]")
+            } else {
+                ("[", "]")
+            };
+            writeln!(&mut result, "    n{}{}\"{}\"{}", i, open, label, close)
+                .unwrap();
+        }
+        for (from, to, kind) in &drawing.edges {
+            let arrow = match kind {
+                EdgeKind::Normal => "-->",
+                EdgeKind::OrderOnly => "-.->"
+            };
+            writeln!(&mut result, "    n{} {} n{}", from, arrow, to).unwrap();
+        }
+        Ok(result)
+    }
+}
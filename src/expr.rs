@@ -2,15 +2,20 @@ use std::ops::{Add, AddAssign};
 
 use crate::{
     emittable::Emittable,
+    error::Error,
+    eval::{Env, EvalError},
     function::{Function, Substitution},
     symbol_context::SymbolContext,
-    var::Variable
+    var::{Variable, VariableName}
 };
 
+#[derive(Default)]
 pub enum Expr {
+    #[default]
     Empty,
     Raw(String),
     Var(Variable),
+    Ref(Box<VariableName>),
     Concat(Vec<Expr>),
     SubstRef(Box<Substitution>),
     Function(Function)
@@ -32,11 +37,11 @@ impl Expr {
     pub fn then<E: Into<Expr>>(self, expr: E) -> Expr {
         self.concat(" ").concat(expr)
     }
-}
 
-impl Default for Expr {
-    fn default() -> Self {
-        Self::Empty
+    /// Expands this expression as make would with the variables in `env`.
+    pub fn evaluate(&self, env: &Env) -> Result<String, EvalError> {
+        let text = self.emit(&mut env.symbols_mut());
+        env.expand(text)
     }
 }
 
@@ -52,6 +57,12 @@ impl From<Variable> for Expr {
     }
 }
 
+impl From<VariableName> for Expr {
+    fn from(value: VariableName) -> Self {
+        Self::Ref(Box::new(value))
+    }
+}
+
 impl From<Function> for Expr {
     fn from(value: Function) -> Self {
         Self::Function(value)
@@ -70,11 +81,27 @@ impl Emittable for Expr {
             Expr::Empty => String::new(),
             Expr::Raw(string) => string.clone(),
             Expr::Var(var) => var.emit(ctx),
+            Expr::Ref(name) => name.emit(ctx),
             Expr::Concat(list) => list.join_emit("", ctx),
             Expr::SubstRef(subst) => subst.emit(ctx),
             Expr::Function(func) => func.emit(ctx)
         }
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        match &self {
+            Expr::Empty | Expr::Raw(_) => {}
+            Expr::Var(var) => var.validate(ctx, errors),
+            Expr::Ref(name) => name.validate(ctx, errors),
+            Expr::Concat(list) => {
+                for expr in list {
+                    expr.validate(ctx, errors);
+                }
+            }
+            Expr::SubstRef(subst) => subst.validate(ctx, errors),
+            Expr::Function(func) => func.validate(ctx, errors)
+        }
+    }
 }
 
 pub trait EmittableVec {
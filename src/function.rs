@@ -1,9 +1,13 @@
+use std::fmt::{self, Display};
+
 use crate::{
+    catalog::{self, FunctionSpec},
     emittable::Emittable,
+    error::Error,
     expr::{EmittableVec, Expr},
-    symbol_context::{Resolvable, SymbolContext, VariablePromise}
+    symbol_context::SymbolContext,
+    var::VariableName
 };
-use paste::paste;
 
 /// A Makefile function.
 pub struct Function {
@@ -11,6 +15,67 @@ pub struct Function {
     args: Vec<Expr>
 }
 
+/// An invalid call to a builtin function.
+#[derive(Debug)]
+pub enum FunctionError {
+    /// There is no builtin function called `name`.
+    Unknown { name: String },
+    /// The builtin function `name` was called with `given` arguments.
+    Arity {
+        name: String,
+        min: usize,
+        max: Option<usize>,
+        given: usize
+    }
+}
+
+impl Display for FunctionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown { name } => {
+                write!(f, "there is no builtin function `{}`", name)
+            }
+            Self::Arity {
+                name,
+                min,
+                max,
+                given
+            } => {
+                write!(f, "function `{}` takes ", name)?;
+                match max {
+                    Some(max) if max == min => write!(f, "{}", min)?,
+                    Some(max) => write!(f, "{} to {}", min, max)?,
+                    None => write!(f, "at least {}", min)?
+                }
+                write!(f, " argument(s) but was given {}", given)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FunctionError {}
+
+/// How `$(file ...)` accesses its file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileOp {
+    /// `>`, which overwrites the file.
+    Write,
+    /// `>>`, which appends to the file.
+    Append,
+    /// `<`, which reads the file.
+    Read
+}
+
+impl FileOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Write => ">",
+            Self::Append => ">>",
+            Self::Read => "<"
+        }
+    }
+}
+
 fn fix_name(name: &str) -> String {
     let mut name = name.to_string();
     if name.ends_with('_') {
@@ -20,61 +85,59 @@ fn fix_name(name: &str) -> String {
     name
 }
 
+/// Joins `list` into a single space-separated argument.
+fn words(list: Vec<Expr>) -> Expr {
+    list.into_iter()
+        .reduce(|words, word| words.then(word))
+        .unwrap_or_default()
+}
+
+/// `func!(name a, b..., c?, d*)` defines a constructor for the builtin function
+/// `name` whose argument `a` is a single expression, `b` is a list of
+/// expressions passed as one space-separated argument, `c` is an optional
+/// argument, and `d` is a list of expressions each passed as its own argument.
 macro_rules! func {
-    ($name:ident $var_arg:ident..., $($arg:ident),+) => {
-        paste! {
-            pub fn $name<$([<T $arg>]: Into<Expr>),*, TVarArg: Into<Vec<Expr>>>(
-                $var_arg: TVarArg,
-                $([<$arg>]: [<T $arg>]),*
-            ) -> Function {
-                let mut args = vec![$([<$arg>].into()),*];
-                args.extend($var_arg.into());
-                Function {
-                    name: fix_name(stringify!($name)),
-                    args
-                }
-            }
+    (@munch $name:ident $args:ident [$($params:tt)*] [$($body:tt)*]) => {
+        #[allow(clippy::vec_init_then_push)]
+        pub fn $name($($params)*) -> Function {
+            let mut $args: Vec<Option<Expr>> = vec![];
+            $($body)*
+            Function::from_optional_args(fix_name(stringify!($name)), $args)
         }
     };
-    ($name:ident $($arg:ident)*, $var_arg:ident...) => {
-        paste! {
-            pub fn $name<$([<T $arg>]: Into<Expr>),*, TVarArg: Into<Vec<Expr>>>(
-                $([<$arg>]: [<T $arg>]),*,
-                $var_arg: TVarArg
-            ) -> Function {
-                let mut args = vec![$([<$arg>].into()),*];
-                args.extend($var_arg.into());
-                Function {
-                    name: fix_name(stringify!($name)),
-                    args
-                }
-            }
-        }
+    (@munch $name:ident $args:ident [$($params:tt)*] [$($body:tt)*]
+        $arg:ident..., $($rest:tt)*) => {
+        func!(@munch $name $args
+            [$($params)* $arg: impl Into<Vec<Expr>>,]
+            [$($body)* $args.push(Some(words($arg.into())));]
+            $($rest)*);
     };
-    ($name:ident $($arg:ident),*) => {
-        paste! {
-            pub fn $name<$([<T $arg>]: Into<Expr>),*>($($arg: [<T $arg>]),*) -> Function {
-                Function {
-                    name: fix_name(stringify!($name)),
-                    args: vec![$($arg.into()),*]
-                }
-            }
-        }
+    (@munch $name:ident $args:ident [$($params:tt)*] [$($body:tt)*]
+        $arg:ident?, $($rest:tt)*) => {
+        func!(@munch $name $args
+            [$($params)* $arg: Option<Expr>,]
+            [$($body)* $args.push($arg);]
+            $($rest)*);
     };
-    ($name:ident $var_arg:ident...) => {
-        paste! {
-            pub fn $name<TVarArg: Into<Vec<Expr>>>($var_arg: TVarArg) -> Function {
-                Function {
-                    name: fix_name(stringify!($name)),
-                    args: $var_arg.into()
-                }
-            }
-        }
+    (@munch $name:ident $args:ident [$($params:tt)*] [$($body:tt)*]
+        $arg:ident*, $($rest:tt)*) => {
+        func!(@munch $name $args
+            [$($params)* $arg: impl Into<Vec<Expr>>,]
+            [$($body)* $args.extend($arg.into().into_iter().map(Some));]
+            $($rest)*);
+    };
+    (@munch $name:ident $args:ident [$($params:tt)*] [$($body:tt)*]
+        $arg:ident, $($rest:tt)*) => {
+        func!(@munch $name $args
+            [$($params)* $arg: impl Into<Expr>,]
+            [$($body)* $args.push(Some($arg.into()));]
+            $($rest)*);
+    };
+    ($name:ident $($rest:tt)*) => {
+        func!(@munch $name args [] [] $($rest)*,);
     };
 }
 
-#[allow(dead_code)]
-#[allow(non_camel_case_types)]
 impl Function {
     func!(subst from,to,text);
     func!(patsubst pattern,replacement,text);
@@ -84,15 +147,15 @@ impl Function {
     func!(filter_out pattern...,text);
     func!(sort list);
     func!(word n,text);
-    func!(words text);
     func!(wordlist s,e,text);
+    func!(words text);
     func!(firstword names...);
     func!(lastword names...);
     func!(dir names...);
     func!(notdir names...);
     func!(suffix names...);
     func!(basename names...);
-    func!(addsufix suffix,names...);
+    func!(addsuffix suffix,names...);
     func!(addprefix prefix,names...);
     func!(join list1,list2);
     func!(wildcard pattern...);
@@ -100,52 +163,185 @@ impl Function {
     func!(abspath names...);
     func!(error text...);
     func!(warning text...);
+    func!(info text...);
     func!(shell command);
     func!(origin variable);
     func!(flavor variable);
-    func!(let_ var...,words,text);
-    func!(foreach var,words,text);
-    func!(if_ condition,then_part,else_part);
-    func!(or condition...);
-    func!(and condition...);
-    func!(intcmp lhs,rhs,lt_part,eq_part,gt_part);
-    func!(call var,param...);
+    func!(let_ var...,list,text);
+    func!(foreach var,list,text);
+    func!(call var,param*);
+    func!(value variable);
     func!(eval text);
-    func!(value var);
+    func!(if_ condition,then_part,else_part?);
+    func!(or condition*);
+    func!(and condition*);
+    func!(intcmp lhs,rhs,lt_part?,eq_part?,gt_part?);
+    func!(guile program);
+
+    #[deprecated(note = "use `Function::addsuffix`")]
+    pub fn addsufix(
+        suffix: impl Into<Expr>, names: impl Into<Vec<Expr>>
+    ) -> Function {
+        Self::addsuffix(suffix, names)
+    }
+
+    /// `$(file op filename[,text])`, which writes `text` to or reads from
+    /// `filename`.
+    pub fn file(
+        op: FileOp, filename: impl Into<Expr>, text: Option<Expr>
+    ) -> Function {
+        Self::from_optional_args(
+            "file".into(),
+            vec![Some(Expr::from(op.symbol()).concat(filename)), text]
+        )
+    }
+
+    /// Constructs a call to the builtin function `name`, checking that it
+    /// exists and accepts the number of arguments in `args`.
+    pub fn builtin<S: AsRef<str>, A: Into<Vec<Expr>>>(
+        name: S, args: A
+    ) -> Result<Function, FunctionError> {
+        let name = name.as_ref();
+        let args = args.into();
+        let spec =
+            catalog::lookup(name).ok_or_else(|| FunctionError::Unknown {
+                name: name.to_string()
+            })?;
+        if !spec.accepts(args.len()) {
+            return Err(FunctionError::Arity {
+                name: name.to_string(),
+                min: spec.min_args,
+                max: spec.max_args,
+                given: args.len()
+            });
+        }
+        Ok(Function {
+            name: name.to_string(),
+            args
+        })
+    }
+
+    /// Trims absent trailing arguments and leaves absent intermediate ones
+    /// empty.
+    fn from_optional_args(name: String, mut args: Vec<Option<Expr>>) -> Self {
+        while let Some(None) = args.last() {
+            args.pop();
+        }
+        Function {
+            name,
+            args: args.into_iter().map(Option::unwrap_or_default).collect()
+        }
+    }
+
+    /// The name of the called function.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The arguments passed to the function.
+    pub fn args(&self) -> &[Expr] {
+        &self.args
+    }
+
+    /// The signature of the called function.
+    pub fn spec(&self) -> Option<&'static FunctionSpec> {
+        catalog::lookup(&self.name)
+    }
 }
 
 impl Emittable for Function {
     fn emit(&self, ctx: &mut SymbolContext) -> String {
+        // The space is required even without arguments, since make reads
+        // `$(name)` as a reference to the variable `name`.
         format!("$({} {})", self.name, self.args.join_emit(",", ctx))
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        if let Some(spec) = self.spec() {
+            if !spec.accepts(self.args.len()) {
+                errors.push(Error::Function(FunctionError::Arity {
+                    name: self.name.clone(),
+                    min: spec.min_args,
+                    max: spec.max_args,
+                    given: self.args.len()
+                }));
+            }
+        }
+        for arg in &self.args {
+            arg.validate(ctx, errors);
+        }
+    }
 }
 
-/// A suffix substitution.
+/// The form of a [`Substitution`]. See section
+/// [6.3.1](https://www.gnu.org/software/make/manual/make.html#Substitution-Refs).
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SubstitutionKind {
+    /// `$(VAR:.c=.o)`, which replaces a suffix of each word.
+    Suffix,
+    /// `$(VAR:%.c=build/%.o)`, which works like `patsubst` on each word.
+    Pattern
+}
+
+/// A substitution reference, which transforms each word in the value of a
+/// variable.
 pub struct Substitution {
-    var: VariablePromise,
-    old_suffix: Expr,
-    new_suffix: Expr
+    pub(crate) kind: SubstitutionKind,
+    pub(crate) var: VariableName,
+    pub(crate) old: Expr,
+    pub(crate) new: Expr
 }
 
 impl Substitution {
     /// Constructs a new suffix substitution that substitutes `old_suffix` for
     /// `new_suffix` in `var`.
-    pub fn new<V: Into<VariablePromise>, E1: Into<Expr>, E2: Into<Expr>>(
+    pub fn new<V: Into<VariableName>, E1: Into<Expr>, E2: Into<Expr>>(
         var: V, old_suffix: E1, new_suffix: E2
     ) -> Self {
         Self {
+            kind: SubstitutionKind::Suffix,
             var: var.into(),
-            old_suffix: old_suffix.into(),
-            new_suffix: new_suffix.into()
+            old: old_suffix.into(),
+            new: new_suffix.into()
         }
     }
+
+    /// Constructs a new pattern substitution that replaces words in `var`
+    /// matching `pattern` with `replacement`, where a `%` in each stands for
+    /// the same text.
+    pub fn pattern<V: Into<VariableName>, E1: Into<Expr>, E2: Into<Expr>>(
+        var: V, pattern: E1, replacement: E2
+    ) -> Self {
+        Self {
+            kind: SubstitutionKind::Pattern,
+            var: var.into(),
+            old: pattern.into(),
+            new: replacement.into()
+        }
+    }
+
+    /// The form of this substitution.
+    pub fn kind(&self) -> SubstitutionKind {
+        self.kind
+    }
 }
 
 impl Emittable for Substitution {
     fn emit(&self, ctx: &mut SymbolContext) -> String {
-        let old_suffix = self.old_suffix.emit(ctx);
-        let new_suffix = self.new_suffix.emit(ctx);
-        let name = self.var.name(ctx);
-        format!("$({}:{}={})", name, old_suffix, new_suffix)
+        let old = self.old.emit(ctx);
+        let new = self.new.emit(ctx);
+        let name = self.var.emit_name(ctx);
+        format!("$({}:{}={})", name, old, new)
+    }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        self.var.validate(ctx, errors);
+        self.old.validate(ctx, errors);
+        self.new.validate(ctx, errors);
     }
 }
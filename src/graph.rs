@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    emittable::Emittable,
+    entry::Entry,
+    error::{Error, Result},
+    makefile::Makefile,
+    rule::Rule,
+    symbol_context::{Resolvable, SymbolContext},
+    syntax::{self, StaticValues}
+};
+
+/// The special targets that make gives meaning to, which are not part of the
+/// rule graph. See section
+/// [4.9](https://www.gnu.org/software/make/manual/make.html#Special-Targets).
+pub const SPECIAL_TARGETS: &[&str] = &[
+    ".PHONY",
+    ".SUFFIXES",
+    ".DEFAULT",
+    ".PRECIOUS",
+    ".INTERMEDIATE",
+    ".NOTINTERMEDIATE",
+    ".SECONDARY",
+    ".SECONDEXPANSION",
+    ".DELETE_ON_ERROR",
+    ".IGNORE",
+    ".LOW_RESOLUTION_TIME",
+    ".SILENT",
+    ".EXPORT_ALL_VARIABLES",
+    ".NOTPARALLEL",
+    ".ONESHELL",
+    ".POSIX",
+    ".WAIT"
+];
+
+/// Whether `target` is one of the [`SPECIAL_TARGETS`].
+pub fn is_special_target(target: &str) -> bool {
+    SPECIAL_TARGETS.contains(&target)
+}
+
+/// A rule whose targets and prerequisites were expanded without running make.
+pub(crate) struct StaticRule {
+    pub(crate) targets: Vec<String>,
+    pub(crate) static_pattern: Option<String>,
+    pub(crate) dependencies: Vec<String>,
+    pub(crate) order_only_dependencies: Vec<String>,
+    pub(crate) is_phony: bool,
+    pub(crate) has_recipe: bool
+}
+
+impl StaticRule {
+    /// Expands the targets and prerequisites of `rule` using `values`, adding
+    /// the text of each that cannot be expanded to `unresolved`.
+    pub(crate) fn expand(
+        rule: &Rule, ctx: &mut SymbolContext, values: &StaticValues,
+        unresolved: &mut Vec<String>
+    ) -> Option<Self> {
+        let mut words = |text: String| match values.expand(&text) {
+            Some(value) => Some(
+                value
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            ),
+            None => {
+                unresolved.push(text);
+                None
+            }
+        };
+        let targets = words(rule.target.emit(ctx))?;
+        let static_pattern = match &rule.static_pattern {
+            Some(pattern) => Some(words(pattern.emit(ctx))?.join(" ")),
+            None => None
+        };
+        let dependencies = rule
+            .dependencies
+            .iter()
+            .filter_map(|dependency| words(dependency.emit(ctx)))
+            .flatten()
+            .collect();
+        let order_only_dependencies = rule
+            .order_only_dependencies
+            .iter()
+            .filter_map(|dependency| words(dependency.emit(ctx)))
+            .flatten()
+            .collect();
+        Some(Self {
+            targets,
+            static_pattern,
+            dependencies,
+            order_only_dependencies,
+            is_phony: rule.is_phony,
+            has_recipe: !rule.commands.is_empty()
+        })
+    }
+
+    /// The prerequisites and order-only prerequisites of `target`, which are
+    /// specific to it in a static pattern rule.
+    pub(crate) fn prerequisites_of(
+        &self, target: &str
+    ) -> (Vec<String>, Vec<String>) {
+        match self
+            .static_pattern
+            .as_deref()
+            .and_then(|pattern| syntax::match_pattern(pattern, target))
+        {
+            Some(stem) => (
+                syntax::substitute_stem(&self.dependencies, stem),
+                syntax::substitute_stem(&self.order_only_dependencies, stem)
+            ),
+            None => (
+                self.dependencies.clone(),
+                self.order_only_dependencies.clone()
+            )
+        }
+    }
+}
+
+/// How a target depends on a prerequisite.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum EdgeKind {
+    /// The target is rebuilt when the prerequisite is newer.
+    Normal,
+    /// The prerequisite is only built before the target.
+    OrderOnly
+}
+
+struct Node {
+    name: String,
+    has_rule: bool,
+    has_recipe: bool,
+    prerequisites: Vec<(usize, EdgeKind)>
+}
+
+/// The targets of a Makefile and the prerequisites of each, as far as they can
+/// be determined without running make. Targets and prerequisites that
+/// reference variables are resolved if the variables are assigned outside any
+/// conditional to text that does not call functions.
+pub struct RuleGraph {
+    nodes: Vec<Node>,
+    indices: HashMap<String, usize>,
+    phony: HashSet<String>,
+    unresolved: Vec<String>
+}
+
+impl RuleGraph {
+    pub(crate) fn new(makefile: &Makefile) -> Self {
+        let mut graph = RuleGraph {
+            nodes: vec![],
+            indices: HashMap::new(),
+            phony: HashSet::new(),
+            unresolved: vec![]
+        };
+        let mut values = StaticValues::default();
+        let mut ctx = makefile.symbols_mut();
+        for entry in &makefile.contents {
+            entry.walk(false, &mut |entry, conditional| match entry {
+                Entry::Assignment(assignment) => {
+                    let name = assignment.var.name(&mut ctx).to_string();
+                    let text = assignment.value.emit(&mut ctx);
+                    values.assign(&name, assignment.kind, text, conditional);
+                }
+                Entry::Rule(rule) => {
+                    let rule = rule.rule.borrow();
+                    if conditional {
+                        graph.unresolved.push(rule.target.emit(&mut ctx));
+                    } else if let Some(rule) = StaticRule::expand(
+                        &rule,
+                        &mut ctx,
+                        &values,
+                        &mut graph.unresolved
+                    ) {
+                        graph.add(rule);
+                    }
+                }
+                _ => {}
+            });
+        }
+        graph
+    }
+
+    fn add(&mut self, rule: StaticRule) {
+        if rule.targets.iter().any(|target| target == ".PHONY") {
+            self.phony.extend(rule.dependencies.iter().cloned());
+        }
+        if rule.is_phony {
+            self.phony.extend(rule.targets.iter().cloned());
+        }
+        for target in &rule.targets {
+            if is_special_target(target) {
+                continue;
+            }
+            let (dependencies, order_only_dependencies) =
+                rule.prerequisites_of(target);
+            let target = self.index(target);
+            self.nodes[target].has_rule = true;
+            self.nodes[target].has_recipe |= rule.has_recipe;
+            let prerequisites = dependencies
+                .iter()
+                .map(|dependency| (dependency, EdgeKind::Normal))
+                .chain(
+                    order_only_dependencies
+                        .iter()
+                        .map(|dependency| (dependency, EdgeKind::OrderOnly))
+                );
+            for (prerequisite, kind) in prerequisites {
+                let prerequisite = self.index(prerequisite);
+                if !self.nodes[target]
+                    .prerequisites
+                    .contains(&(prerequisite, kind))
+                {
+                    self.nodes[target].prerequisites.push((prerequisite, kind));
+                }
+            }
+        }
+    }
+
+    fn index(&mut self, name: &str) -> usize {
+        if let Some(index) = self.indices.get(name) {
+            return *index;
+        }
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            has_rule: false,
+            has_recipe: false,
+            prerequisites: vec![]
+        });
+        self.indices.insert(name.to_string(), index);
+        index
+    }
+
+    /// Every target and prerequisite in the graph, in order of appearance.
+    pub fn targets(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(|node| node.name.as_str())
+    }
+
+    /// Whether `target` appears in the graph.
+    pub fn contains(&self, target: &str) -> bool {
+        self.indices.contains_key(target)
+    }
+
+    /// Whether `target` is the target of some rule, rather than only a
+    /// prerequisite.
+    pub fn has_rule(&self, target: &str) -> bool {
+        self.node(target).is_some_and(|node| node.has_rule)
+    }
+
+    /// Whether some rule for `target` has a recipe.
+    pub fn has_recipe(&self, target: &str) -> bool {
+        self.node(target).is_some_and(|node| node.has_recipe)
+    }
+
+    /// Whether `target` is phony.
+    pub fn is_phony(&self, target: &str) -> bool {
+        self.phony.contains(target)
+    }
+
+    /// Whether `target` is a pattern, like `%.o`.
+    pub fn is_pattern(&self, target: &str) -> bool {
+        target.contains('%')
+    }
+
+    /// The direct prerequisites of `target`, in order.
+    pub fn prerequisites(&self, target: &str) -> Vec<(&str, EdgeKind)> {
+        self.node(target)
+            .map(|node| {
+                node.prerequisites
+                    .iter()
+                    .map(|(index, kind)| {
+                        (self.nodes[*index].name.as_str(), *kind)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Everything `target` depends on, directly or indirectly, with each
+    /// prerequisite before whatever depends on it.
+    pub fn transitive_prerequisites(&self, target: &str) -> Vec<&str> {
+        let Some(start) = self.indices.get(target) else {
+            return vec![];
+        };
+        let mut visited = HashSet::from([*start]);
+        let mut order = vec![];
+        self.postorder(*start, &mut visited, &mut order);
+        order.pop();
+        order
+            .into_iter()
+            .map(|index| self.nodes[index].name.as_str())
+            .collect()
+    }
+
+    fn postorder(
+        &self, index: usize, visited: &mut HashSet<usize>,
+        order: &mut Vec<usize>
+    ) {
+        for (prerequisite, _) in &self.nodes[index].prerequisites {
+            if visited.insert(*prerequisite) {
+                self.postorder(*prerequisite, visited, order);
+            }
+        }
+        order.push(index);
+    }
+
+    /// Every target in the graph except pattern targets like `%.o`, with each
+    /// prerequisite before whatever depends on it, or
+    /// [`Error::CircularDependency`] with the path of a cycle if there is one.
+    pub fn topological_order(&self) -> Result<Vec<&str>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            Visiting,
+            Visited
+        }
+
+        fn visit(
+            graph: &RuleGraph, index: usize, states: &mut [State],
+            path: &mut Vec<usize>, order: &mut Vec<usize>
+        ) -> Result<()> {
+            states[index] = State::Visiting;
+            path.push(index);
+            for (prerequisite, _) in &graph.nodes[index].prerequisites {
+                if graph.is_pattern(&graph.nodes[*prerequisite].name) {
+                    continue;
+                }
+                match states[*prerequisite] {
+                    State::Unvisited => {
+                        visit(graph, *prerequisite, states, path, order)?
+                    }
+                    State::Visiting => {
+                        let start = path
+                            .iter()
+                            .position(|index| index == prerequisite)
+                            .expect("node being visited is on the path");
+                        let cycle = path[start..]
+                            .iter()
+                            .chain([prerequisite])
+                            .map(|index| graph.nodes[*index].name.clone())
+                            .collect();
+                        return Err(Error::CircularDependency { cycle });
+                    }
+                    State::Visited => {}
+                }
+            }
+            path.pop();
+            states[index] = State::Visited;
+            order.push(index);
+            Ok(())
+        }
+
+        let mut states = vec![State::Unvisited; self.nodes.len()];
+        let mut order = vec![];
+        for index in 0..self.nodes.len() {
+            if states[index] == State::Unvisited
+                && !self.is_pattern(&self.nodes[index].name)
+            {
+                visit(self, index, &mut states, &mut vec![], &mut order)?;
+            }
+        }
+        Ok(order
+            .into_iter()
+            .map(|index| self.nodes[index].name.as_str())
+            .collect())
+    }
+
+    /// The targets of rules that nothing depends on, such as the goals a user
+    /// would run make with, in order of appearance. Pattern targets like `%.o`
+    /// are never goals and so are excluded.
+    pub fn unreferenced_targets(&self) -> Vec<&str> {
+        let referenced = self
+            .nodes
+            .iter()
+            .flat_map(|node| &node.prerequisites)
+            .map(|(index, _)| *index)
+            .collect::<HashSet<_>>();
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(index, node)| {
+                node.has_rule
+                    && !referenced.contains(index)
+                    && !self.is_pattern(&node.name)
+            })
+            .map(|(_, node)| node.name.as_str())
+            .collect()
+    }
+
+    /// The text of every target or prerequisite that could not be expanded
+    /// without running make and is therefore missing from the graph.
+    pub fn unresolved(&self) -> &[String] {
+        &self.unresolved
+    }
+
+    fn node(&self, target: &str) -> Option<&Node> {
+        self.indices.get(target).map(|index| &self.nodes[*index])
+    }
+}
@@ -11,8 +11,9 @@
 //!
 //! Although version-agnostic, `makemake` does support GNU `make` features. If
 //! you intend your Makefiles to be extremely portable (although even macOS
-//! comes with GNU `make`), avoid those features as you would when writing
-//! Makefiles by hand.
+//! comes with GNU `make`), construct them with
+//! `Makefile::with_dialect(Dialect::Posix2024)`: building them then reports
+//! every GNU feature used, lowering those with a POSIX equivalent.
 //!
 //! ## Usage
 //!
@@ -59,11 +60,13 @@
 //! ));
 //! ```
 //! > In the `expr!` macro, use `;` to separate arguments by spaces and `,` to
-//! > put them directly adjacent.
+//! > put them directly adjacent. Alternatively, the `mk!` macro works like
+//! > `format!`: `mk!("{cc} {cflags} -o {} {}", target, deps)`.
 //!
-//! Finally, we can print the resultant Makefile.
+//! Finally, we can print the resultant Makefile. Building it reports every
+//! problem that would make it invalid, such as a variable name with spaces.
 //! ```rs
-//! print!("{}", makefile.build());
+//! print!("{}", makefile.build()?);
 //! ```
 //!
 //! Indeed, we can use our Makefile to build an example project.
@@ -75,6 +78,35 @@
 //! The actual example (`c_project.rs` in the `examples/` directory) also comes
 //! with a `make clean`!
 //!
+//! Since every C project repeats this, [`CProject`](c_project::CProject)
+//! derives it for you, along with object files under a build directory,
+//! header dependencies, debug and release flags, and libraries.
+//! ```rs
+//! CProject::executable("main")
+//!     .source("main.c")
+//!     .source("src/*.c")
+//!     .lib("m")
+//!     .emit(&mut makefile);
+//! ```
+//!
+//! The same rules give clangd its `compile_commands.json`, through
+//! [`Makefile::compile_commands`](makefile::Makefile::compile_commands).
+//!
+//! ### Without Rust
+//!
+//! With the `toml` feature, the [`manifest`] module reads a Makefile described
+//! in TOML. The `makemake` binary, built with the `cli` feature, turns a
+//! `Makefile.toml` into a `Makefile`, and can also format, lint, graph,
+//! convert, and explain existing Makefiles, which it reads with the [`reader`]
+//! module.
+//! ```shell
+//! cargo install makemake --features cli
+//! makemake Makefile.toml Makefile
+//! makemake fmt --check Makefile
+//! makemake convert Makefile build.ninja
+//! makemake convert compile_commands.json Makefile
+//! ```
+//!
 //! ### Setting Up Git Hooks
 //!
 //! After cloning the repository, run the following script to set up the hooks:
@@ -87,28 +119,73 @@
 //! This project is licensed under the [LGPL License](LICENSE), a copy of which
 //! is available in this directory.
 
+extern crate self as makemake;
+
+pub use error::{Error, Result};
+pub use makemake_macros::mk;
+
 pub mod assignment;
+mod bsd;
+pub mod c_project;
+pub mod cargo;
+pub mod catalog;
+pub mod compile_commands;
 pub mod conditional;
+pub mod dialect;
 pub mod directive;
 pub mod emittable;
 pub mod emitter;
+mod entry;
+pub mod error;
+pub mod eval;
+#[cfg(feature = "executor")]
+pub mod executor;
+pub mod explain;
+pub mod export;
 pub mod expr;
 pub mod function;
+pub mod graph;
+pub mod lint;
 pub mod makefile;
+#[cfg(feature = "toml")]
+pub mod manifest;
 pub mod misc;
+pub mod namespace;
+mod ninja;
+pub mod plan;
 pub mod prelude;
+pub mod reader;
 pub mod rrc;
 pub mod rule;
+#[cfg(feature = "serde")]
+pub mod schema;
+mod simulate;
 pub mod symbol_context;
+mod syntax;
 pub mod var;
+pub mod version;
 
 #[cfg(test)]
 mod tests {
     use crate::{
+        c_project::CProject,
+        compile_commands::{self, CompileCommand, CompileCommandsOptions},
+        dialect::Dialect,
         emitter::Emitter,
+        error::Error,
+        eval::{Env, EvalError, Flavor},
+        explain::{Explanation, Reason},
+        export::ExportOptions,
         expr,
-        function::{Function, Substitution},
-        makefile::Makefile
+        expr::Expr,
+        function::{FileOp, Function, FunctionError, Substitution},
+        graph::EdgeKind,
+        lint::{Diagnostic, LintOptions},
+        makefile::Makefile,
+        mk,
+        plan::VirtualFileSystem,
+        var::VariableName,
+        version::{Requirement, Version}
     };
     use insta::assert_snapshot;
 
@@ -144,6 +221,1413 @@ mod tests {
             .when_def("b", |e| e.comment("b"))
             .otherwise(|e| e.comment("c"));
 
-        assert_snapshot!(makefile.build());
+        assert_snapshot!(makefile.build().unwrap());
+    }
+
+    #[test]
+    fn test_namespace() {
+        let mut makefile = Makefile::new();
+        let src = makefile.assign("SRC", "main.c");
+        makefile.namespace("libfoo", |ns| {
+            let lib_src = ns.assign("SRC", "foo.c");
+            ns.namespace("test", |ns| {
+                ns.assign("SRC", expr!(lib_src; "test.c"));
+            });
+            let tmp = ns.fresh("tmp");
+            ns.assign(tmp, expr!(src; lib_src));
+        });
+        let tmp = makefile.fresh("tmp");
+        makefile.assign(tmp, "top");
+        makefile.namespace("empty", |_| {});
+        let user_tmp = makefile.assign("tmp_0", "user");
+        assert_eq!(makefile.symbols().name(tmp), "tmp_1");
+        assert_eq!(makefile.symbols().name(user_tmp), "tmp_0");
+        let generated = makefile
+            .symbols()
+            .generated_names()
+            .into_iter()
+            .map(|(name, original)| format!("{} <- {}", name, original))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            generated,
+            [
+                "libfoo_SRC <- SRC",
+                "libfoo_test_SRC <- SRC",
+                "libfoo_tmp_0 <- tmp",
+                "tmp_1 <- tmp"
+            ]
+        );
+
+        assert_snapshot!(makefile.build().unwrap());
+    }
+
+    #[test]
+    fn test_fresh_names_are_frozen() {
+        let mut makefile = Makefile::new();
+        let tmp = makefile.fresh("tmp");
+        makefile.assign(tmp, "x");
+        makefile
+            .rule("all")
+            .cmd(Expr::from("echo").then(VariableName::from("tmp_0")));
+        let output = makefile.render().unwrap();
+        assert_eq!(output, "tmp_0 = x\nall: \n\techo $(tmp_0)\n");
+        assert_eq!(makefile.render().unwrap(), output);
+        assert_eq!(makefile.symbols().name(tmp), "tmp_0");
+    }
+
+    #[test]
+    fn test_variable_references() {
+        let mut makefile = Makefile::new();
+        let arch = makefile.assign("ARCH", "x86_64");
+        makefile.assign("x86_64_CFLAGS", "-march=x86-64");
+        let src = makefile.assign("SRC", "main.c util.c");
+        makefile.assign("OBJ", Substitution::pattern(src, "%.c", "build/%.o"));
+        makefile.assign(
+            "ARCH_OBJ",
+            Substitution::new(
+                VariableName::computed(expr!(arch, "_SRC")),
+                ".c",
+                ".o"
+            )
+        );
+        let target = makefile.target_var();
+        makefile.rule("main").cmd(expr!(
+            "cc";
+            VariableName::computed(expr!(arch, "_CFLAGS"));
+            VariableName::computed(expr!("CFLAGS_", target))
+        ));
+
+        assert_snapshot!(makefile.build().unwrap());
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut makefile = Makefile::new();
+        let src = makefile.assign("SRC", "a.c b.c");
+        makefile
+            .assign("OBJ", Function::addsuffix(".o", [expr!("a"), expr!("b")]));
+        makefile
+            .assign("C", Function::filter(["%.c", "%.h"].map(Expr::from), src));
+        makefile.assign("IF", Function::if_(src, "yes", None));
+        makefile.assign(
+            "CMP",
+            Function::intcmp("1", "2", None, Some("eq".into()), None)
+        );
+        makefile.assign("OR", Function::or(["", "b"].map(Expr::from)));
+        makefile
+            .assign("ARGS", Function::call("f", ["x", "y"].map(Expr::from)));
+        makefile.assign(
+            "LET",
+            Function::let_(["a", "b"].map(Expr::from), "1 2", "$(a)")
+        );
+        makefile.assign("ERR", Function::error(vec![]));
+        makefile.assign(
+            "FILE",
+            Function::file(FileOp::Write, "out", Some(src.into()))
+        );
+        makefile.assign(
+            "GUILE",
+            Function::builtin("guile", ["(+ 1 2)"].map(Expr::from)).unwrap()
+        );
+
+        assert!(matches!(
+            Function::builtin("if", [expr!("cond")]),
+            Err(FunctionError::Arity {
+                given: 1,
+                min: 2,
+                max: Some(3),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Function::builtin("addsufix", []),
+            Err(FunctionError::Unknown { .. })
+        ));
+
+        assert_snapshot!(makefile.build().unwrap());
+
+        let mut makefile = Makefile::new();
+        makefile.assign("OR", Function::or(vec![]));
+        assert!(matches!(
+            makefile.build().err().unwrap(),
+            Error::Function(FunctionError::Arity {
+                given: 0,
+                min: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_response_file() {
+        let mut makefile = Makefile::new();
+        let obj = makefile.assign("OBJ", "a.o b.o");
+        let ld = makefile.var("LD");
+        makefile.rule("short").cmd_with_response_file(
+            ld,
+            [expr!("-o"), expr!("short"), expr!(obj)]
+        );
+        let target = makefile.target_var();
+        makefile
+            .rule("long")
+            .response_file_threshold(0)
+            .cmd_with_response_file(
+                ld,
+                [expr!("-o"), expr!(target), expr!(obj)]
+            );
+        let deps = makefile.deps_var();
+        let many = makefile.assign("MANY", "a.o b.o c.o d.o e.o f.o");
+        makefile
+            .rule("many")
+            .dep(many)
+            .response_file_threshold(16)
+            .cmd_with_response_file(ld, [expr!(deps)]);
+        makefile
+            .rule("few")
+            .dep("a.o")
+            .response_file_threshold(16)
+            .cmd_with_response_file(ld, [expr!(deps)]);
+
+        assert_snapshot!(makefile.build().unwrap());
+    }
+
+    #[test]
+    fn test_mk() {
+        let mut makefile = Makefile::new();
+        let cc = makefile.assign("CC", "cc");
+        let cflags = makefile.assign("CFLAGS", "-Wall");
+        let target = makefile.target_var();
+        let deps = makefile.deps_var();
+        makefile
+            .rule("main")
+            .cmd(mk!("{cc} {cflags} -o {} {}", target, deps))
+            .cmd(mk!("echo {{{0}}} {0} {flag}", target, flag = "-n"))
+            .cmd(mk!("{}{}", Function::notdir([expr!("a/b")]), ".txt"));
+
+        assert_snapshot!(makefile.build().unwrap());
+    }
+
+    #[test]
+    fn test_mk_arity() {
+        let cases = trybuild::TestCases::new();
+        cases.compile_fail("tests/ui/mk_*.rs");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut makefile = Makefile::new();
+        let target = makefile.assign("TARGET", "main");
+        makefile.rule(target).dep("main.c").cmd("cc -o $@ $^");
+
+        let first = makefile.render().unwrap();
+        let mut streamed = vec![];
+        makefile.write_to(&mut streamed).unwrap();
+        assert_eq!(first, String::from_utf8(streamed).unwrap());
+        assert_eq!(first, makefile.render().unwrap());
+        assert_eq!(first, makefile.build().unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut makefile = Makefile::new();
+        makefile.assign("BAD NAME", "x");
+        makefile.assign("A=B", "x");
+        makefile.rule(" ").cmd("true");
+        makefile.var("UNUSED NAME");
+        makefile.branch_tree().otherwise(|e| e.comment("first"));
+        makefile
+            .branch_tree()
+            .when_def("A", |e| e.comment("a"))
+            .otherwise(|e| e.comment("b"))
+            .otherwise(|e| e.comment("c"));
+        makefile.branch_tree();
+
+        let Err(Error::Multiple(errors)) = makefile.build() else {
+            panic!("expected multiple errors");
+        };
+        let messages = errors.iter().map(Error::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "rule target is empty",
+                "conditional begins with an `else` branch",
+                "conditional has a branch after its `else` branch",
+                "conditional has no branches",
+                "variable name `BAD NAME` contains invalid character ' '",
+                "variable name `A=B` contains invalid character '='"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branch_after_else() {
+        let mut makefile = Makefile::new();
+        makefile
+            .branch_tree()
+            .when_def("A", |e| e.comment("a"))
+            .otherwise(|e| e.comment("b"))
+            .when_def("B", |e| e.comment("c"));
+        assert!(matches!(makefile.render(), Err(Error::BranchAfterElse)));
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut makefile = Makefile::new();
+        let cc = makefile.var("CC");
+        let src = makefile.assign("SRC", "main.c");
+        makefile.assign("UNUSED", "$(HOME) $(TOOLCHAIN)");
+        let a = makefile.var("A");
+        let b = makefile.assign("B", expr!(a; "-O2"));
+        makefile.assign(a, expr!(b));
+        makefile.assign("LIST", "$(foreach x,$(SRC),$(x).o)");
+        makefile.assign("CFLAGS", "-O2");
+        makefile
+            .branch_tree()
+            .when_def("DEBUG", |e| e.comment("debug"));
+        makefile.rule("all").phony().dep("main");
+        makefile
+            .rule("main")
+            .dep(src)
+            .dep("all")
+            .cmd(expr!(cc; src));
+        makefile.rule("main").cmd("true");
+
+        assert_eq!(
+            makefile.lint(),
+            [
+                Diagnostic::UndefinedVariable {
+                    name: "TOOLCHAIN".into()
+                },
+                Diagnostic::UnusedVariable {
+                    name: "UNUSED".into()
+                },
+                Diagnostic::UnusedVariable {
+                    name: "LIST".into()
+                },
+                Diagnostic::OverriddenRecipe {
+                    target: "main".into()
+                },
+                Diagnostic::PhonyPrerequisite {
+                    phony: "all".into(),
+                    target: "main".into()
+                },
+                Diagnostic::SelfReferentialVariable {
+                    name: "B".into(),
+                    cycle: vec!["B".into(), "A".into(), "B".into()]
+                }
+            ]
+        );
+        assert!(makefile
+            .lint_with(&LintOptions::new().allow_none().allow("TOOLCHAIN"))
+            .contains(&Diagnostic::UndefinedVariable {
+                name: "HOME".into()
+            }));
+    }
+
+    #[test]
+    fn test_lint_append_to_undefined() {
+        let mut makefile = Makefile::new();
+        makefile.append("X", "$(X) -g");
+        makefile.assign_simple("Y", "a");
+        makefile.append("Y", "$(Y) b");
+        makefile.rule("all").cmd("echo $(X) $(Y)");
+
+        assert_eq!(
+            makefile.lint(),
+            [Diagnostic::SelfReferentialVariable {
+                name: "X".into(),
+                cycle: vec!["X".into(), "X".into()]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_graph() {
+        let mut makefile = Makefile::new();
+        let obj = makefile.assign("OBJ", "a.o b.o");
+        let target = makefile.assign("TARGET", "main");
+        makefile.rule("all").phony().dep(target);
+        makefile.rule(target).dep(obj).order_only_dep("build");
+        makefile.rule("%.o").dep("%.c").cmd("cc -c $<");
+        makefile.rule("build").cmd("mkdir -p build");
+        makefile
+            .rule("docs")
+            .dep(Function::wildcard([expr!("*.md")]));
+
+        let graph = makefile.graph();
+        assert_eq!(
+            graph.prerequisites("main"),
+            [
+                ("a.o", EdgeKind::Normal),
+                ("b.o", EdgeKind::Normal),
+                ("build", EdgeKind::OrderOnly)
+            ]
+        );
+        assert_eq!(
+            graph.transitive_prerequisites("all"),
+            ["a.o", "b.o", "build", "main"]
+        );
+        assert_eq!(
+            graph.topological_order().unwrap(),
+            ["a.o", "b.o", "build", "main", "all", "docs"]
+        );
+        assert_eq!(graph.unreferenced_targets(), ["all", "docs"]);
+        assert_eq!(graph.unresolved(), ["$(wildcard *.md)"]);
+        assert!(graph.is_phony("all") && !graph.is_phony("main"));
+
+        makefile.rule("build").dep("all");
+        let Err(Error::CircularDependency { cycle }) =
+            makefile.graph().topological_order()
+        else {
+            panic!("expected circular dependency");
+        };
+        assert_eq!(cycle, ["all", "main", "build", "all"]);
+        assert_eq!(
+            Error::CircularDependency { cycle }.to_string(),
+            "circular dependency `all -> main -> build -> all`"
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let mut makefile = Makefile::new();
+        makefile.rule("all").phony().dep("main").dep("docs");
+        makefile
+            .rule("main")
+            .dep("a.o")
+            .dep("b.o")
+            .order_only_dep("build");
+        makefile.rule("%.o").dep("%.c").cmd("cc -c $<");
+        makefile.rule("build").cmd("mkdir -p build");
+        makefile.rule("docs").dep("\"quoted\".md");
+
+        let graph = makefile.graph();
+        assert_snapshot!(graph.to_dot(&ExportOptions::new()).unwrap());
+        assert_snapshot!(graph
+            .to_mermaid(&ExportOptions::new().collapse_patterns().goal("main"))
+            .unwrap());
+        assert!(matches!(
+            graph.to_dot(&ExportOptions::new().goal("missing")),
+            Err(Error::UnknownTarget { .. })
+        ));
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut makefile = Makefile::new();
+        let src = makefile.assign("SRC", "main.c util.c");
+        let obj = makefile.assign("OBJ", Substitution::new(src, ".c", ".o"));
+        let now = makefile.assign_simple("NOW", "$(SRC)");
+        makefile.append(src, "extra.c");
+        makefile.append(now, "$(OBJ)");
+        makefile.assign_without_overwrite("CC", "gcc");
+        makefile.assign_without_overwrite(src, "ignored.c");
+        makefile
+            .branch_tree()
+            .when_eq("$(CC)", "gcc", |branch| {
+                branch.assign("WARN", "-Wall");
+            })
+            .otherwise(|branch| {
+                branch.assign("WARN", "");
+            });
+        makefile.assign(
+            "reverse",
+            "$(if $(1),$(call reverse,$(wordlist 2,$(words $(1)),$(1))) \
+             $(firstword $(1)))"
+        );
+        makefile.assign("loop", "$(loop)");
+
+        let env = Env::from_makefile(&makefile)
+            .unwrap()
+            .with_shell(|command| format!("ran {}\n", command))
+            .with_wildcard(|pattern| vec![pattern.replace('*', "a")]);
+        assert_eq!(
+            Expr::from(obj).evaluate(&env).unwrap(),
+            "main.o util.o extra.o"
+        );
+        assert_eq!(
+            env.get("NOW").unwrap(),
+            "main.c util.c main.o util.o extra.o"
+        );
+        assert_eq!(env.get("WARN").unwrap(), "-Wall");
+        assert_eq!(env.flavor("NOW"), Some(Flavor::Simple));
+        assert_eq!(
+            Expr::from(Function::patsubst("%.c", "build/%.o", src))
+                .evaluate(&env)
+                .unwrap(),
+            "build/main.o build/util.o build/extra.o"
+        );
+        for (text, expected) in [
+            ("$(sort b a c a)", "a b c"),
+            ("$(subst a,x,abc) $(subst ,x,ab)", "xbc abx"),
+            ("$(filter %.c,a.c b.h c.c)", "a.c c.c"),
+            ("$(word 2,$(SRC))", "util.c"),
+            ("$(foreach x,a b,<$(x)>)", "<a> <b>"),
+            ("$(let a b,1 2 3,$(b)-$(a))", "2 3-1"),
+            ("$(strip $(call reverse,a b c))", "c b a"),
+            ("$(basename src/a.c b)$(suffix src/a.c b)", "src/a b.c"),
+            ("$(shell echo hi) $(wildcard *.c)", "ran echo hi a.c"),
+            ("$(or ,$(CC)) $(and x,) $(intcmp 1,2,lt,eq,gt)", "gcc  lt")
+        ] {
+            assert_eq!(env.expand(text).unwrap(), expected);
+        }
+        assert_eq!(
+            env.get("loop"),
+            Err(EvalError::RecursiveVariable {
+                name: "loop".into()
+            })
+        );
+        assert_eq!(
+            env.expand("$(error oops)"),
+            Err(EvalError::Error {
+                message: "oops".into()
+            })
+        );
+    }
+
+    #[cfg(feature = "executor")]
+    #[test]
+    fn test_executor() {
+        use crate::executor::{Executor, Outcome};
+
+        let dir = std::env::temp_dir()
+            .join(format!("makemake-executor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).display().to_string();
+        std::fs::write(path("in.txt"), "input").unwrap();
+
+        let mut makefile = Makefile::new();
+        makefile.rule(".DELETE_ON_ERROR");
+        makefile
+            .rule("all")
+            .phony()
+            .dep(path("out.txt"))
+            .dep(path("bad"));
+        makefile.rule("%.txt").dep("%.in").cmd("cp $< $@");
+        makefile
+            .rule(path("out.txt"))
+            .dep(path("in.txt"))
+            .cmd("@cp $< $@");
+        makefile.rule(path("bad")).cmd("@echo partial > $@; exit 3");
+
+        let executor = Executor::new(&makefile).jobs(2).keep_going();
+        let report = executor.run(&["all"]).unwrap();
+        assert_eq!(report.outcome(&path("out.txt")), Some(&Outcome::Built));
+        assert_eq!(
+            report.outcome(&path("bad")),
+            Some(&Outcome::Failed { exit_code: Some(3) })
+        );
+        assert_eq!(report.outcome("all"), Some(&Outcome::Skipped));
+        assert!(!report.is_success());
+        assert!(!dir.join("bad").exists());
+        assert_eq!(std::fs::read_to_string(path("out.txt")).unwrap(), "input");
+
+        let report = executor.run(&[path("out.txt")]).unwrap();
+        assert_eq!(report.outcome(&path("out.txt")), Some(&Outcome::UpToDate));
+        let report = executor.run(&[path("missing")]).unwrap();
+        assert_eq!(report.outcome(&path("missing")), Some(&Outcome::NoRule));
+
+        let mut makefile = Makefile::new();
+        let inputs = makefile
+            .assign_simple("IN", Function::wildcard([expr!(path("*.txt"))]));
+        let count = makefile.assign_simple(
+            "COUNT",
+            Function::shell(expr!("echo"; Function::words(inputs)))
+        );
+        makefile
+            .rule(path("all.cat"))
+            .dep(inputs)
+            .cmd(expr!("@cat $^ > $@ && echo"; count; ">> $@"));
+        let report = Executor::new(&makefile).run(&[path("all.cat")]).unwrap();
+        assert_eq!(report.outcome(&path("all.cat")), Some(&Outcome::Built));
+        assert_eq!(
+            std::fs::read_to_string(path("all.cat")).unwrap(),
+            "inputinput2\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut makefile = Makefile::new();
+        makefile.rule("all").phony().dep("main");
+        makefile
+            .rule("main")
+            .dep("a.o")
+            .dep("b.o")
+            .cmd("cc -o $@ $^");
+        makefile.rule("%.o").dep("%.c").cmd("cc -c $<");
+        makefile.rule("b.o").dep("b.h");
+
+        let files = VirtualFileSystem::from_iter([
+            ("main", 10),
+            ("a.o", 5),
+            ("a.c", 1),
+            ("b.o", 5),
+            ("b.c", 1),
+            ("b.h", 7)
+        ]);
+        let explanations = makefile.explain("all", &files).unwrap();
+        assert_eq!(
+            explanations,
+            [
+                Explanation {
+                    target: "b.o".into(),
+                    reasons: vec![Reason::NewerPrerequisite {
+                        prerequisite: "b.h".into()
+                    }]
+                },
+                Explanation {
+                    target: "main".into(),
+                    reasons: vec![Reason::RemadePrerequisite {
+                        prerequisite: "b.o".into()
+                    }]
+                },
+                Explanation {
+                    target: "all".into(),
+                    reasons: vec![Reason::Phony]
+                }
+            ]
+        );
+        assert_eq!(
+            explanations[0].to_string(),
+            "`b.o` is remade because prerequisite `b.h` is newer"
+        );
+
+        let files = VirtualFileSystem::from_iter([("a.c", 1), ("b.c", 1)]);
+        assert!(matches!(
+            makefile.explain("main", &files),
+            Err(Error::UnknownTarget { target }) if target == "b.h"
+        ));
+
+        let mut makefile = Makefile::new();
+        let src =
+            makefile.assign("SRC", Function::wildcard([expr!("src/*.c")]));
+        makefile.rule("lib.a").dep(src).cmd("ar rcs $@ $^");
+        let files = VirtualFileSystem::from_iter([
+            ("lib.a", 5),
+            ("src/a.c", 1),
+            ("src/b.c", 7),
+            ("src/b.h", 9),
+            ("src/sub/c.c", 9)
+        ]);
+        assert_eq!(
+            makefile.explain("lib.a", &files).unwrap(),
+            [Explanation {
+                target: "lib.a".into(),
+                reasons: vec![Reason::NewerPrerequisite {
+                    prerequisite: "src/b.c".into()
+                }]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_simulate() {
+        let mut makefile = Makefile::new();
+        let cc = makefile.assign("CC", "cc");
+        let obj = makefile.assign("OBJ", "build/a.o build/b.o");
+        makefile.rule("all").phony().dep("main");
+        let target = makefile.target_var();
+        makefile
+            .rule("main")
+            .dep(obj)
+            .cmd(expr!(cc; "-o"; target; "$^"));
+        makefile
+            .rule("build/%.o")
+            .dep("src/%.c")
+            .order_only_dep("build")
+            .cmd("@echo compiling $*")
+            .cmd(expr!(cc; "-c $< -o $@"));
+        makefile.rule("build").cmd("mkdir -p $@");
+
+        let files = VirtualFileSystem::new()
+            .with_file("src/a.c", 1)
+            .with_file("src/b.c", 3)
+            .with_file("build", 1)
+            .with_file("build/a.o", 2)
+            .with_file("build/b.o", 2)
+            .with_file("main", 2);
+        assert_eq!(
+            makefile.simulate("all", &files).unwrap(),
+            [
+                "echo compiling b",
+                "cc -c src/b.c -o build/b.o",
+                "cc -o main build/a.o build/b.o"
+            ]
+        );
+
+        let files = VirtualFileSystem::new()
+            .with_file("src/a.c", 1)
+            .with_file("src/b.c", 1);
+        assert_eq!(
+            makefile.simulate("build/a.o", &files).unwrap(),
+            [
+                "mkdir -p build",
+                "echo compiling a",
+                "cc -c src/a.c -o build/a.o"
+            ]
+        );
+        let mut makefile = Makefile::new();
+        makefile
+            .rule("app")
+            .dep(Function::wildcard([expr!("src/*.c"), expr!("[ab].h")]))
+            .cmd("cc -o $@ $^");
+        let files = VirtualFileSystem::from_iter([
+            ("src/b.c", 1),
+            ("src/a.c", 1),
+            ("src/.hidden.c", 1),
+            ("a.h", 1),
+            ("c.h", 1)
+        ]);
+        assert_eq!(
+            makefile.simulate("app", &files).unwrap(),
+            ["cc -o app src/a.c src/b.c a.h"]
+        );
+    }
+
+    #[test]
+    fn test_ninja() {
+        let mut makefile = Makefile::new();
+        let cc = makefile.assign("CC", "cc");
+        let obj = makefile.assign("OBJ", "build/main.o build/util.o");
+        makefile
+            .branch_tree()
+            .when_eq(cc, "cc", |branch| {
+                branch.assign("CFLAGS", "-O2");
+            })
+            .otherwise(|branch| {
+                branch.assign("CFLAGS", "-O0");
+            });
+        makefile
+            .rule("all")
+            .phony()
+            .dep("app")
+            .dep("docs/index.html");
+        makefile.rule("app").dep(obj).cmd("$(CC) -o $@ $^");
+        makefile
+            .rule(obj)
+            .static_pattern("build/%.o")
+            .dep("src/%.c")
+            .order_only_dep("build")
+            .cmd("$(CC) $(CFLAGS) -c $< -o $@");
+        makefile.rule("build").cmd("mkdir -p $@");
+        makefile
+            .rule("docs/%.html")
+            .dep("docs/%.md")
+            .cmd("-pandoc $< > $@");
+        makefile.rule("clean").phony().cmd("rm -rf build app");
+        makefile
+            .rule("docs/index.md")
+            .cmd("cd docs")
+            .cmd("-ls > $@")
+            .cmd("touch $@");
+
+        assert_snapshot!(makefile.render().unwrap());
+        assert_snapshot!(makefile.render_ninja().unwrap());
+
+        makefile.assign_simple("REV", "$(shell git rev-parse HEAD)");
+        assert!(matches!(
+            makefile.render_ninja(),
+            Err(Error::Unsupported { construct, .. })
+                if construct == "`$(shell)`"
+        ));
+    }
+
+    #[test]
+    fn test_dialect() {
+        let mut makefile = Makefile::with_dialect(Dialect::Posix2024);
+        let cc = makefile.assign_without_overwrite("CC", "cc");
+        let rev = makefile.assign_simple("REV", Function::shell("git log -1"));
+        let obj = makefile.assign_simple("OBJ", "main.o util.o");
+        makefile
+            .rule("app")
+            .dep(obj)
+            .cmd(expr!(cc; "-o $@"; obj; "#"; rev));
+        makefile.rule("clean").phony().cmd("rm -f app $(OBJ)");
+        assert_snapshot!(makefile.render().unwrap());
+
+        makefile.assign("SRC", Function::wildcard([expr!("*.c")]));
+        makefile.append("REV", Function::shell("date"));
+        makefile.assign("NOW", Function::shell("date"));
+        makefile.rule("%.o").dep("%.c").order_only_dep("build");
+        makefile.branch_tree().when_def("DEBUG", |branch| {
+            branch.assign("CFLAGS", "-g");
+        });
+        assert_eq!(
+            makefile.render().unwrap_err().to_string(),
+            "`$(wildcard)` in the value of `SRC` has no equivalent in POSIX \
+             make\n`$(shell)` in the value of `REV` has no equivalent in POSIX \
+             make\n`$(shell)` in the value of `NOW` has no equivalent in POSIX \
+             make\nthe pattern rule for `%.o` has no equivalent in POSIX \
+             make\nthe order-only prerequisite `build` in the rule for `%.o` \
+             has no equivalent in POSIX make\nthe conditional `ifdef DEBUG` \
+             has no equivalent in POSIX make"
+        );
+    }
+
+    #[test]
+    fn test_bsd() {
+        let mut makefile = Makefile::new();
+        makefile.comment("Generated for bmake");
+        let src = makefile.assign("SRC", "src/main.c src/util.c");
+        let obj = makefile
+            .assign("OBJ", Substitution::pattern(src, "src/%.c", "build/%.o"));
+        makefile.assign("HDR", Substitution::new(src, ".c", ".h"));
+        makefile.assign_simple("REV", Function::shell("git rev-parse HEAD"));
+        makefile.append(
+            "CFLAGS",
+            Function::addprefix(
+                "-I",
+                [Expr::from(Function::dir([Expr::from(src)]))]
+            )
+        );
+        makefile.assign(
+            "NAMES",
+            Function::sort(Function::basename([Expr::from(Function::notdir(
+                [Expr::from(src)]
+            ))]))
+        );
+        makefile.assign("FIRST", Function::firstword([Expr::from(obj)]));
+        makefile.assign(
+            "MAIN",
+            Function::filter([expr!("%main.c")], Function::strip(src))
+        );
+        makefile
+            .assign("LIBS", Function::foreach("lib", "m pthread", "-l$(lib)"));
+        makefile
+            .branch_tree()
+            .when_eq(Function::subst("/", "_", "$(CC)"), "cc", |branch| {
+                branch.assign("LD", "cc");
+            })
+            .when_def("DEBUG", |branch| {
+                branch.append("CFLAGS", "-g");
+            })
+            .otherwise(|branch| {
+                branch.append("CFLAGS", "-O2");
+            });
+        makefile.include("config.mk");
+        makefile.rule("all").phony().dep("app");
+        makefile
+            .rule("app")
+            .dep(obj)
+            .cmd("$(CC) -o $@ $^ $(LIBS)")
+            .cmd("@echo built $(@F) in $(@D)");
+        makefile
+            .rule("%.o")
+            .dep("%.c")
+            .cmd("$(CC) $(CFLAGS) -c $< -o $@");
+        assert_snapshot!(makefile.render_bsd().unwrap());
+
+        makefile.rule("build/a.o").order_only_dep("build");
+        makefile.assign("SRC", Function::wildcard([expr!("src/*.c")]));
+        assert_eq!(
+            makefile.render_bsd().unwrap_err().to_string(),
+            "the order-only prerequisite `build` in the rule for `build/a.o` \
+             has no equivalent in BSD make\n`$(wildcard)` in the value of \
+             `SRC` has no equivalent in BSD make"
+        );
+    }
+
+    #[test]
+    fn test_required_version() {
+        let mut makefile = Makefile::new();
+        makefile.set_version_guard(true);
+        makefile.rule("all").phony().dep("app");
+        assert_eq!(makefile.required_version(), Version::OLDEST);
+        assert_eq!(makefile.render().unwrap(), ".PHONY: all\nall: app\n");
+
+        makefile.rule("app").dep("a.o").order_only_dep("build");
+        makefile
+            .branch_tree()
+            .when_def("DEBUG", |branch| {
+                branch.assign("CFLAGS", "-g");
+            })
+            .when_def("RELEASE", |branch| {
+                branch.assign("LAST", Function::lastword([expr!("a b")]));
+            });
+        makefile.rule("check").dep("a").dep(".WAIT").dep("b");
+        makefile.assign(
+            "MAX",
+            Function::intcmp("1", "2", Some(expr!("2")), None, None)
+        );
+        assert_eq!(
+            makefile.requirements(),
+            vec![
+                Requirement {
+                    feature: "order-only prerequisites".into(),
+                    since: Version::new(3, 80)
+                },
+                Requirement {
+                    feature: "an `else` with a condition".into(),
+                    since: Version::new(3, 81)
+                },
+                Requirement {
+                    feature: "the function `lastword`".into(),
+                    since: Version::new(3, 81)
+                },
+                Requirement {
+                    feature: "the special target `.WAIT`".into(),
+                    since: Version::new(4, 4)
+                },
+                Requirement {
+                    feature: "the function `intcmp`".into(),
+                    since: Version::new(4, 4)
+                }
+            ]
+        );
+        assert_eq!(makefile.required_version(), Version::new(4, 4));
+        assert_snapshot!(makefile.render().unwrap());
+    }
+
+    #[test]
+    fn test_required_version_of_grouped_targets() {
+        let mut makefile = Makefile::new();
+        makefile.rule("parser.c parser.h &").dep("parser.y");
+        assert_eq!(
+            makefile.requirements(),
+            vec![Requirement {
+                feature: "grouped targets".into(),
+                since: Version::new(4, 3)
+            }]
+        );
+        makefile.set_version_guard(true);
+        let output = makefile.render().unwrap();
+        assert!(output.contains("is required for grouped targets"));
+        assert!(output.ends_with("parser.c parser.h &: parser.y\n"));
+    }
+
+    #[test]
+    fn test_required_version_of_immediate_assignment() {
+        let mut makefile = Makefile::new();
+        makefile.assign("NAME", "app");
+        makefile.assign_immediate("OUT", "$(NAME).$$$$");
+        makefile.assign("NAME", "lib");
+        assert_eq!(
+            makefile.requirements(),
+            vec![Requirement {
+                feature: "the assignment `:::=`".into(),
+                since: Version::new(4, 4)
+            }]
+        );
+        let output = makefile.render().unwrap();
+        assert!(output.contains("OUT :::= $(NAME).$$$$\n"));
+        assert_eq!(
+            crate::reader::parse(&output, "Makefile")
+                .unwrap()
+                .build()
+                .unwrap(),
+            output
+        );
+
+        let env = Env::from_makefile(&makefile).unwrap();
+        assert_eq!(env.get("OUT").unwrap(), "app.$$");
+        assert_eq!(env.flavor("OUT"), Some(Flavor::Recursive));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut makefile = Makefile::new();
+        makefile.set_version_guard(true);
+        makefile.comment("Serialized");
+        let src = makefile.assign("SRC", Function::wildcard([expr!("*.c")]));
+        let obj = makefile.assign("OBJ", Substitution::new(src, ".c", ".o"));
+        makefile.namespace("lib", |ns| {
+            ns.assign_simple("FLAGS", expr!("-I"; Function::dir([expr!(src)])));
+        });
+        makefile
+            .branch_tree()
+            .when_eq("$(CC)", "cc", |branch| {
+                branch.append("CFLAGS", "-Wall");
+            })
+            .when_def("DEBUG", |branch| {
+                branch.newline();
+            })
+            .otherwise(|branch| {
+                branch.include("release.mk");
+            });
+        makefile
+            .rule("app")
+            .phony()
+            .dep(obj)
+            .order_only_dep("build")
+            .cmd(expr!("cc -o"; makefile.target_var(); makefile.deps_var()))
+            .cmd_with_response_file("ar rcs $@", [expr!(obj)])
+            .response_file_threshold(0);
+        makefile
+            .rule(obj)
+            .static_pattern("%.o")
+            .dep(Expr::from(VariableName::computed("$(ARCH)_SRC")))
+            .cmd("cc -c $< -o $@");
+        let expected = makefile.render().unwrap();
+
+        let json = serde_json::to_string(&makefile).unwrap();
+        let from_json: Makefile = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json.render().unwrap(), expected);
+        assert_eq!(serde_json::to_string(&from_json).unwrap(), json);
+
+        let toml = toml::to_string(&makefile).unwrap();
+        let from_toml: Makefile = toml::from_str(&toml).unwrap();
+        assert_eq!(from_toml.render().unwrap(), expected);
+
+        let newer = json.replacen("\"schema\":1", "\"schema\":2", 1);
+        assert!(serde_json::from_str::<Makefile>(&newer)
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("schema version 2 is not supported"));
+        let unknown = json.replacen("\"wildcard\"", "\"glob\"", 1);
+        assert!(serde_json::from_str::<Makefile>(&unknown)
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("there is no builtin function `glob`"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_default_response_file_threshold() {
+        let mut makefile = Makefile::new();
+        let obj = makefile.assign("OBJ", "a.o b.o");
+        makefile
+            .rule("lib.a")
+            .dep(obj)
+            .cmd_with_response_file("ar rcs $@", [expr!(obj)]);
+        let expected = makefile.render().unwrap();
+
+        let json = serde_json::to_string(&makefile).unwrap();
+        assert!(!json.contains("response_file_threshold"));
+        let from_json: Makefile = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json.render().unwrap(), expected);
+        assert_eq!(serde_json::to_string(&from_json).unwrap(), json);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_manifest() {
+        let text = r#"
+include = ["config.mk"]
+
+[[variable]]
+name = "CC"
+value = "cc"
+op = "?="
+
+[[variable]]
+name = "OBJ"
+value = "main.o util.o"
+
+[[conditional]]
+[[conditional.branch]]
+eq = ["$(CC)", "gcc"]
+variable = [{ name = "CFLAGS", value = "-fanalyzer", op = "+=" }]
+
+[[conditional.branch]]
+defined = "DEBUG"
+variable = [{ name = "CFLAGS", value = "-g", op = "+=" }]
+
+[[conditional.branch]]
+rule = [{ target = "release", phony = true }]
+
+[[rule]]
+target = "app"
+deps = ["$(OBJ)"]
+order_only = ["build"]
+commands = ["$(CC) $(CFLAGS) -o $@ $^"]
+
+[[rule]]
+target = "$(OBJ)"
+static_pattern = "%.o"
+deps = ["%.c"]
+commands = ["$(CC) -c $< -o $@"]
+"#;
+        let makefile = crate::manifest::parse(text, "Makefile.toml").unwrap();
+        assert_snapshot!(makefile.build().unwrap());
+
+        let error = |text| {
+            crate::manifest::parse(text, "Makefile.toml")
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            error("[[variable]]\nname = \"A\"\nop = \"::=\"\n"),
+            "Makefile.toml:3:6: unknown assignment `::=`, expected one of \
+             `=`, `?=`, `:=`, and `+=`"
+        );
+        assert_eq!(
+            error("[[rule]]\ntarget = \"a\"\n\n[[variable]]\nname = \"A B\"\n"),
+            "Makefile.toml:5:8: variable name `A B` contains invalid \
+             character ' '"
+        );
+        assert!(error("[[rule]]\ntarget = \"a\"\ndep = [\"b\"]\n")
+            .starts_with("Makefile.toml:3:1: unknown field `dep`"));
+        assert_eq!(
+            error(
+                "[[conditional]]\n[[conditional.branch]]\n\n\
+                 [[conditional.branch]]\ndefined = \"A\"\n"
+            ),
+            "Makefile.toml:2:1: branch without a condition is not the last"
+        );
+        assert_eq!(
+            error("[[rule]]\ntarget = \"a\"\n\n[[conditional]]\nbranch = []\n"),
+            "Makefile.toml:4:1: conditional has no branches"
+        );
+        assert_eq!(
+            error("[[conditional]]\n[[conditional.branch]]\n"),
+            "Makefile.toml:2:1: branch without a condition is the only one"
+        );
+        assert_eq!(
+            error("[[conditional]]\n[[conditional.branch]]\ndefined = \"\"\n"),
+            "Makefile.toml:3:11: variable name is empty"
+        );
+        assert_eq!(
+            error(
+                "[[variable]]\nname = \"A B\"\n\n[[rule]]\ntarget = \" \"\n\n\
+                 [[conditional]]\n[[conditional.branch]]\ndefined = \"C=D\"\n\
+                 variable = [{ name = \"E#\" }]\n"
+            ),
+            "Makefile.toml:2:8: variable name `A B` contains invalid \
+             character ' '\n\
+             Makefile.toml:5:10: rule target is empty\n\
+             Makefile.toml:9:11: variable name `C=D` contains invalid \
+             character '='\n\
+             Makefile.toml:10:22: variable name `E#` contains invalid \
+             character '#'"
+        );
+
+        let makefile = crate::manifest::parse(
+            "[[rule]]\ntarget = \"a\"\n\n[[variable]]\nname = \"A\"\n\n\
+             [[rule]]\ntarget = \"b\"\n",
+            "Makefile.toml"
+        )
+        .unwrap();
+        assert_eq!(makefile.build().unwrap(), "a: \nA = \nb: \n");
+    }
+
+    #[test]
+    fn test_reader() {
+        let text = "\
+# Build the app.
+CC ?= cc
+SRC := main.c \\
+       util.c
+# objects
+OBJ = $(SRC:.c=.o)
+HASH != git rev-parse HEAD
+
+.PHONY: all clean
+all: app
+
+ifneq ($(CC),gcc)
+CFLAGS += -O2
+else ifdef DEBUG
+CFLAGS += -g
+endif
+
+app: $(OBJ) | build
+\t$(CC) -o $@ $^
+# linked
+\t@echo done \\
+\t  $@
+
+$(OBJ): %.o: %.c ; $(CC) -c $< -o $@
+
+clean:
+\trm -f app $(OBJ)
+";
+        let output = crate::reader::parse(text, "Makefile")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_snapshot!(output);
+        assert_eq!(
+            crate::reader::parse(&output, "Makefile")
+                .unwrap()
+                .build()
+                .unwrap(),
+            output
+        );
+
+        let error = |text| {
+            crate::reader::parse(text, "Makefile")
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(error("a: b\n\ncc\n"), "Makefile:3:1: missing separator");
+        assert_eq!(error("ifdef A\nB = 1\n"), "Makefile:2:1: missing `endif`");
+        assert_eq!(
+            error("a: CFLAGS = -g\n"),
+            "Makefile:1:1: target-specific variables are not supported"
+        );
+        assert_eq!(
+            error("define A\nendef\n"),
+            "Makefile:1:1: `define` is not supported"
+        );
+        assert_eq!(
+            error("A = a # b\n"),
+            "Makefile:1:1: comments after other text are not supported"
+        );
+        assert_eq!(
+            error("ifdef $(A)\nendif\n"),
+            "Makefile:1:1: `ifdef` of a computed variable name is not supported"
+        );
+    }
+
+    #[test]
+    fn test_cargo() {
+        let root = std::env::temp_dir()
+            .join(format!("makemake_test_cargo_{}", std::process::id()));
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n\
+             [features]\ndefault = [\"fast\"]\nfast = []\nextra = []\n\n\
+             [[example]]\nname = \"demo\"\nrequired-features = [\"extra\"]\n\n\
+             [workspace]\nmembers = [\"crates/*\"]\nexclude = \
+             [\"crates/old\"]\n"
+        );
+        write("src/main.rs", "fn main() {}\n");
+        write("examples/demo.rs", "fn main() {}\n");
+        write("tests/cli.rs", "");
+        for member in ["core", "old"] {
+            write(
+                &format!("crates/{}/Cargo.toml", member),
+                &format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n",
+                    member
+                )
+            );
+            write(&format!("crates/{}/src/lib.rs", member), "");
+        }
+        write("crates/core/src/bin/app.rs", "fn main() {}\n");
+        write("crates/core/examples/demo.rs", "fn main() {}\n");
+
+        let workspace =
+            crate::cargo::Workspace::load(root.join("Cargo.toml")).unwrap();
+        assert_eq!(
+            workspace
+                .crates()
+                .iter()
+                .map(|krate| krate.name.as_str())
+                .collect::<Vec<_>>(),
+            ["app", "core"]
+        );
+        let mut makefile = Makefile::new();
+        workspace.emit(&mut makefile);
+        let output = makefile.build().unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_snapshot!(output.replace(&root.display().to_string(), "rust"));
+    }
+
+    #[test]
+    fn test_c_project() {
+        let mut makefile = Makefile::new();
+        CProject::executable("app")
+            .source("src/*.c")
+            .source("main.c")
+            .include_dir("include")
+            .define("VERSION=2")
+            .lib("m")
+            .emit(&mut makefile);
+        let output = makefile.build().unwrap();
+        assert_eq!(
+            crate::reader::parse(&output, "Makefile")
+                .unwrap()
+                .build()
+                .unwrap(),
+            output
+        );
+        assert_snapshot!(output);
+
+        let mut makefile = Makefile::new();
+        makefile.namespace("util", |namespace| {
+            CProject::shared_library("util")
+                .sources(["util.cpp", "log.c"])
+                .build_dir("out")
+                .soname_version("1")
+                .emit(namespace);
+        });
+        assert_snapshot!(makefile.build().unwrap());
+
+        let mut makefile = Makefile::new();
+        CProject::static_library("util")
+            .source("util.c")
+            .emit(&mut makefile);
+        assert!(makefile
+            .build()
+            .unwrap()
+            .contains("$(TARGET): $(OBJ)\n\t$(AR) rcs $@ $^\n"));
+    }
+
+    #[test]
+    fn test_compile_commands() {
+        let mut makefile = Makefile::new();
+        CProject::executable("app")
+            .source("src/*.c")
+            .source("main.c")
+            .include_dir("include")
+            .define("VERSION=2")
+            .emit(&mut makefile);
+        let options = CompileCommandsOptions::new("/home/user/app")
+            .with_wildcard(|_| vec!["src/a.c".into(), "src/b.c".into()]);
+        let commands = makefile.compile_commands(options).unwrap();
+        assert_eq!(
+            commands
+                .iter()
+                .map(|command| command.file.as_str())
+                .collect::<Vec<_>>(),
+            ["src/a.c", "src/b.c", "main.c"]
+        );
+        assert_snapshot!(compile_commands::to_json(&commands));
+
+        let mut makefile = Makefile::new();
+        let cc = makefile.assign("CC", "clang");
+        makefile
+            .rule("tool.o")
+            .dep("tool.c")
+            .dep("tool.h")
+            .compiles()
+            .cmd(Expr::from(cc).then("-DNAME='\"my tool\"' -c $< -o $@"));
+        makefile.rule("tool").dep("tool.o").cmd("$(CC) -o $@ $^");
+        let commands = makefile
+            .compile_commands(CompileCommandsOptions::new("/src"))
+            .unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].output, "tool.o");
+        assert_eq!(
+            commands[0].arguments,
+            [
+                "clang",
+                "-DNAME=\"my tool\"",
+                "-c",
+                "tool.c",
+                "-o",
+                "tool.o"
+            ]
+        );
+
+        let mut makefile = Makefile::new();
+        CProject::executable("app")
+            .source("*.c")
+            .emit(&mut makefile);
+        assert!(matches!(
+            makefile
+                .compile_commands(CompileCommandsOptions::new("/src"))
+                .err()
+                .unwrap(),
+            Error::Eval(EvalError::MissingHook { .. })
+        ));
+    }
+
+    #[test]
+    fn test_compile_commands_to_makefile() {
+        let command =
+            |file: &str, output: &str, arguments: &str| CompileCommand {
+                directory: "/home/user/proj".into(),
+                file: file.into(),
+                output: output.into(),
+                arguments: arguments.split(' ').map(str::to_string).collect()
+            };
+        let commands = [
+            command(
+                "src/main.c",
+                "build/src/main.o",
+                "gcc -Iinclude -O2 -Wall -c -o build/src/main.o src/main.c"
+            ),
+            command(
+                "/home/user/proj/src/util.c",
+                "build/src/util.o",
+                "gcc -Iinclude -O2 -Wall -c -o build/src/util.o \
+                 /home/user/proj/src/util.c"
+            ),
+            command(
+                "src/fast.c",
+                "build/src/fast.o",
+                "gcc -Iinclude -O3 -Wall -MMD -MF build/src/fast.d -c \
+                 src/fast.c -o build/src/fast.o"
+            ),
+            command(
+                "lib/vec.cpp",
+                "vec.o",
+                "clang++ -Iinclude -std=c++17 -c lib/vec.cpp -o vec.o"
+            )
+        ];
+        let makefile = compile_commands::to_makefile(&commands);
+        let output = makefile.render().unwrap();
+        assert_eq!(
+            crate::reader::parse(&output, "Makefile")
+                .unwrap()
+                .build()
+                .unwrap(),
+            output
+        );
+        assert_snapshot!(output);
+
+        let regenerated = makefile
+            .compile_commands(CompileCommandsOptions::new("/home/user/proj"))
+            .unwrap();
+        assert_eq!(
+            regenerated
+                .iter()
+                .map(|command| (command.file.as_str(), command.output.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("src/main.c", "build/src/main.o"),
+                ("src/util.c", "build/src/util.o"),
+                ("src/fast.c", "build/src/fast.o"),
+                ("lib/vec.cpp", "vec.o")
+            ]
+        );
+        assert_eq!(
+            regenerated[2].arguments.join(" "),
+            "gcc -Iinclude -O3 -Wall -MMD -c -o build/src/fast.o src/fast.c"
+        );
+
+        let makefile = compile_commands::to_makefile(&commands[..1]);
+        assert!(makefile.render().unwrap().contains(
+            "build/%.o: %.c\n\t@mkdir -p $(@D)\n\t$(CC) $(CPPFLAGS) $(CFLAGS) \
+             -c -o $@ $<\n"
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_compile_commands_parse() {
+        let text = r#"[
+  {
+    "directory": "/src",
+    "file": "a.c",
+    "command": "cc -DNAME='\"a b\"' -c a.c"
+  },
+  {
+    "directory": "/src",
+    "file": "b.c",
+    "arguments": ["cc", "-c", "b.c", "-o", "out/b.o"]
+  }
+]"#;
+        let commands =
+            compile_commands::parse(text, "compile_commands.json").unwrap();
+        assert_eq!(commands[0].output, "a.o");
+        assert_eq!(
+            commands[0].arguments,
+            ["cc", "-DNAME=\"a b\"", "-c", "a.c"]
+        );
+        assert_eq!(commands[1].output, "out/b.o");
+        assert!(compile_commands::to_makefile(&commands)
+            .render()
+            .unwrap()
+            .contains("FLAGS_0 = '-DNAME=\"a b\"'\n"));
+
+        let error = compile_commands::parse("[{\"file\": \"a.c\"}]", "db.json")
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::Parse { line: 1, .. }));
     }
 }
@@ -0,0 +1,504 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display}
+};
+
+use crate::{
+    assignment::AssignmentKind,
+    conditional::Condition,
+    emittable::Emittable,
+    entry::Entry,
+    expr::Expr,
+    graph::{self, StaticRule},
+    makefile::Makefile,
+    rule::Command,
+    symbol_context::{Resolvable, SymbolContext},
+    syntax::{self, Fragment, StaticValues},
+    var::VariableName
+};
+
+/// Variables that make defines itself or that are commonly read from the
+/// environment, which are allowed to be read without being assigned, in
+/// addition to [`IMPLICIT_RULE_VARIABLES`].
+pub const DEFAULT_ALLOWED_VARIABLES: &[&str] = &[
+    // Special variables
+    "MAKE",
+    "MAKEFLAGS",
+    "MAKECMDGOALS",
+    "MAKEFILE_LIST",
+    "MAKELEVEL",
+    "MAKE_VERSION",
+    "MAKE_HOST",
+    "CURDIR",
+    "SHELL",
+    ".DEFAULT_GOAL",
+    ".RECIPEPREFIX",
+    ".VARIABLES",
+    ".FEATURES",
+    ".INCLUDE_DIRS",
+    // Environment variables
+    "HOME",
+    "PATH",
+    "PWD",
+    "USER",
+    "TMPDIR"
+];
+
+/// The variables read by make's built-in implicit rules, which are allowed to
+/// be read without being assigned and assigned without being read. See section
+/// [10.3](https://www.gnu.org/software/make/manual/make.html#Implicit-Variables).
+pub const IMPLICIT_RULE_VARIABLES: &[&str] = &[
+    "AR",
+    "AS",
+    "CC",
+    "CXX",
+    "CPP",
+    "FC",
+    "LEX",
+    "YACC",
+    "RM",
+    "ARFLAGS",
+    "ASFLAGS",
+    "CFLAGS",
+    "CXXFLAGS",
+    "CPPFLAGS",
+    "FFLAGS",
+    "LDFLAGS",
+    "LDLIBS",
+    "LOADLIBES",
+    "LFLAGS",
+    "YFLAGS",
+    "TARGET_ARCH",
+    "OUTPUT_OPTION"
+];
+
+/// The automatic variables set by make for each rule. See section
+/// [10.5.3](https://www.gnu.org/software/make/manual/make.html#Automatic-Variables).
+const AUTOMATIC_VARIABLES: &[&str] = &["@", "%", "<", "?", "^", "+", "|", "*"];
+
+/// Options for [`Makefile::lint_with`].
+pub struct LintOptions {
+    allowed: HashSet<String>
+}
+
+impl LintOptions {
+    /// Constructs options allowing [`DEFAULT_ALLOWED_VARIABLES`] and
+    /// [`IMPLICIT_RULE_VARIABLES`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows `name` to be read without being assigned, e.g., because it is
+    /// expected from the environment.
+    pub fn allow<S: AsRef<str>>(mut self, name: S) -> Self {
+        self.allowed.insert(name.as_ref().to_string());
+        self
+    }
+
+    /// Allows nothing to be read without being assigned, not even
+    /// [`DEFAULT_ALLOWED_VARIABLES`] or [`IMPLICIT_RULE_VARIABLES`].
+    pub fn allow_none(mut self) -> Self {
+        self.allowed.clear();
+        self
+    }
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            allowed: DEFAULT_ALLOWED_VARIABLES
+                .iter()
+                .chain(IMPLICIT_RULE_VARIABLES)
+                .map(|name| name.to_string())
+                .collect()
+        }
+    }
+}
+
+/// A likely bug found in a Makefile by [`Makefile::lint`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Diagnostic {
+    /// The variable `name` is read but never assigned.
+    UndefinedVariable { name: String },
+    /// The variable `name` is assigned but never read.
+    UnusedVariable { name: String },
+    /// A later rule for `target` overrides the recipe of an earlier one.
+    OverriddenRecipe { target: String },
+    /// The phony target `phony` is a prerequisite of the file `target`, which
+    /// will therefore always be rebuilt.
+    PhonyPrerequisite { phony: String, target: String },
+    /// The recursively expanded variable `name` references itself through
+    /// `cycle`, which begins and ends with `name`. Each cycle is reported
+    /// once, for the member assigned first.
+    SelfReferentialVariable { name: String, cycle: Vec<String> }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndefinedVariable { name } => {
+                write!(f, "variable `{}` is read but never assigned", name)
+            }
+            Self::UnusedVariable { name } => {
+                write!(f, "variable `{}` is assigned but never read", name)
+            }
+            Self::OverriddenRecipe { target } => {
+                write!(f, "recipe for target `{}` is overridden", target)
+            }
+            Self::PhonyPrerequisite { phony, target } => write!(
+                f,
+                "phony target `{}` is a prerequisite of file `{}`, which will \
+                 always be rebuilt",
+                phony, target
+            ),
+            Self::SelfReferentialVariable { name, cycle } => write!(
+                f,
+                "recursive variable `{}` references itself through `{}`",
+                name,
+                cycle.join(" -> ")
+            )
+        }
+    }
+}
+
+/// The facts about a Makefile that the lints are computed from.
+#[derive(Default)]
+struct Facts {
+    /// Variables read, in the order they are first read.
+    reads: Vec<String>,
+    /// Variables bound by `foreach` or `let`.
+    bound: HashSet<String>,
+    /// Variables tested by `ifdef` or `ifndef`, which need not be assigned.
+    tested: HashSet<String>,
+    /// Variables assigned, in the order they are first assigned.
+    assigned: Vec<String>,
+    /// For each recursively expanded variable, the variables its value reads.
+    recursive_references: HashMap<String, Vec<String>>,
+    /// Rules outside conditionals.
+    rules: Vec<StaticRule>
+}
+
+impl Facts {
+    fn read(&mut self, name: &str) {
+        if !self.reads.iter().any(|read| read == name) {
+            self.reads.push(name.to_string());
+        }
+    }
+
+    /// Records the variables read when expanding `expr`, returning them.
+    fn read_expr(
+        &mut self, expr: &Expr, ctx: &mut SymbolContext
+    ) -> Vec<String> {
+        let mut names = vec![];
+        self.reads_of(expr, ctx, &mut names);
+        for name in &names {
+            self.read(name);
+        }
+        names
+    }
+
+    /// Adds the variables read when expanding `expr` to `names`.
+    fn reads_of(
+        &mut self, expr: &Expr, ctx: &mut SymbolContext,
+        names: &mut Vec<String>
+    ) {
+        match expr {
+            Expr::Empty => {}
+            Expr::Raw(text) => self.reads_of_text(text, names),
+            Expr::Var(var) => names.push(var.name(ctx).to_string()),
+            Expr::Ref(name) => self.reads_of_name(name, ctx, names),
+            Expr::Concat(list) => {
+                for expr in list {
+                    self.reads_of(expr, ctx, names);
+                }
+            }
+            Expr::SubstRef(subst) => {
+                self.reads_of_name(&subst.var, ctx, names);
+                self.reads_of(&subst.old, ctx, names);
+                self.reads_of(&subst.new, ctx, names);
+            }
+            Expr::Function(function) => {
+                let first = function.args().first().and_then(|arg| match arg {
+                    Expr::Raw(text) if !text.contains('$') => Some(text.trim()),
+                    _ => None
+                });
+                self.read_call(function.name(), first, names);
+                for arg in function.args() {
+                    self.reads_of(arg, ctx, names);
+                }
+            }
+        }
+    }
+
+    fn reads_of_name(
+        &mut self, name: &VariableName, ctx: &mut SymbolContext,
+        names: &mut Vec<String>
+    ) {
+        match name {
+            VariableName::Static(_) => names.push(name.emit_name(ctx)),
+            VariableName::Computed(name) => self.reads_of(name, ctx, names)
+        }
+    }
+
+    /// Records the variables that a call to `function` binds or names, in
+    /// `names`, by its `first` argument if that is literal text.
+    fn read_call(
+        &mut self, function: &str, first: Option<&str>, names: &mut Vec<String>
+    ) {
+        match (function, first) {
+            ("foreach" | "let", Some(vars)) => {
+                self.bound
+                    .extend(vars.split_whitespace().map(str::to_string));
+            }
+            ("call" | "value" | "origin" | "flavor", Some(name)) => {
+                names.push(name.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds the variables read when expanding `text`, which was given as raw
+    /// make text rather than built from the model, to `names`.
+    fn reads_of_text(&mut self, text: &str, names: &mut Vec<String>) {
+        let Ok(fragments) = syntax::parse(text) else {
+            return;
+        };
+        syntax::walk_all(&fragments, &mut |fragment| match fragment {
+            Fragment::Reference { .. } => {
+                if let Some(name) = fragment.static_name() {
+                    names.push(name.to_string());
+                }
+            }
+            Fragment::Call { spec, args } => {
+                let first = args.first().and_then(|arg| match arg.as_slice() {
+                    [Fragment::Text(text)] => Some(text.trim()),
+                    _ => None
+                });
+                self.read_call(spec.name, first, names);
+            }
+            Fragment::Text(_) => {}
+        });
+    }
+}
+
+fn is_automatic(name: &str) -> bool {
+    let name = name.strip_suffix(['D', 'F']).unwrap_or(name);
+    AUTOMATIC_VARIABLES.contains(&name)
+}
+
+fn collect(
+    entry: &Entry, conditional: bool, ctx: &mut SymbolContext,
+    facts: &mut Facts, values: &mut StaticValues
+) {
+    match entry {
+        Entry::Assignment(assignment) => {
+            let name = assignment.var.name(ctx).to_string();
+            let reads = facts.read_expr(&assignment.value, ctx);
+            let text = assignment.value.emit(ctx);
+            let is_new = !facts.assigned.contains(&name);
+            if is_new {
+                facts.assigned.push(name.clone());
+            }
+            match assignment.kind {
+                AssignmentKind::Overwrite => {
+                    facts.recursive_references.insert(name.clone(), reads);
+                }
+                AssignmentKind::Underwrite => {
+                    facts
+                        .recursive_references
+                        .entry(name.clone())
+                        .or_insert(reads);
+                }
+                AssignmentKind::Simple => {
+                    facts.recursive_references.remove(&name);
+                }
+                AssignmentKind::Immediate => {
+                    facts.recursive_references.insert(name.clone(), vec![]);
+                }
+                // Appending to an undefined variable defines it like `=`.
+                AssignmentKind::Append if is_new => {
+                    facts.recursive_references.insert(name.clone(), reads);
+                }
+                AssignmentKind::Append => {
+                    if let Some(references) =
+                        facts.recursive_references.get_mut(&name)
+                    {
+                        references.extend(reads);
+                    }
+                }
+            }
+            values.assign(&name, assignment.kind, text, conditional);
+        }
+        Entry::Include(include) => {
+            facts.read_expr(include.path(), ctx);
+        }
+        Entry::Rule(rule) => {
+            let rule = rule.rule.borrow();
+            facts.read_expr(&rule.target, ctx);
+            if let Some(pattern) = &rule.static_pattern {
+                facts.read_expr(pattern, ctx);
+            }
+            for dependency in rule
+                .dependencies
+                .iter()
+                .chain(&rule.order_only_dependencies)
+            {
+                facts.read_expr(dependency, ctx);
+            }
+            for command in &rule.commands {
+                match command {
+                    Command::Plain(line) => {
+                        facts.read_expr(line, ctx);
+                    }
+                    Command::ResponseFile { cmd, args } => {
+                        facts.read_expr(cmd, ctx);
+                        for arg in args {
+                            facts.read_expr(arg, ctx);
+                        }
+                    }
+                }
+            }
+            if conditional {
+                return;
+            }
+            if let Some(rule) =
+                StaticRule::expand(&rule, ctx, values, &mut vec![])
+            {
+                facts.rules.push(rule);
+            }
+        }
+        Entry::Conditional(conditional) => {
+            for branch in &conditional.conditional.borrow().branches {
+                match &branch.condition {
+                    Some(Condition::Eq(lhs, rhs)) => {
+                        facts.read_expr(lhs, ctx);
+                        facts.read_expr(rhs, ctx);
+                    }
+                    Some(Condition::Def(var) | Condition::Undef(var)) => {
+                        facts.tested.insert(var.name(ctx).to_string());
+                    }
+                    None => {}
+                }
+            }
+        }
+        Entry::Comment(_) | Entry::Newline(_) | Entry::Namespace(_) => {}
+    }
+}
+
+/// The cycle through which the recursively expanded variable `name` references
+/// itself, if any.
+fn find_cycle(
+    name: &str, references: &HashMap<String, Vec<String>>
+) -> Option<Vec<String>> {
+    fn search(
+        current: &str, name: &str, references: &HashMap<String, Vec<String>>,
+        path: &mut Vec<String>, visited: &mut HashSet<String>
+    ) -> bool {
+        for next in references.get(current).into_iter().flatten() {
+            if next == name {
+                path.push(next.clone());
+                return true;
+            }
+            if visited.insert(next.clone()) {
+                path.push(next.clone());
+                if search(next, name, references, path, visited) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+
+    let mut path = vec![name.to_string()];
+    search(name, name, references, &mut path, &mut HashSet::new())
+        .then_some(path)
+}
+
+pub(crate) fn lint(
+    makefile: &Makefile, options: &LintOptions
+) -> Vec<Diagnostic> {
+    let mut facts = Facts::default();
+    let mut values = StaticValues::default();
+    {
+        let mut ctx = makefile.symbols_mut();
+        for entry in &makefile.contents {
+            entry.walk(false, &mut |entry, conditional| {
+                collect(entry, conditional, &mut ctx, &mut facts, &mut values);
+            });
+        }
+    }
+
+    let mut diagnostics = vec![];
+    for name in &facts.reads {
+        if !facts.assigned.contains(name)
+            && !facts.bound.contains(name)
+            && !options.allowed.contains(name)
+            && !is_automatic(name)
+        {
+            diagnostics
+                .push(Diagnostic::UndefinedVariable { name: name.clone() });
+        }
+    }
+    for name in &facts.assigned {
+        if !facts.reads.contains(name)
+            && !facts.tested.contains(name)
+            && !IMPLICIT_RULE_VARIABLES.contains(&name.as_str())
+        {
+            diagnostics.push(Diagnostic::UnusedVariable { name: name.clone() });
+        }
+    }
+
+    let mut with_recipe = HashSet::new();
+    let mut phony = HashSet::new();
+    for rule in &facts.rules {
+        if rule.targets.iter().any(|target| target == ".PHONY") {
+            phony.extend(rule.dependencies.iter().cloned());
+        }
+        if rule.is_phony {
+            phony.extend(rule.targets.iter().cloned());
+        }
+        if !rule.has_recipe {
+            continue;
+        }
+        for target in &rule.targets {
+            if !graph::is_special_target(target)
+                && !with_recipe.insert(target.clone())
+            {
+                diagnostics.push(Diagnostic::OverriddenRecipe {
+                    target: target.clone()
+                });
+            }
+        }
+    }
+    for rule in &facts.rules {
+        for target in &rule.targets {
+            if graph::is_special_target(target) || phony.contains(target) {
+                continue;
+            }
+            for dependency in rule.prerequisites_of(target).0 {
+                if phony.contains(&dependency) {
+                    diagnostics.push(Diagnostic::PhonyPrerequisite {
+                        phony: dependency.clone(),
+                        target: target.clone()
+                    });
+                }
+            }
+        }
+    }
+
+    let mut in_reported_cycle = HashSet::new();
+    for name in &facts.assigned {
+        if in_reported_cycle.contains(name) {
+            continue;
+        }
+        if let Some(cycle) = find_cycle(name, &facts.recursive_references) {
+            in_reported_cycle.extend(cycle.iter().cloned());
+            diagnostics.push(Diagnostic::SelfReferentialVariable {
+                name: name.clone(),
+                cycle
+            });
+        }
+    }
+    diagnostics
+}
@@ -0,0 +1,343 @@
+use std::{
+    fs,
+    io::{self, Read},
+    process::ExitCode
+};
+
+use makemake::{
+    compile_commands, export::ExportOptions, makefile::Makefile, manifest,
+    plan::RealFileSystem, reader
+};
+
+const USAGE: &str = "usage: makemake [COMMAND] [ARGS]
+
+Commands:
+  build [INPUT [OUTPUT]]
+      Writes the Makefile described by INPUT (default: Makefile.toml) to
+      OUTPUT (default: Makefile). This is the command if none is given.
+  fmt [--check] [FILE...]
+      Rewrites each Makefile FILE (default: Makefile) canonically, or with
+      --check, lists each one that would be rewritten.
+  lint [FILE]
+      Reports likely bugs in FILE (default: Makefile).
+  graph [--mermaid] [--collapse] [--goal GOAL] [FILE]
+      Prints the graph of the targets in FILE (default: Makefile) in DOT, or
+      as a Mermaid flowchart with --mermaid, collapsing targets into their
+      patterns with --collapse and drawing only what GOAL depends on.
+  convert [--from FORMAT] [--to FORMAT] INPUT [OUTPUT]
+      Converts INPUT to OUTPUT (default: standard output).
+  explain GOAL [FILE]
+      Explains why make would remake GOAL with FILE (default: Makefile).
+
+FORMAT is `make`, `toml` (input only), `json`, `compdb` (input only), `ninja`
+(output only), or `bsd` (output only), and is otherwise guessed from the name
+of the file. A `compdb` is a compilation database, `compile_commands.json`,
+from which a Makefile compiling the same files is started. A file named `-`
+is standard input or output.
+
+Exits with 1 if `lint` finds a problem or `fmt --check` finds a file to
+rewrite, and with 2 on any error.";
+
+/// The exit status on an error.
+const ERROR: u8 = 2;
+
+/// The exit status when a check fails.
+const CHECK_FAILED: u8 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Make,
+    Toml,
+    Json,
+    CompileCommands,
+    Ninja,
+    Bsd
+}
+
+impl Format {
+    fn named(name: &str) -> Result<Self, String> {
+        match name {
+            "make" => Ok(Self::Make),
+            "toml" => Ok(Self::Toml),
+            "json" => Ok(Self::Json),
+            "compdb" => Ok(Self::CompileCommands),
+            "ninja" => Ok(Self::Ninja),
+            "bsd" => Ok(Self::Bsd),
+            _ => Err(format!("unknown format `{}`", name))
+        }
+    }
+
+    fn of(path: &str) -> Self {
+        if path.ends_with(".toml") {
+            Self::Toml
+        } else if path.ends_with("compile_commands.json") {
+            Self::CompileCommands
+        } else if path.ends_with(".json") {
+            Self::Json
+        } else if path.ends_with(".ninja") {
+            Self::Ninja
+        } else {
+            Self::Make
+        }
+    }
+}
+
+/// The arguments to a command, split into flags and the rest.
+struct Args {
+    flags: Vec<String>,
+    options: Vec<(String, String)>,
+    positional: Vec<String>
+}
+
+impl Args {
+    /// Splits `args`, allowing `flags` and `options`, which take a value.
+    fn parse(
+        args: &[String], flags: &[&str], options: &[&str]
+    ) -> Result<Self, String> {
+        let mut result = Args {
+            flags: vec![],
+            options: vec![],
+            positional: vec![]
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if flags.contains(&arg.as_str()) {
+                result.flags.push(arg.clone());
+            } else if options.contains(&arg.as_str()) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value", arg))?;
+                result.options.push((arg.clone(), value.clone()));
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(format!("unknown option `{}`\n\n{}", arg, USAGE));
+            } else {
+                result.positional.push(arg.clone());
+            }
+        }
+        Ok(result)
+    }
+
+    fn flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|arg| arg == flag)
+    }
+
+    fn option(&self, option: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(name, _)| name == option)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The positional arguments, of which there must be at most `max`.
+    fn positional(&self, max: usize) -> Result<&[String], String> {
+        if self.positional.len() > max {
+            Err(USAGE.to_string())
+        } else {
+            Ok(&self.positional)
+        }
+    }
+}
+
+fn read_text(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|error| format!("<stdin>: {}", error))?;
+        Ok(text)
+    } else {
+        fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))
+    }
+}
+
+fn write_text(path: &str, text: &str) -> Result<(), String> {
+    if path == "-" {
+        print!("{}", text);
+        Ok(())
+    } else {
+        fs::write(path, text).map_err(|error| format!("{}: {}", path, error))
+    }
+}
+
+fn read(path: &str, format: Format) -> Result<Makefile, String> {
+    let text = read_text(path)?;
+    let file = if path == "-" { "<stdin>" } else { path };
+    match format {
+        Format::Make => {
+            reader::parse(&text, file).map_err(|error| error.to_string())
+        }
+        Format::Toml => {
+            manifest::parse(&text, file).map_err(|error| error.to_string())
+        }
+        Format::Json => serde_json::from_str(&text)
+            .map_err(|error| format!("{}: {}", file, error)),
+        Format::CompileCommands => compile_commands::parse(&text, file)
+            .map(|commands| compile_commands::to_makefile(&commands))
+            .map_err(|error| error.to_string()),
+        Format::Ninja | Format::Bsd => {
+            Err(format!("{}: cannot read this format", file))
+        }
+    }
+}
+
+fn render(makefile: &Makefile, format: Format) -> Result<String, String> {
+    match format {
+        Format::Make => makefile.render(),
+        Format::Ninja => makefile.render_ninja(),
+        Format::Bsd => makefile.render_bsd(),
+        Format::Json => {
+            return serde_json::to_string_pretty(makefile)
+                .map(|json| json + "\n")
+                .map_err(|error| error.to_string())
+        }
+        Format::Toml | Format::CompileCommands => {
+            return Err("cannot write this format".into())
+        }
+    }
+    .map_err(|error| error.to_string())
+}
+
+fn build(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &[], &[])?;
+    let (input, output) = match args.positional(2)? {
+        [] => ("Makefile.toml", "Makefile"),
+        [input] => (input.as_str(), "Makefile"),
+        [input, output] => (input.as_str(), output.as_str()),
+        _ => unreachable!()
+    };
+    let makefile = read(input, Format::of(input))?;
+    write_text(output, &render(&makefile, Format::Make)?)?;
+    Ok(0)
+}
+
+fn fmt(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &["--check"], &[])?;
+    let check = args.flag("--check");
+    let default = ["Makefile".to_string()];
+    let files = match args.positional(usize::MAX)? {
+        [] => &default[..],
+        files => files
+    };
+    let mut status = 0;
+    for file in files {
+        let text = read_text(file)?;
+        let formatted = reader::parse(&text, file)
+            .and_then(|makefile| makefile.build())
+            .map_err(|error| error.to_string())?;
+        if check {
+            if formatted != text {
+                println!("{}", file);
+                status = CHECK_FAILED;
+            }
+        } else if file == "-" || formatted != text {
+            write_text(file, &formatted)?;
+        }
+    }
+    Ok(status)
+}
+
+fn lint(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &[], &[])?;
+    let file = args
+        .positional(1)?
+        .first()
+        .map_or("Makefile", String::as_str);
+    let diagnostics = read(file, Format::of(file))?.lint();
+    for diagnostic in &diagnostics {
+        println!("{}: {}", file, diagnostic);
+    }
+    Ok(if diagnostics.is_empty() {
+        0
+    } else {
+        CHECK_FAILED
+    })
+}
+
+fn graph(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &["--mermaid", "--collapse"], &["--goal"])?;
+    let file = args
+        .positional(1)?
+        .first()
+        .map_or("Makefile", String::as_str);
+    let mut options = ExportOptions::new();
+    if args.flag("--collapse") {
+        options = options.collapse_patterns();
+    }
+    if let Some(goal) = args.option("--goal") {
+        options = options.goal(goal);
+    }
+    let graph = read(file, Format::of(file))?.graph();
+    let text = if args.flag("--mermaid") {
+        graph.to_mermaid(&options)
+    } else {
+        graph.to_dot(&options)
+    }
+    .map_err(|error| error.to_string())?;
+    print!("{}", text);
+    Ok(0)
+}
+
+fn convert(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &[], &["--from", "--to"])?;
+    let (input, output) = match args.positional(2)? {
+        [input] => (input.as_str(), "-"),
+        [input, output] => (input.as_str(), output.as_str()),
+        _ => return Err(USAGE.to_string())
+    };
+    let from = args
+        .option("--from")
+        .map_or(Ok(Format::of(input)), Format::named)?;
+    let to = args
+        .option("--to")
+        .map_or(Ok(Format::of(output)), Format::named)?;
+    let makefile = read(input, from)?;
+    write_text(output, &render(&makefile, to)?)?;
+    Ok(0)
+}
+
+fn explain(args: &[String]) -> Result<u8, String> {
+    let args = Args::parse(args, &[], &[])?;
+    let (goal, file) = match args.positional(2)? {
+        [goal] => (goal.as_str(), "Makefile"),
+        [goal, file] => (goal.as_str(), file.as_str()),
+        _ => return Err(USAGE.to_string())
+    };
+    let explanations = read(file, Format::of(file))?
+        .explain(goal, &RealFileSystem)
+        .map_err(|error| error.to_string())?;
+    if explanations.is_empty() {
+        println!("`{}` is up to date", goal);
+    }
+    for explanation in explanations {
+        println!("{}", explanation);
+    }
+    Ok(0)
+}
+
+fn run(args: &[String]) -> Result<u8, String> {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(0);
+    }
+    match args.first().map(String::as_str) {
+        Some("build") => build(&args[1..]),
+        Some("fmt") => fmt(&args[1..]),
+        Some("lint") => lint(&args[1..]),
+        Some("graph") => graph(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("explain") => explain(&args[1..]),
+        _ => build(args)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(status) => ExitCode::from(status),
+        Err(message) => {
+            eprintln!("makemake: {}", message);
+            ExitCode::from(ERROR)
+        }
+    }
+}
@@ -1,15 +1,35 @@
-use std::fmt::Write;
+use std::{
+    cell::{Ref, RefMut},
+    io::Write
+};
 
 use crate::{
-    emittable::EmittableRef, emitter::EmittableContainer, rrc::RRC,
-    symbol_context::SymbolContext
+    bsd,
+    compile_commands::{self, CompileCommand, CompileCommandsOptions},
+    dialect::{self, Dialect},
+    emittable::Emittable,
+    emitter::EmittableContainer,
+    entry::Entry,
+    error::{Error, Result},
+    eval::Env,
+    explain::{self, Explanation},
+    graph::RuleGraph,
+    lint::{self, Diagnostic, LintOptions},
+    ninja,
+    plan::FileSystem,
+    rrc::RRC,
+    rule::ResponseFiles,
+    simulate,
+    symbol_context::SymbolContext,
+    version::{self, Requirement, Version}
 };
 
 /// A Makefile.
 #[derive(Default)]
 pub struct Makefile {
-    contents: Vec<EmittableRef>,
-    ctx: RRC<SymbolContext>
+    pub(crate) contents: Vec<Entry>,
+    ctx: RRC<SymbolContext>,
+    pub(crate) version_guard: bool
 }
 
 impl Makefile {
@@ -18,23 +38,237 @@ impl Makefile {
         Self::default()
     }
 
-    /// Converts this Makefile into its textual representation.
-    pub fn build(mut self) -> String {
-        let mut result = String::new();
-        for content in self.contents.drain(..) {
-            writeln!(
-                &mut result,
-                "{}",
-                content.emit(&mut self.ctx.borrow_mut())
-            )
-            .unwrap();
+    /// Constructs an empty Makefile for make in `dialect`.
+    pub fn with_dialect(dialect: Dialect) -> Self {
+        let mut makefile = Self::new();
+        makefile.set_dialect(dialect);
+        makefile
+    }
+
+    /// The variant of make that this Makefile is written for, which is
+    /// [`Dialect::Gnu`] by default.
+    pub fn dialect(&self) -> Dialect {
+        self.ctx.borrow().dialect()
+    }
+
+    /// Writes this Makefile for make in `dialect`. Building it then reports
+    /// every construct that make in `dialect` does not support, lowering those
+    /// with an equivalent, such as `:=` to `::=` for POSIX make.
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.ctx.borrow_mut().set_dialect(dialect);
+    }
+
+    /// Every feature used by this Makefile that not all versions of GNU make
+    /// provide, in the order they are first used. Outside of writing this
+    /// Makefile, a command whose arguments reference variables is taken to use
+    /// its response file.
+    pub fn requirements(&self) -> Vec<Requirement> {
+        version::requirements(self)
+    }
+
+    /// The oldest version of GNU make that provides every feature used by this
+    /// Makefile.
+    pub fn required_version(&self) -> Version {
+        self.newest_requirement()
+            .map(|requirement| requirement.since)
+            .unwrap_or_default()
+    }
+
+    /// Begins this Makefile, when written for GNU make, with a check that
+    /// stops make with an error if it is older than
+    /// [`Makefile::required_version`].
+    ///
+    /// The check compares versions as text with `$(sort)`, so it would
+    /// misorder a version with a two-digit minor version, e.g., take 4.10 to
+    /// be older than 4.2. No released version of GNU make has one yet.
+    pub fn set_version_guard(&mut self, guard: bool) {
+        self.version_guard = guard;
+    }
+
+    /// The symbols defined in this Makefile, e.g., to look up which name a
+    /// namespaced or fresh variable was generated from.
+    pub fn symbols(&self) -> Ref<'_, SymbolContext> {
+        self.ctx.borrow()
+    }
+
+    /// The symbols for emitting this Makefile, whose fresh variables keep
+    /// their names from then on.
+    pub(crate) fn symbols_mut(&self) -> RefMut<'_, SymbolContext> {
+        let mut ctx = self.ctx.borrow_mut();
+        ctx.freeze();
+        ctx
+    }
+
+    /// The symbols for emitting this Makefile later, whose fresh variables
+    /// keep their names from then on.
+    pub(crate) fn shared_symbols(&self) -> RRC<SymbolContext> {
+        self.ctx.borrow_mut().freeze();
+        self.ctx.clone()
+    }
+
+    /// Finds likely bugs in this Makefile, such as variables that are read but
+    /// never assigned, allowing [`lint::DEFAULT_ALLOWED_VARIABLES`] and
+    /// [`lint::IMPLICIT_RULE_VARIABLES`] to be read from the environment.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        self.lint_with(&LintOptions::default())
+    }
+
+    /// Finds likely bugs in this Makefile as configured by `options`.
+    pub fn lint_with(&self, options: &LintOptions) -> Vec<Diagnostic> {
+        lint::lint(self, options)
+    }
+
+    /// The graph of the targets in this Makefile and their prerequisites.
+    pub fn graph(&self) -> RuleGraph {
+        RuleGraph::new(self)
+    }
+
+    /// Every target that make would remake to bring `goal` up to date with
+    /// the files in `fs`, in the order it would remake them, each with why it
+    /// would be remade. `$(wildcard)` lists the files in `fs`.
+    pub fn explain<S: AsRef<str>>(
+        &self, goal: S, fs: &dyn FileSystem
+    ) -> Result<Vec<Explanation>> {
+        explain::explain(self, goal.as_ref(), fs)
+    }
+
+    /// The recipe lines make would run, expanded and in order, to bring `goal`
+    /// up to date with the files in `fs`, without running them. `$(wildcard)`
+    /// lists the files in `fs`.
+    pub fn simulate<S: AsRef<str>>(
+        &self, goal: S, fs: &dyn FileSystem
+    ) -> Result<Vec<String>> {
+        simulate::simulate(self, goal.as_ref(), fs)
+    }
+
+    /// The compilation database for the targets this Makefile makes with rules
+    /// marked with [`RuleRef::compiles`], expanding their recipes as make
+    /// would. See [`compile_commands::to_json`] for `compile_commands.json`.
+    ///
+    /// [`RuleRef::compiles`]: crate::rule::RuleRef::compiles
+    pub fn compile_commands(
+        &self, options: CompileCommandsOptions
+    ) -> Result<Vec<CompileCommand>> {
+        compile_commands::collect(self, options)
+    }
+
+    /// Translates this Makefile into a Ninja `build.ninja`, expanding its
+    /// variables and conditionals and instantiating its pattern rules for
+    /// every target they match. Fails on constructs that Ninja cannot express,
+    /// such as `$(shell)`, which would run when make reads the Makefile.
+    pub fn render_ninja(&self) -> Result<String> {
+        ninja::render(self)
+    }
+
+    /// Translates this Makefile into the syntax of BSD make, e.g., conditionals
+    /// into `.if` and functions into variable modifiers. Fails on constructs
+    /// that BSD make cannot express, such as order-only prerequisites.
+    pub fn render_bsd(&self) -> Result<String> {
+        bsd::render(self)
+    }
+
+    /// Converts this Makefile into its textual representation, or reports
+    /// every problem that would make it invalid.
+    pub fn build(self) -> Result<String> {
+        self.render()
+    }
+
+    /// Constructs the textual representation of this Makefile, which can be
+    /// done any number of times, or reports every problem that would make it
+    /// invalid.
+    pub fn render(&self) -> Result<String> {
+        let mut result = vec![];
+        self.write_to(&mut result)?;
+        Ok(String::from_utf8(result).expect("emitted text is valid UTF-8"))
+    }
+
+    /// Checks that this Makefile is valid, reporting every problem otherwise.
+    pub fn validate(&self) -> Result<()> {
+        self.with_response_files(|| self.check())
+    }
+
+    /// Writes the textual representation of this Makefile to `writer` one
+    /// entry at a time, or reports every problem that would make it invalid
+    /// without writing anything.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        self.with_response_files(|| {
+            self.check()?;
+            if self.version_guard && self.dialect() == Dialect::Gnu {
+                if let Some(requirement) = self.newest_requirement() {
+                    writeln!(writer, "{}", version::guard(&requirement))?;
+                }
+            }
+            for content in &self.contents {
+                if content.is_empty() {
+                    continue;
+                }
+                let text = content.emit(&mut self.ctx.borrow_mut());
+                writeln!(writer, "{}", text)?;
+            }
+            writer.flush()?;
+            Ok(())
+        })
+    }
+}
+
+impl Makefile {
+    /// Checks that this Makefile is valid, with its commands with response
+    /// files measured.
+    fn check(&self) -> Result<()> {
+        let mut errors = vec![];
+        let mut ctx = self.symbols_mut();
+        for content in &self.contents {
+            content.validate(&mut ctx, &mut errors);
+        }
+        ctx.validate(&mut errors);
+        let dialect = ctx.dialect();
+        dialect::check(&self.contents, dialect, &mut ctx, &mut errors);
+        Error::combine(errors).map_or(Ok(()), Err)
+    }
+
+    /// The first of the requirements needing the newest version of GNU make.
+    fn newest_requirement(&self) -> Option<Requirement> {
+        self.requirements()
+            .into_iter()
+            .reduce(|newest, requirement| {
+                if requirement.since > newest.since {
+                    requirement
+                } else {
+                    newest
+                }
+            })
+    }
+
+    /// Runs `f` with the commands that pass their arguments through a
+    /// response file decided, unless they already are, by expanding the
+    /// arguments once with the values this Makefile assigns.
+    pub(crate) fn with_response_files<T>(&self, f: impl FnOnce() -> T) -> T {
+        if self.ctx.borrow().has_response_files() {
+            return f();
+        }
+        self.ctx.borrow_mut().freeze();
+        let env = Env::from_makefile(self).ok();
+        let mut measured = ResponseFiles::new();
+        for content in &self.contents {
+            content.walk(false, &mut |entry, _| {
+                if let Entry::Rule(rule) = entry {
+                    rule.rule.borrow().measure_response_files(
+                        env.as_ref(),
+                        &self.ctx,
+                        &mut measured
+                    );
+                }
+            });
         }
+        self.ctx.borrow_mut().set_response_files(Some(measured));
+        let result = f();
+        self.ctx.borrow_mut().set_response_files(None);
         result
     }
 }
 
 impl EmittableContainer for Makefile {
-    fn add(&mut self, e: EmittableRef) {
+    fn add(&mut self, e: Entry) {
         self.contents.push(e);
     }
 
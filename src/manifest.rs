@@ -0,0 +1,365 @@
+//! A declarative description of a Makefile in TOML, usually named
+//! `Makefile.toml`, for those who would rather not write Rust.
+//!
+//! Variables, includes, conditionals, and rules are added in the order they
+//! are written, even when the arrays they belong to are interleaved. Every
+//! value is make text, so it may reference variables and call functions.
+//!
+//! ```toml
+//! include = ["config.mk"]
+//!
+//! [[variable]]
+//! name = "CC"
+//! value = "cc"
+//! op = "?="
+//!
+//! [[variable]]
+//! name = "OBJ"
+//! value = "main.o util.o"
+//!
+//! [[conditional]]
+//! [[conditional.branch]]
+//! defined = "DEBUG"
+//! variable = [{ name = "CFLAGS", value = "-g", op = "+=" }]
+//!
+//! [[conditional.branch]]
+//! variable = [{ name = "CFLAGS", value = "-O2", op = "+=" }]
+//!
+//! [[rule]]
+//! target = "app"
+//! deps = ["$(OBJ)"]
+//! commands = ["$(CC) $(CFLAGS) -o $@ $^"]
+//!
+//! [[rule]]
+//! target = "clean"
+//! phony = true
+//! commands = ["rm -f app $(OBJ)"]
+//! ```
+//!
+//! A branch of a conditional holds the same variables, includes,
+//! conditionals, and rules as the top level, along with at most one of
+//! `eq = [lhs, rhs]`, `defined = name`, or `undefined = name`. A branch with
+//! none of them is the `else` branch, which must be last and cannot be the
+//! only branch.
+
+use std::{fs, ops::Range, path::Path};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{
+    conditional::Branch,
+    emitter::Emitter,
+    error::{Error, Result},
+    makefile::Makefile,
+    symbol_context::invalid_character
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Section {
+    #[serde(default)]
+    variable: Vec<VariableSpec>,
+    #[serde(default)]
+    include: Vec<Spanned<String>>,
+    #[serde(default)]
+    conditional: Vec<Spanned<ConditionalSpec>>,
+    #[serde(default)]
+    rule: Vec<RuleSpec>
+}
+
+/// An entry of a [`Section`].
+enum Item {
+    Variable(VariableSpec),
+    Include(String),
+    Conditional(Spanned<ConditionalSpec>),
+    Rule(RuleSpec)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariableSpec {
+    name: Spanned<String>,
+    #[serde(default)]
+    value: String,
+    /// One of `=`, `?=`, `:=`, and `+=`, which is `=` by default.
+    op: Option<Spanned<String>>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConditionalSpec {
+    branch: Vec<Spanned<BranchSpec>>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BranchSpec {
+    eq: Option<[String; 2]>,
+    defined: Option<Spanned<String>>,
+    undefined: Option<Spanned<String>>,
+    #[serde(default)]
+    variable: Vec<VariableSpec>,
+    #[serde(default)]
+    include: Vec<Spanned<String>>,
+    #[serde(default)]
+    conditional: Vec<Spanned<ConditionalSpec>>,
+    #[serde(default)]
+    rule: Vec<RuleSpec>
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    target: Spanned<String>,
+    #[serde(default)]
+    deps: Vec<String>,
+    #[serde(default)]
+    order_only: Vec<String>,
+    static_pattern: Option<String>,
+    #[serde(default)]
+    phony: bool,
+    #[serde(default)]
+    commands: Vec<String>
+}
+
+/// The condition guarding a branch other than the `else` branch.
+enum Condition {
+    Eq(String, String),
+    Defined(Spanned<String>),
+    Undefined(Spanned<String>)
+}
+
+struct Loader<'a> {
+    file: &'a str,
+    text: &'a str
+}
+
+impl Loader<'_> {
+    /// An error with `message` at the start of `span` in the text.
+    fn error(&self, span: Range<usize>, message: String) -> Error {
+        let before = &self.text[..span.start.min(self.text.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Error::Parse {
+            file: self.file.to_string(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message
+        }
+    }
+
+    /// An error for the variable `name` at `span` if make does not allow it.
+    fn check_name(&self, name: &str, span: Range<usize>) -> Result<()> {
+        if name.is_empty() {
+            return Err(self.error(span, "variable name is empty".into()));
+        }
+        if let Some(character) = invalid_character(name) {
+            return Err(self.error(
+                span,
+                format!(
+                    "variable name `{}` contains invalid character {:?}",
+                    name, character
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    /// Adds the entries of `section` to `emitter` in the order they are
+    /// written, which deserializing into separate arrays loses but their
+    /// spans keep, reporting each invalid entry to `errors`.
+    fn section<E: Emitter>(
+        &self, section: Section, emitter: &mut E, errors: &mut Vec<Error>
+    ) {
+        let mut items = vec![];
+        for variable in section.variable {
+            items.push((variable.name.span().start, Item::Variable(variable)));
+        }
+        for path in section.include {
+            items.push((path.span().start, Item::Include(path.into_inner())));
+        }
+        for conditional in section.conditional {
+            items.push((
+                conditional.span().start,
+                Item::Conditional(conditional)
+            ));
+        }
+        for rule in section.rule {
+            items.push((rule.target.span().start, Item::Rule(rule)));
+        }
+        items.sort_by_key(|(start, _)| *start);
+        for (_, item) in items {
+            match item {
+                Item::Variable(variable) => {
+                    self.variable(variable, emitter, errors)
+                }
+                Item::Include(path) => emitter.include(path),
+                Item::Conditional(conditional) => {
+                    self.conditional(conditional, emitter, errors)
+                }
+                Item::Rule(rule) => self.rule(rule, emitter, errors)
+            }
+        }
+    }
+
+    fn variable<E: Emitter>(
+        &self, variable: VariableSpec, emitter: &mut E, errors: &mut Vec<Error>
+    ) {
+        let span = variable.name.span();
+        let name = variable.name.into_inner();
+        if let Err(error) = self.check_name(&name, span) {
+            errors.push(error);
+            return;
+        }
+        let value = variable.value;
+        match variable.op.as_ref().map(|op| op.get_ref().as_str()) {
+            None | Some("=") => {
+                emitter.assign(name, value);
+            }
+            Some("?=") => {
+                emitter.assign_without_overwrite(name, value);
+            }
+            Some(":=") => {
+                emitter.assign_simple(name, value);
+            }
+            Some("+=") => {
+                emitter.append(name, value);
+            }
+            Some(op) => {
+                errors.push(self.error(
+                    variable.op.as_ref().unwrap().span(),
+                    format!(
+                        "unknown assignment `{}`, expected one of `=`, `?=`, \
+                         `:=`, and `+=`",
+                        op
+                    )
+                ));
+            }
+        }
+    }
+
+    fn conditional<E: Emitter>(
+        &self, conditional: Spanned<ConditionalSpec>, emitter: &mut E,
+        errors: &mut Vec<Error>
+    ) {
+        let span = conditional.span();
+        let conditional = conditional.into_inner();
+        if conditional.branch.is_empty() {
+            errors.push(self.error(span, Error::EmptyConditional.to_string()));
+            return;
+        }
+        let count = conditional.branch.len();
+        let mut tree = emitter.branch_tree();
+        for (i, branch) in conditional.branch.into_iter().enumerate() {
+            let span = branch.span();
+            let branch = branch.into_inner();
+            let mut conditions = vec![];
+            if let Some([lhs, rhs]) = branch.eq {
+                conditions.push(Condition::Eq(lhs, rhs));
+            }
+            if let Some(name) = branch.defined {
+                conditions.push(Condition::Defined(name));
+            }
+            if let Some(name) = branch.undefined {
+                conditions.push(Condition::Undefined(name));
+            }
+            if conditions.len() > 1 {
+                errors.push(
+                    self.error(
+                        span.clone(),
+                        "branch has more than one of `eq`, `defined`, and \
+                     `undefined`"
+                            .into()
+                    )
+                );
+            }
+            let condition = conditions.pop();
+            match &condition {
+                None if i + 1 < count => {
+                    errors.push(self.error(
+                        span,
+                        "branch without a condition is not the last".into()
+                    ));
+                }
+                None if i == 0 => {
+                    errors.push(self.error(
+                        span,
+                        "branch without a condition is the only one".into()
+                    ));
+                }
+                Some(Condition::Defined(name) | Condition::Undefined(name)) => {
+                    if let Err(error) =
+                        self.check_name(name.get_ref(), name.span())
+                    {
+                        errors.push(error);
+                    }
+                }
+                _ => {}
+            }
+            let section = Section {
+                variable: branch.variable,
+                include: branch.include,
+                conditional: branch.conditional,
+                rule: branch.rule
+            };
+            let load =
+                |branch: &mut Branch| self.section(section, branch, errors);
+            tree = match condition {
+                Some(Condition::Eq(lhs, rhs)) => tree.when_eq(lhs, rhs, load),
+                Some(Condition::Defined(name)) => {
+                    tree.when_def(name.into_inner(), load)
+                }
+                Some(Condition::Undefined(name)) => {
+                    tree.when_undef(name.into_inner(), load)
+                }
+                None => tree.otherwise(load)
+            };
+        }
+    }
+
+    fn rule<E: Emitter>(
+        &self, rule: RuleSpec, emitter: &mut E, errors: &mut Vec<Error>
+    ) {
+        let span = rule.target.span();
+        let target = rule.target.into_inner();
+        if target.trim().is_empty() {
+            errors.push(self.error(span, "rule target is empty".into()));
+            return;
+        }
+        let mut builder = emitter.rule(target);
+        if rule.phony {
+            builder = builder.phony();
+        }
+        if let Some(pattern) = rule.static_pattern {
+            builder = builder.static_pattern(pattern);
+        }
+        for dep in rule.deps {
+            builder = builder.dep(dep);
+        }
+        for dep in rule.order_only {
+            builder = builder.order_only_dep(dep);
+        }
+        for command in rule.commands {
+            builder = builder.cmd(command);
+        }
+    }
+}
+
+/// Reads the description `text` of a Makefile, reporting every error as being
+/// in `file`.
+pub fn parse(text: &str, file: &str) -> Result<Makefile> {
+    let loader = Loader { file, text };
+    let section: Section = toml::from_str(text).map_err(|error| {
+        loader.error(error.span().unwrap_or(0..0), error.message().to_string())
+    })?;
+    let mut makefile = Makefile::new();
+    let mut errors = vec![];
+    loader.section(section, &mut makefile, &mut errors);
+    Error::combine(errors).map_or(Ok(makefile), Err)
+}
+
+/// Reads the description of a Makefile in the file at `path`.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Makefile> {
+    let path = path.as_ref();
+    parse(&fs::read_to_string(path)?, &path.display().to_string())
+}
@@ -1,7 +1,7 @@
 use crate::{emittable::Emittable, symbol_context::SymbolContext};
 
 pub struct Comment {
-    text: String
+    pub(crate) text: String
 }
 
 impl Comment {
@@ -0,0 +1,48 @@
+use crate::{
+    emittable::Emittable, emitter::EmittableContainer, entry::Entry,
+    error::Error, rrc::RRC, symbol_context::SymbolContext
+};
+
+/// A scope within a Makefile in which user variable names are automatically
+/// prefixed by the namespace name, e.g., `SRC` becomes `libfoo_SRC` inside the
+/// namespace `libfoo`.
+pub struct Namespace {
+    pub(crate) contents: Vec<Entry>,
+    ctx: RRC<SymbolContext>
+}
+
+impl Namespace {
+    pub(crate) fn new(ctx: RRC<SymbolContext>) -> Self {
+        Self {
+            contents: vec![],
+            ctx
+        }
+    }
+}
+
+impl EmittableContainer for Namespace {
+    fn add(&mut self, e: Entry) {
+        self.contents.push(e);
+    }
+
+    fn ctx(&mut self) -> RRC<SymbolContext> {
+        self.ctx.clone()
+    }
+}
+
+impl Emittable for Namespace {
+    fn emit(&self, ctx: &mut SymbolContext) -> String {
+        self.contents
+            .iter()
+            .filter(|content| !content.is_empty())
+            .map(|content| content.emit(ctx))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        for content in &self.contents {
+            content.validate(ctx, errors);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{
+    error::{Error, Result},
+    eval::EvalError,
+    makefile::Makefile,
+    plan::{AssumeExists, RecipeLine, Resolved, Rules}
+};
+
+const BACKEND: &str = "Ninja";
+
+/// Ninja has no equivalent of the automatic variables other than `$in` and
+/// `$out`, so the others are passed to each `build` as variables of these
+/// names.
+const BUILD_VARIABLES: &[(&str, &str)] =
+    &[("<", "in_first"), ("|", "order_only"), ("*", "stem")];
+
+/// Escapes `text` for use in a `build` line.
+fn escape_path(text: &str) -> String {
+    text.replace('$', "$$")
+        .replace(' ', "$ ")
+        .replace(':', "$:")
+}
+
+/// A placeholder for an automatic variable while expanding a recipe, which
+/// becomes the Ninja variable `ninja` afterward.
+fn placeholder(ninja: &str) -> String {
+    format!("\u{1}{}\u{1}", ninja)
+}
+
+fn unsupported(error: EvalError) -> Error {
+    match error {
+        EvalError::MissingHook { function }
+        | EvalError::Unsupported { function } => Error::Unsupported {
+            construct: format!("`$({})`", function),
+            backend: BACKEND
+        },
+        error => Error::Eval(error)
+    }
+}
+
+/// The shell command that runs the expanded recipe `lines`, each in its own
+/// subshell like make, so that, e.g., a `cd` does not carry over to the next.
+fn command(lines: &[String]) -> String {
+    let commands = lines
+        .iter()
+        .map(|line| {
+            let line = RecipeLine::parse(line);
+            let command = line.command.replace('\n', "");
+            if line.ignore_errors {
+                format!("{} || true", command)
+            } else {
+                command
+            }
+        })
+        .collect::<Vec<_>>();
+    match commands.as_slice() {
+        [command] => command.clone(),
+        commands => commands
+            .iter()
+            .map(|command| format!("({})", command))
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+}
+
+/// The command for the recipe of `resolved` in a Ninja `rule`, which refers
+/// to the automatic variables through Ninja variables if it can, along with
+/// the Ninja variables the `build` must define.
+fn rule_command(
+    rules: &Rules, resolved: &Resolved
+) -> Result<(String, Vec<(&'static str, String)>)> {
+    let mut actual =
+        rules.automatic_variables(resolved, &AssumeExists, &HashSet::new());
+    // Ninja reruns a command with all of its inputs, so `$?` is `$^`.
+    for suffix in ["", "D", "F"] {
+        let all = actual
+            .iter()
+            .find(|(name, _)| *name == format!("^{}", suffix))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+        for (name, value) in &mut actual {
+            if *name == format!("?{}", suffix) {
+                *value = all.clone();
+            }
+        }
+    }
+    let concrete = command(
+        &rules
+            .expand_recipe_with(resolved, &actual)
+            .map_err(unsupported)?
+    );
+
+    let mut bindings = actual.clone();
+    for (name, value) in &mut bindings {
+        *value = match name.as_str() {
+            "@" => placeholder("out"),
+            "^" | "+" | "?" => placeholder("in"),
+            name => match BUILD_VARIABLES.iter().find(|(make, _)| *make == name)
+            {
+                Some((_, ninja)) => placeholder(ninja),
+                None => continue
+            }
+        };
+    }
+    let generic = command(
+        &rules
+            .expand_recipe_with(resolved, &bindings)
+            .map_err(unsupported)?
+    );
+
+    // The placeholders only stand for the variables if the recipe does not
+    // look inside them, e.g., with `$(notdir $<)`.
+    let mut substituted = generic.clone();
+    for ((name, placeholder), (_, value)) in bindings.iter().zip(&actual) {
+        if !matches!(name.as_str(), "@" | "^" | "+" | "?" | "<" | "|" | "*") {
+            continue;
+        }
+        substituted = substituted.replace(placeholder, value);
+    }
+    if substituted != concrete {
+        return Ok((concrete.replace('$', "$$"), vec![]));
+    }
+
+    let mut result = generic.replace('$', "$$");
+    let mut variables = vec![];
+    for (name, ninja) in BUILD_VARIABLES {
+        if result.contains(&placeholder(ninja)) {
+            let value = actual.iter().find(|(other, _)| other == name).unwrap();
+            variables.push((*ninja, value.1.clone()));
+        }
+    }
+    for ninja in ["out", "in", "in_first", "order_only", "stem"] {
+        result = result.replace(&placeholder(ninja), &format!("${}", ninja));
+    }
+    Ok((result, variables))
+}
+
+pub(crate) fn render(makefile: &Makefile) -> Result<String> {
+    let rules = Rules::load(makefile).map_err(unsupported)?;
+    let targets = rules.closure(&rules.targets, &AssumeExists)?;
+
+    let mut commands: Vec<String> = vec![];
+    let mut builds = String::new();
+    for resolved in &targets {
+        if !resolved.has_rule && !rules.is_phony(&resolved.target) {
+            continue;
+        }
+        let inputs = resolved
+            .prerequisites
+            .iter()
+            .map(|input| escape_path(input))
+            .collect::<Vec<_>>();
+        let mut line = format!("build {}: ", escape_path(&resolved.target));
+        let mut variables = vec![];
+        if resolved.recipe.is_empty() {
+            line.push_str("phony");
+        } else {
+            let (command, build_variables) = rule_command(&rules, resolved)?;
+            let index =
+                match commands.iter().position(|other| *other == command) {
+                    Some(index) => index,
+                    None => {
+                        commands.push(command);
+                        commands.len() - 1
+                    }
+                };
+            write!(&mut line, "rule_{}", index + 1).unwrap();
+            variables = build_variables;
+        }
+        for input in &inputs {
+            write!(&mut line, " {}", input).unwrap();
+        }
+        if !resolved.order_only.is_empty() {
+            line.push_str(" ||");
+            for input in &resolved.order_only {
+                write!(&mut line, " {}", escape_path(input)).unwrap();
+            }
+        }
+        writeln!(&mut builds, "{}", line).unwrap();
+        for (name, value) in variables {
+            writeln!(&mut builds, "  {} = {}", name, value.replace('$', "$$"))
+                .unwrap();
+        }
+    }
+
+    let mut result = String::new();
+    for (i, command) in commands.iter().enumerate() {
+        writeln!(&mut result, "rule rule_{}", i + 1).unwrap();
+        writeln!(&mut result, "  command = {}", command).unwrap();
+        writeln!(&mut result).unwrap();
+    }
+    result.push_str(&builds);
+    if let Some(goal) = &rules.default_goal {
+        writeln!(&mut result, "\ndefault {}", escape_path(goal)).unwrap();
+    }
+    Ok(result)
+}
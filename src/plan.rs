@@ -0,0 +1,689 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    time::{Duration, SystemTime}
+};
+
+use crate::{
+    error::{Error, Result},
+    eval::{Env, EvalError, WildcardHook},
+    explain::Reason,
+    graph,
+    makefile::Makefile,
+    syntax
+};
+
+/// The files that targets and prerequisites name, as far as deciding what to
+/// rebuild is concerned.
+pub trait FileSystem {
+    /// When the file at `path` was last modified, or `None` if it does not
+    /// exist.
+    fn modified(&self, path: &str) -> Option<SystemTime>;
+
+    /// A hook for [`Env::with_wildcard`] that expands a shell glob to the
+    /// paths in this file system matching it, sorted.
+    fn wildcard(&self) -> WildcardHook;
+}
+
+/// The file system of this machine.
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn wildcard(&self) -> WildcardHook {
+        Box::new(glob_disk)
+    }
+}
+
+/// Files that only exist in memory, such as to check in tests what make would
+/// do without touching the disk.
+#[derive(Clone, Default)]
+pub struct VirtualFileSystem {
+    files: HashMap<String, SystemTime>
+}
+
+impl VirtualFileSystem {
+    /// Constructs a file system without any files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file at `path` last modified `modified` seconds after the Unix
+    /// epoch.
+    pub fn with_file<S: AsRef<str>>(mut self, path: S, modified: u64) -> Self {
+        self.insert(
+            path,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(modified)
+        );
+        self
+    }
+
+    /// Adds or replaces the file at `path`, last modified at `modified`.
+    pub fn insert<S: AsRef<str>>(&mut self, path: S, modified: SystemTime) {
+        self.files.insert(path.as_ref().to_string(), modified);
+    }
+
+    /// Removes the file at `path`, if it exists.
+    pub fn remove<S: AsRef<str>>(&mut self, path: S) {
+        self.files.remove(path.as_ref());
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<(S, u64)> for VirtualFileSystem {
+    fn from_iter<T: IntoIterator<Item = (S, u64)>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::new(), |fs, (path, modified)| {
+            fs.with_file(path, modified)
+        })
+    }
+}
+
+impl FileSystem for VirtualFileSystem {
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        self.files.get(path).copied()
+    }
+
+    fn wildcard(&self) -> WildcardHook {
+        let mut paths = self.files.keys().cloned().collect::<Vec<_>>();
+        paths.sort();
+        Box::new(move |pattern| {
+            paths
+                .iter()
+                .filter(|path| glob_matches(pattern, path))
+                .cloned()
+                .collect()
+        })
+    }
+}
+
+/// Treats every file as existing, so that each target that matches a pattern
+/// rule is made with the first such rule.
+pub(crate) struct AssumeExists;
+
+impl FileSystem for AssumeExists {
+    fn modified(&self, _path: &str) -> Option<SystemTime> {
+        Some(SystemTime::UNIX_EPOCH)
+    }
+
+    /// No files can be listed, so a pattern only matches itself, and only if
+    /// it has no glob characters.
+    fn wildcard(&self) -> WildcardHook {
+        Box::new(|pattern| {
+            if pattern.contains(['*', '?', '[']) {
+                vec![]
+            } else {
+                vec![pattern.to_string()]
+            }
+        })
+    }
+}
+
+/// Whether `path` matches the shell glob `pattern`, in which `*` and `?` never
+/// match a `/`, `[...]` matches a character in a set, and a leading `.` must be
+/// matched literally.
+pub(crate) fn glob_matches(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[char], path: &[char], at_start: bool) -> bool {
+        let hidden = at_start && path.first() == Some(&'.');
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some(('*', rest)) => {
+                if hidden {
+                    return false;
+                }
+                (0..=path.len())
+                    .take_while(|&i| i == 0 || path[i - 1] != '/')
+                    .any(|i| matches(rest, &path[i..], false))
+            }
+            Some(('?', rest)) => match path.split_first() {
+                Some((c, path)) if *c != '/' && !hidden => {
+                    matches(rest, path, false)
+                }
+                _ => false
+            },
+            Some(('[', rest)) if !hidden => {
+                let Some((c, path)) = path.split_first() else {
+                    return false;
+                };
+                let Some(end) = rest.iter().skip(1).position(|c| *c == ']')
+                else {
+                    return *c == '[' && matches(rest, path, false);
+                };
+                let (set, rest) = (&rest[..end + 1], &rest[end + 2..]);
+                let (negated, set) = match set.split_first() {
+                    Some(('!' | '^', set)) => (true, set),
+                    _ => (false, set)
+                };
+                let mut found = false;
+                let mut i = 0;
+                while i < set.len() {
+                    if i + 2 < set.len() && set[i + 1] == '-' {
+                        found |= (set[i]..=set[i + 2]).contains(c);
+                        i += 3;
+                    } else {
+                        found |= set[i] == *c;
+                        i += 1;
+                    }
+                }
+                *c != '/' && found != negated && matches(rest, path, false)
+            }
+            Some(('\\', [escaped, rest @ ..])) | Some((escaped, rest)) => {
+                match path.split_first() {
+                    Some((c, path)) if c == escaped => {
+                        matches(rest, path, *c == '/')
+                    }
+                    _ => false
+                }
+            }
+        }
+    }
+
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let path = path.chars().collect::<Vec<_>>();
+    matches(&pattern, &path, true)
+}
+
+/// The paths on disk matching the shell glob `pattern`, sorted.
+fn glob_disk(pattern: &str) -> Vec<String> {
+    let mut paths = vec![String::new()];
+    let mut components = pattern.split('/').peekable();
+    while let Some(component) = components.next() {
+        let is_last = components.peek().is_none();
+        let mut next = vec![];
+        for prefix in paths {
+            if !component.contains(['*', '?', '[']) {
+                next.push(format!("{}{}", prefix, component));
+                continue;
+            }
+            let directory = if prefix.is_empty() { "." } else { &prefix };
+            let Ok(entries) = fs::read_dir(directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if glob_matches(component, &name) {
+                    next.push(format!("{}{}", prefix, name));
+                }
+            }
+        }
+        paths = next;
+        if !is_last {
+            for path in &mut paths {
+                path.push('/');
+            }
+        }
+    }
+    let mut paths = paths
+        .into_iter()
+        .filter(|path| fs::symlink_metadata(path).is_ok())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// A rule as make reads it, with its targets and prerequisites expanded but
+/// not its recipe.
+pub(crate) struct ReadRule {
+    pub(crate) targets: Vec<String>,
+    pub(crate) static_pattern: Option<String>,
+    pub(crate) prerequisites: Vec<String>,
+    pub(crate) order_only: Vec<String>,
+    pub(crate) recipe: Vec<String>,
+    pub(crate) is_phony: bool,
+    pub(crate) compiles: bool
+}
+
+/// What the rules for a single target, which is not a pattern, say about it.
+#[derive(Default)]
+struct ExplicitRule {
+    prerequisites: Vec<String>,
+    order_only: Vec<String>,
+    recipe: Option<Vec<String>>,
+    /// What the `%` of the target pattern matched in a static pattern rule.
+    stem: String,
+    /// Whether the recipe compiles the first prerequisite into the target.
+    compiles: bool
+}
+
+struct PatternRule {
+    target: String,
+    prerequisites: Vec<String>,
+    order_only: Vec<String>,
+    recipe: Vec<String>,
+    compiles: bool
+}
+
+/// How a target is made, once the rules for it have been found.
+pub(crate) struct Resolved {
+    pub(crate) target: String,
+    pub(crate) prerequisites: Vec<String>,
+    pub(crate) order_only: Vec<String>,
+    /// The recipe lines, which are expanded only when they are run.
+    pub(crate) recipe: Vec<String>,
+    /// What the `%` of the pattern rule used matched.
+    pub(crate) stem: String,
+    /// Whether any rule, explicit or pattern, applies to the target.
+    pub(crate) has_rule: bool,
+    /// Whether the recipe compiles the first prerequisite into the target.
+    pub(crate) compiles: bool
+}
+
+/// The rules of a Makefile, as read by make, along with the variables their
+/// recipes are expanded with.
+pub(crate) struct Rules {
+    env: Env,
+    explicit: HashMap<String, ExplicitRule>,
+    patterns: Vec<PatternRule>,
+    phony: HashSet<String>,
+    /// The targets of explicit rules, in the order they are first read.
+    pub(crate) targets: Vec<String>,
+    pub(crate) delete_on_error: bool,
+    pub(crate) default_goal: Option<String>
+}
+
+impl Rules {
+    /// The longest chain of pattern rules followed to make a prerequisite.
+    const MAX_CHAIN: usize = 4;
+
+    pub(crate) fn load(
+        makefile: &Makefile
+    ) -> std::result::Result<Self, EvalError> {
+        Self::load_with(makefile, Env::new())
+    }
+
+    /// Like [`Rules::load`], but expanding with the hooks of `env`.
+    pub(crate) fn load_with(
+        makefile: &Makefile, env: Env
+    ) -> std::result::Result<Self, EvalError> {
+        let mut read = vec![];
+        let env = env.read(makefile, &mut read)?;
+        let mut rules = Self {
+            env,
+            explicit: HashMap::new(),
+            patterns: vec![],
+            phony: HashSet::new(),
+            targets: vec![],
+            delete_on_error: false,
+            default_goal: None
+        };
+        for rule in read {
+            rules.add(rule);
+        }
+        Ok(rules)
+    }
+
+    fn add(&mut self, rule: ReadRule) {
+        if rule.is_phony {
+            self.phony.extend(rule.targets.iter().cloned());
+        }
+        for target in &rule.targets {
+            match target.as_str() {
+                ".PHONY" => {
+                    self.phony.extend(rule.prerequisites.iter().cloned());
+                }
+                ".DELETE_ON_ERROR" => self.delete_on_error = true,
+                target if graph::is_special_target(target) => {}
+                target if target.contains('%') => {
+                    self.patterns.push(PatternRule {
+                        target: target.to_string(),
+                        prerequisites: rule.prerequisites.clone(),
+                        order_only: rule.order_only.clone(),
+                        recipe: rule.recipe.clone(),
+                        compiles: rule.compiles
+                    });
+                }
+                target => {
+                    if self.default_goal.is_none() && !target.starts_with('.') {
+                        self.default_goal = Some(target.to_string());
+                    }
+                    let stem =
+                        rule.static_pattern.as_deref().and_then(|pattern| {
+                            syntax::match_pattern(pattern, target)
+                        });
+                    if !self.explicit.contains_key(target) {
+                        self.targets.push(target.to_string());
+                    }
+                    let explicit =
+                        self.explicit.entry(target.to_string()).or_default();
+                    match stem {
+                        Some(stem) => {
+                            explicit.prerequisites.extend(
+                                syntax::substitute_stem(
+                                    &rule.prerequisites,
+                                    stem
+                                )
+                            );
+                            explicit.order_only.extend(
+                                syntax::substitute_stem(&rule.order_only, stem)
+                            );
+                            explicit.stem = stem.to_string();
+                        }
+                        None => {
+                            explicit
+                                .prerequisites
+                                .extend(rule.prerequisites.iter().cloned());
+                            explicit
+                                .order_only
+                                .extend(rule.order_only.iter().cloned());
+                        }
+                    }
+                    if !rule.recipe.is_empty() {
+                        explicit.recipe = Some(rule.recipe.clone());
+                        explicit.compiles = rule.compiles;
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn is_phony(&self, target: &str) -> bool {
+        self.phony.contains(target)
+    }
+
+    /// Finds the rules that make `target`, searching the pattern rules if no
+    /// explicit rule for it has a recipe.
+    pub(crate) fn resolve(
+        &self, target: &str, fs: &dyn FileSystem
+    ) -> Resolved {
+        let explicit = self.explicit.get(target);
+        let mut resolved = Resolved {
+            target: target.to_string(),
+            prerequisites: explicit
+                .map(|rule| rule.prerequisites.clone())
+                .unwrap_or_default(),
+            order_only: explicit
+                .map(|rule| rule.order_only.clone())
+                .unwrap_or_default(),
+            recipe: explicit
+                .and_then(|rule| rule.recipe.clone())
+                .unwrap_or_default(),
+            stem: explicit.map(|rule| rule.stem.clone()).unwrap_or_default(),
+            has_rule: explicit.is_some(),
+            compiles: explicit.is_some_and(|rule| rule.compiles)
+        };
+        if explicit.is_some_and(|rule| rule.recipe.is_some())
+            || self.is_phony(target)
+        {
+            return resolved;
+        }
+        if let Some((rule, stem)) = self.find_pattern(target, fs, 0) {
+            let mut prerequisites =
+                syntax::substitute_stem(&rule.prerequisites, &stem);
+            prerequisites.append(&mut resolved.prerequisites);
+            resolved.prerequisites = prerequisites;
+            resolved
+                .order_only
+                .extend(syntax::substitute_stem(&rule.order_only, &stem));
+            resolved.recipe = rule.recipe.clone();
+            resolved.stem = stem;
+            resolved.has_rule = true;
+            resolved.compiles = rule.compiles;
+        }
+        resolved
+    }
+
+    /// The first pattern rule with a recipe whose target matches `target` and
+    /// whose prerequisites exist or can be made, along with the stem.
+    fn find_pattern(
+        &self, target: &str, fs: &dyn FileSystem, depth: usize
+    ) -> Option<(&PatternRule, String)> {
+        if depth > Self::MAX_CHAIN {
+            return None;
+        }
+        self.patterns
+            .iter()
+            .filter(|rule| !rule.recipe.is_empty())
+            .find_map(|rule| {
+                let stem = syntax::match_pattern(&rule.target, target)?;
+                rule.prerequisites
+                    .iter()
+                    .map(|pattern| pattern.replacen('%', stem, 1))
+                    .all(|prerequisite| {
+                        fs.modified(&prerequisite).is_some()
+                            || self.explicit.contains_key(&prerequisite)
+                            || self
+                                .find_pattern(&prerequisite, fs, depth + 1)
+                                .is_some()
+                    })
+                    .then(|| (rule, stem.to_string()))
+            })
+    }
+
+    /// Resolves `goals` and everything they depend on, each once and after
+    /// everything it depends on, or reports a circular dependency.
+    pub(crate) fn closure(
+        &self, goals: &[String], fs: &dyn FileSystem
+    ) -> Result<Vec<Resolved>> {
+        fn visit(
+            rules: &Rules, target: &str, fs: &dyn FileSystem,
+            path: &mut Vec<String>, done: &mut HashSet<String>,
+            order: &mut Vec<Resolved>
+        ) -> Result<()> {
+            if let Some(start) = path.iter().position(|other| other == target) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(target.to_string());
+                return Err(Error::CircularDependency { cycle });
+            }
+            if done.contains(target) {
+                return Ok(());
+            }
+            let resolved = rules.resolve(target, fs);
+            path.push(target.to_string());
+            for prerequisite in
+                resolved.prerequisites.iter().chain(&resolved.order_only)
+            {
+                visit(rules, prerequisite, fs, path, done, order)?;
+            }
+            path.pop();
+            done.insert(target.to_string());
+            order.push(resolved);
+            Ok(())
+        }
+
+        let mut done = HashSet::new();
+        let mut order = vec![];
+        for goal in goals {
+            visit(self, goal, fs, &mut vec![], &mut done, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Why `resolved` needs to be made, if it does, given the prerequisites
+    /// that have been `remade` already.
+    pub(crate) fn reasons(
+        &self, resolved: &Resolved, fs: &dyn FileSystem,
+        remade: &HashSet<String>
+    ) -> Vec<Reason> {
+        if self.is_phony(&resolved.target) {
+            return vec![Reason::Phony];
+        }
+        let Some(modified) = fs.modified(&resolved.target) else {
+            return vec![Reason::Missing];
+        };
+        resolved
+            .prerequisites
+            .iter()
+            .filter_map(|prerequisite| {
+                self.prerequisite_reason(
+                    prerequisite,
+                    Some(modified),
+                    fs,
+                    remade
+                )
+            })
+            .collect()
+    }
+
+    /// Why `prerequisite` causes a target last `modified` then to be made, if
+    /// it does.
+    fn prerequisite_reason(
+        &self, prerequisite: &str, modified: Option<SystemTime>,
+        fs: &dyn FileSystem, remade: &HashSet<String>
+    ) -> Option<Reason> {
+        let prerequisite_name = prerequisite.to_string();
+        if self.is_phony(prerequisite) {
+            return Some(Reason::PhonyPrerequisite {
+                prerequisite: prerequisite_name
+            });
+        }
+        if remade.contains(prerequisite) {
+            return Some(Reason::RemadePrerequisite {
+                prerequisite: prerequisite_name
+            });
+        }
+        match fs.modified(prerequisite) {
+            None => Some(Reason::MissingPrerequisite {
+                prerequisite: prerequisite_name
+            }),
+            Some(time) if modified.is_none_or(|modified| time > modified) => {
+                Some(Reason::NewerPrerequisite {
+                    prerequisite: prerequisite_name
+                })
+            }
+            Some(_) => None
+        }
+    }
+
+    /// The prerequisites of `resolved` that are newer than it, as in `$?`,
+    /// given those that have been `remade` already.
+    fn newer<'a>(
+        &self, resolved: &'a Resolved, fs: &dyn FileSystem,
+        remade: &HashSet<String>
+    ) -> Vec<&'a str> {
+        let modified = fs.modified(&resolved.target);
+        resolved
+            .prerequisites
+            .iter()
+            .filter(|prerequisite| {
+                self.prerequisite_reason(prerequisite, modified, fs, remade)
+                    .is_some()
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Every target among `goals` and what they depend on that make would
+    /// remake, in order, paired with why. Fails if a file that is needed does
+    /// not exist and has no rule to make it.
+    pub(crate) fn dry_run(
+        &self, goals: &[String], fs: &dyn FileSystem
+    ) -> Result<Vec<(Resolved, Vec<Reason>)>> {
+        let mut remade = HashSet::new();
+        let mut result = vec![];
+        for resolved in self.closure(goals, fs)? {
+            if !resolved.has_rule && !self.is_phony(&resolved.target) {
+                if fs.modified(&resolved.target).is_none() {
+                    return Err(Error::UnknownTarget {
+                        target: resolved.target
+                    });
+                }
+                continue;
+            }
+            let reasons = self.reasons(&resolved, fs, &remade);
+            if !reasons.is_empty() {
+                remade.insert(resolved.target.clone());
+                result.push((resolved, reasons));
+            }
+        }
+        Ok(result)
+    }
+
+    /// The recipe of `resolved` with the automatic variables bound, one entry
+    /// per line.
+    pub(crate) fn expand_recipe(
+        &self, resolved: &Resolved, fs: &dyn FileSystem,
+        remade: &HashSet<String>
+    ) -> std::result::Result<Vec<String>, EvalError> {
+        let bindings = self.automatic_variables(resolved, fs, remade);
+        self.expand_recipe_with(resolved, &bindings)
+    }
+
+    /// The recipe of `resolved` with `bindings` bound, one entry per line.
+    pub(crate) fn expand_recipe_with(
+        &self, resolved: &Resolved, bindings: &[(String, String)]
+    ) -> std::result::Result<Vec<String>, EvalError> {
+        resolved
+            .recipe
+            .iter()
+            .map(|line| self.env.expand_with(line, bindings))
+            .collect()
+    }
+
+    /// The values of the automatic variables, like `$@` and `$(<D)`, in the
+    /// recipe of `resolved`.
+    pub(crate) fn automatic_variables(
+        &self, resolved: &Resolved, fs: &dyn FileSystem,
+        remade: &HashSet<String>
+    ) -> Vec<(String, String)> {
+        let mut deduplicated = vec![];
+        for prerequisite in &resolved.prerequisites {
+            if !deduplicated.contains(prerequisite) {
+                deduplicated.push(prerequisite.clone());
+            }
+        }
+        let automatic = [
+            ("@", resolved.target.clone()),
+            (
+                "<",
+                resolved.prerequisites.first().cloned().unwrap_or_default()
+            ),
+            ("^", deduplicated.join(" ")),
+            ("+", resolved.prerequisites.join(" ")),
+            ("|", resolved.order_only.join(" ")),
+            ("?", self.newer(resolved, fs, remade).join(" ")),
+            ("*", resolved.stem.clone())
+        ];
+        let mut bindings = vec![];
+        for (name, value) in automatic {
+            let words = value.split_whitespace();
+            let dirs = words
+                .clone()
+                .map(|word| match word.rfind('/') {
+                    Some(0) => "/",
+                    Some(slash) => &word[..slash],
+                    None => "."
+                })
+                .collect::<Vec<_>>();
+            let files = words
+                .map(|word| &word[word.rfind('/').map_or(0, |i| i + 1)..])
+                .collect::<Vec<_>>();
+            bindings.push((format!("{}D", name), dirs.join(" ")));
+            bindings.push((format!("{}F", name), files.join(" ")));
+            bindings.push((name.to_string(), value));
+        }
+        bindings
+    }
+}
+
+/// How make runs a recipe line.
+pub(crate) struct RecipeLine<'a> {
+    pub(crate) command: &'a str,
+    /// `@`: the line is not echoed.
+    pub(crate) silent: bool,
+    /// `-`: a failure of the line is ignored.
+    pub(crate) ignore_errors: bool
+}
+
+impl<'a> RecipeLine<'a> {
+    /// Reads the prefixes of an expanded recipe `line`.
+    pub(crate) fn parse(line: &'a str) -> Self {
+        let mut result = Self {
+            command: line.trim_start(),
+            silent: false,
+            ignore_errors: false
+        };
+        loop {
+            match result.command.chars().next() {
+                Some('@') => result.silent = true,
+                Some('-') => result.ignore_errors = true,
+                Some('+') => {}
+                _ => break
+            }
+            result.command = result.command[1..].trim_start();
+        }
+        result
+    }
+}
@@ -1,7 +1,11 @@
 pub use crate::{
+    c_project::CProject,
+    dialect::Dialect,
     directive::Directive,
     emitter::Emitter,
     expr,
     function::{Function, Substitution},
-    makefile::Makefile
+    makefile::Makefile,
+    mk,
+    var::VariableName
 };
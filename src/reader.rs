@@ -0,0 +1,672 @@
+//! Reads the text of a Makefile back into a [`Makefile`], e.g., to emit it
+//! again canonically.
+//!
+//! Only what a [`Makefile`] can hold is read: variable assignments, includes,
+//! rules with their recipes, `.PHONY`, conditionals, comments, and blank
+//! lines. A few constructs are read as equivalents:
+//! - `::=` as `:=`, and `NAME != cmd` as `NAME := $(shell cmd)`;
+//! - `ifneq` as `ifeq` with its branches swapped;
+//! - `.PHONY` as marking each rule it names at the same level of nesting;
+//! - comments among the lines of a recipe as comments on their own lines after
+//!   the rule.
+//!
+//! Everything else, such as `define`, `export`, double-colon rules,
+//! target-specific variables, `ifdef` of a computed variable name, and
+//! comments after other text on the same line, is reported as an error.
+
+use std::{fs, path::Path};
+
+use crate::{
+    assignment::AssignmentKind,
+    conditional::ConditionalRef,
+    emitter::Emitter,
+    error::{Error, Result},
+    expr::Expr,
+    function::Function,
+    makefile::Makefile,
+    symbol_context::invalid_character
+};
+
+/// The directives that begin a conditional.
+const CONDITIONALS: &[&str] = &["ifeq", "ifneq", "ifdef", "ifndef"];
+
+/// The directives that a [`Makefile`] cannot hold.
+const UNSUPPORTED: &[&str] = &[
+    "define", "endef", "export", "unexport", "override", "undefine", "private",
+    "vpath", "load"
+];
+
+/// The test of a branch of a conditional as written.
+enum Test {
+    Eq(String, String),
+    Ne(String, String),
+    Def(String),
+    Undef(String)
+}
+
+struct Rule {
+    targets: Vec<String>,
+    static_pattern: Option<String>,
+    deps: Vec<String>,
+    order_only: Vec<String>,
+    commands: Vec<String>,
+    is_phony: bool
+}
+
+enum Node {
+    Blank,
+    Comment(String),
+    Assignment {
+        name: String,
+        kind: AssignmentKind,
+        value: Expr
+    },
+    Include {
+        path: String,
+        is_optional: bool
+    },
+    Rule(Rule),
+    Conditional(Vec<(Option<Test>, Vec<Node>)>)
+}
+
+/// How a block of lines ended.
+enum End {
+    Eof,
+    /// An `else` line, with the text after `else`, on `line`.
+    Else {
+        rest: String,
+        line: usize
+    },
+    Endif
+}
+
+struct Reader<'a> {
+    file: &'a str,
+    lines: Vec<&'a str>,
+    next: usize
+}
+
+impl Reader<'_> {
+    fn error(&self, line: usize, message: String) -> Error {
+        Error::Parse {
+            file: self.file.to_string(),
+            line,
+            column: 1,
+            message
+        }
+    }
+
+    /// The next line, with the lines it continues onto joined to it, and the
+    /// number of its first line. A recipe line keeps its line breaks, while
+    /// any other line has each one and the whitespace around it replaced with
+    /// a space.
+    fn logical_line(&mut self, recipe: bool) -> Option<(String, usize)> {
+        let number = self.next + 1;
+        let mut text = self.lines.get(self.next)?.to_string();
+        self.next += 1;
+        while ends_with_continuation(&text) && self.next < self.lines.len() {
+            let next = self.lines[self.next];
+            self.next += 1;
+            text.pop();
+            if recipe {
+                text.push('\n');
+                text.push_str(next.strip_prefix('\t').unwrap_or(next));
+            } else {
+                text.truncate(text.trim_end().len());
+                text.push(' ');
+                text.push_str(next.trim_start());
+            }
+        }
+        Some((text, number))
+    }
+
+    /// Reads lines until the end of the text or, within a conditional, the
+    /// `else` or `endif` ending the current branch.
+    fn block(&mut self, nested: bool) -> Result<(Vec<Node>, End)> {
+        let mut nodes = vec![];
+        // The comments and blank lines among the lines of the recipe of the
+        // last rule, which are added after the rule.
+        let mut pending = vec![];
+        let mut in_recipe = false;
+        loop {
+            let is_recipe_line = in_recipe
+                && self
+                    .lines
+                    .get(self.next)
+                    .is_some_and(|line| line.starts_with('\t'));
+            let Some((text, number)) = self.logical_line(is_recipe_line) else {
+                nodes.append(&mut pending);
+                return if nested {
+                    let line = self.lines.len();
+                    Err(self.error(line, "missing `endif`".into()))
+                } else {
+                    Ok((nodes, End::Eof))
+                };
+            };
+            if is_recipe_line {
+                if let Some(Node::Rule(rule)) = nodes.last_mut() {
+                    rule.commands.push(text[1..].to_string());
+                }
+                continue;
+            }
+            let (code, comment) = split_comment(&text);
+            let code = code.trim();
+            if code.is_empty() {
+                let node = match comment {
+                    Some(comment) if !comment.is_empty() => {
+                        Node::Comment(comment)
+                    }
+                    _ => Node::Blank
+                };
+                if in_recipe {
+                    pending.push(node);
+                } else {
+                    nodes.push(node);
+                }
+                continue;
+            }
+            // A comment cannot be kept on the line it follows, and moving it
+            // would change the value of an assignment, which keeps the
+            // whitespace before the comment.
+            if comment.is_some() {
+                return Err(self.error(
+                    number,
+                    "comments after other text are not supported".into()
+                ));
+            }
+            nodes.append(&mut pending);
+            in_recipe = false;
+            let (keyword, rest) = split_keyword(code);
+            match keyword {
+                "else" if nested => {
+                    return Ok((
+                        nodes,
+                        End::Else {
+                            rest: rest.to_string(),
+                            line: number
+                        }
+                    ));
+                }
+                "endif" if nested => return Ok((nodes, End::Endif)),
+                "else" | "endif" => {
+                    return Err(
+                        self.error(number, format!("extraneous `{}`", keyword))
+                    );
+                }
+                "include" | "-include" | "sinclude" => {
+                    nodes.push(Node::Include {
+                        path: rest.to_string(),
+                        is_optional: keyword != "include"
+                    });
+                }
+                _ if CONDITIONALS.contains(&keyword) => {
+                    let test = self.test(keyword, rest, number)?;
+                    nodes.push(self.conditional(test)?);
+                }
+                _ if UNSUPPORTED.contains(&keyword) => {
+                    return Err(self.error(
+                        number,
+                        format!("`{}` is not supported", keyword)
+                    ));
+                }
+                _ => {
+                    let node = self.statement(code, number)?;
+                    in_recipe = matches!(node, Node::Rule(_));
+                    nodes.push(node);
+                }
+            }
+        }
+    }
+
+    /// Reads the branches of a conditional whose first branch has `test`.
+    fn conditional(&mut self, test: Test) -> Result<Node> {
+        let mut branches = vec![];
+        let mut test = Some(test);
+        loop {
+            let (body, end) = self.block(true)?;
+            let has_test = test.is_some();
+            branches.push((test.take(), body));
+            match end {
+                End::Else { rest, line } => {
+                    if !has_test {
+                        return Err(
+                            self.error(line, "extraneous `else`".into())
+                        );
+                    }
+                    if !rest.is_empty() {
+                        let (keyword, arguments) = split_keyword(&rest);
+                        test = Some(self.test(keyword, arguments, line)?);
+                    }
+                }
+                End::Endif => return Ok(Node::Conditional(branches)),
+                End::Eof => unreachable!("nested blocks end with `endif`")
+            }
+        }
+    }
+
+    fn test(
+        &self, keyword: &str, arguments: &str, line: usize
+    ) -> Result<Test> {
+        let invalid =
+            || self.error(line, "invalid syntax in conditional".into());
+        match keyword {
+            "ifeq" | "ifneq" => {
+                let (lhs, rhs) = comparison(arguments).ok_or_else(invalid)?;
+                Ok(if keyword == "ifeq" {
+                    Test::Eq(lhs, rhs)
+                } else {
+                    Test::Ne(lhs, rhs)
+                })
+            }
+            "ifdef" | "ifndef" => {
+                if arguments.is_empty() || words(arguments).len() != 1 {
+                    return Err(invalid());
+                }
+                if arguments.contains('$') {
+                    return Err(self.error(
+                        line,
+                        format!(
+                            "`{}` of a computed variable name is not supported",
+                            keyword
+                        )
+                    ));
+                }
+                if let Some(character) = invalid_character(arguments) {
+                    return Err(self.error(
+                        line,
+                        format!(
+                            "variable name `{}` contains invalid character \
+                             {:?}",
+                            arguments, character
+                        )
+                    ));
+                }
+                let name = arguments.to_string();
+                Ok(if keyword == "ifdef" {
+                    Test::Def(name)
+                } else {
+                    Test::Undef(name)
+                })
+            }
+            _ => Err(invalid())
+        }
+    }
+
+    /// Reads `code`, which is not a directive, as an assignment or a rule.
+    fn statement(&self, code: &str, line: usize) -> Result<Node> {
+        for (i, c) in top_level(code) {
+            let after = &code[i..];
+            if c == '=' {
+                let (name, op) = [
+                    (":::=", AssignmentKind::Immediate),
+                    ("::=", AssignmentKind::Simple),
+                    (":=", AssignmentKind::Simple),
+                    ("?=", AssignmentKind::Underwrite),
+                    ("+=", AssignmentKind::Append),
+                    ("!=", AssignmentKind::Simple),
+                    ("=", AssignmentKind::Overwrite)
+                ]
+                .into_iter()
+                .find_map(|(op, kind)| {
+                    code[..=i].strip_suffix(op).map(|name| (name, (op, kind)))
+                })
+                .expect("`=` ends the operator");
+                return self.assignment(
+                    name.trim(),
+                    op,
+                    code[i + 1..].trim(),
+                    line
+                );
+            }
+            if c == ':'
+                && !after.starts_with(":=")
+                && !after.starts_with("::=")
+                && !after.starts_with(":::=")
+            {
+                if after.starts_with("::") {
+                    return Err(self.error(
+                        line,
+                        "double-colon rules are not supported".into()
+                    ));
+                }
+                return self.rule(&code[..i], &code[i + 1..], line);
+            }
+        }
+        Err(self.error(line, "missing separator".into()))
+    }
+
+    fn assignment(
+        &self, name: &str, (op, kind): (&str, AssignmentKind), value: &str,
+        line: usize
+    ) -> Result<Node> {
+        if name.is_empty() {
+            return Err(self.error(line, "variable name is empty".into()));
+        }
+        if let Some(character) = invalid_character(name) {
+            return Err(self.error(
+                line,
+                format!(
+                    "variable name `{}` contains invalid character {:?}",
+                    name, character
+                )
+            ));
+        }
+        let value = if op == "!=" {
+            Expr::from(Function::shell(value))
+        } else {
+            Expr::from(value)
+        };
+        Ok(Node::Assignment {
+            name: name.to_string(),
+            kind,
+            value
+        })
+    }
+
+    fn rule(&self, targets: &str, rest: &str, line: usize) -> Result<Node> {
+        let (rest, command) =
+            match top_level(rest).into_iter().find(|(_, c)| *c == ';') {
+                Some((i, _)) => (&rest[..i], Some(rest[i + 1..].trim_start())),
+                None => (rest, None)
+            };
+        if top_level(rest).iter().any(|(_, c)| *c == '=') {
+            return Err(self.error(
+                line,
+                "target-specific variables are not supported".into()
+            ));
+        }
+        let (static_pattern, rest) =
+            match top_level(rest).into_iter().find(|(_, c)| *c == ':') {
+                Some((i, _)) => (Some(rest[..i].trim()), &rest[i + 1..]),
+                None => (None, rest)
+            };
+        let (deps, order_only) =
+            match top_level(rest).into_iter().find(|(_, c)| *c == '|') {
+                Some((i, _)) => (&rest[..i], &rest[i + 1..]),
+                None => (rest, "")
+            };
+        let targets = words(targets);
+        if targets.is_empty() {
+            return Err(self.error(line, "rule target is empty".into()));
+        }
+        if static_pattern.is_some_and(|pattern| words(pattern).len() != 1) {
+            return Err(self.error(
+                line,
+                "static pattern rule has more than one target pattern".into()
+            ));
+        }
+        Ok(Node::Rule(Rule {
+            targets,
+            static_pattern: static_pattern.map(str::to_string),
+            deps: words(deps),
+            order_only: words(order_only),
+            commands: command.into_iter().map(str::to_string).collect(),
+            is_phony: false
+        }))
+    }
+}
+
+/// Splits `code` into its first word, which may be followed by `(` as in
+/// `ifeq(a,b)`, and the rest.
+fn split_keyword(code: &str) -> (&str, &str) {
+    let end = code
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(code.len());
+    (&code[..end], code[end..].trim())
+}
+
+/// Whether `text` ends with a backslash that is not itself escaped.
+fn ends_with_continuation(text: &str) -> bool {
+    (text.len() - text.trim_end_matches('\\').len()) % 2 == 1
+}
+
+/// Splits `text` into the code before its first unescaped `#` and the comment
+/// after it, without the `#` and the space following it.
+fn split_comment(text: &str) -> (&str, Option<String>) {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\\' => escaped = !escaped,
+            '#' if !escaped => {
+                let comment = &text[i + 1..];
+                let comment = comment.strip_prefix(' ').unwrap_or(comment);
+                return (&text[..i], Some(comment.trim_end().to_string()));
+            }
+            _ => escaped = false
+        }
+    }
+    (text, None)
+}
+
+/// The characters of `text` outside variable references and function calls,
+/// with their byte offsets.
+fn top_level(text: &str) -> Vec<(usize, char)> {
+    let mut result = vec![];
+    let mut depth = 0usize;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '$' => {
+                if let Some((_, '(' | '{')) = chars.next() {
+                    depth += 1;
+                }
+            }
+            '(' | '{' if depth > 0 => depth += 1,
+            ')' | '}' if depth > 0 => depth -= 1,
+            _ if depth == 0 => result.push((i, c)),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Splits `text` at the whitespace outside variable references and function
+/// calls.
+fn words(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut start = 0;
+    for (i, c) in top_level(text) {
+        if c.is_whitespace() {
+            if start < i {
+                words.push(text[start..i].to_string());
+            }
+            start = i + c.len_utf8();
+        }
+    }
+    if start < text.len() {
+        words.push(text[start..].to_string());
+    }
+    words
+}
+
+/// The two sides of the comparison in `ifeq (lhs, rhs)`, `ifeq "lhs" "rhs"`,
+/// or `ifeq 'lhs' 'rhs'`.
+fn comparison(arguments: &str) -> Option<(String, String)> {
+    if let Some(inner) = arguments.strip_prefix('(') {
+        let inner = inner.strip_suffix(')')?;
+        let mut depth = 0usize;
+        for (i, c) in inner.char_indices() {
+            match c {
+                '(' | '{' => depth += 1,
+                ')' | '}' => depth = depth.checked_sub(1)?,
+                ',' if depth == 0 => {
+                    return Some((
+                        inner[..i].trim().to_string(),
+                        inner[i + 1..].trim().to_string()
+                    ));
+                }
+                _ => {}
+            }
+        }
+        return None;
+    }
+    let quoted = |text: &str| -> Option<(String, usize)> {
+        let quote = text.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let end = text[1..].find(quote)? + 1;
+        Some((text[1..end].to_string(), end + 1))
+    };
+    let (lhs, end) = quoted(arguments)?;
+    let rest = arguments[end..].trim_start();
+    let (rhs, end) = quoted(rest)?;
+    rest[end..].trim().is_empty().then_some((lhs, rhs))
+}
+
+/// Marks each rule in `nodes` as phony if `.PHONY` at the same level names all
+/// its targets, dropping what `.PHONY` then no longer needs to name.
+fn absorb_phony(nodes: &mut Vec<Node>) {
+    let is_phony_declaration = |rule: &Rule| {
+        rule.targets == [".PHONY"]
+            && rule.static_pattern.is_none()
+            && rule.order_only.is_empty()
+            && rule.commands.is_empty()
+    };
+    let mut declared = vec![];
+    for node in nodes.iter() {
+        if let Node::Rule(rule) = node {
+            if is_phony_declaration(rule) {
+                declared.extend(rule.deps.iter().cloned());
+            }
+        }
+    }
+    let mut absorbed = vec![];
+    for node in nodes.iter_mut() {
+        match node {
+            Node::Rule(rule)
+                if !is_phony_declaration(rule)
+                    && rule
+                        .targets
+                        .iter()
+                        .all(|target| declared.contains(target)) =>
+            {
+                rule.is_phony = true;
+                absorbed.extend(rule.targets.iter().cloned());
+            }
+            Node::Conditional(branches) => {
+                for (_, body) in branches {
+                    absorb_phony(body);
+                }
+            }
+            _ => {}
+        }
+    }
+    nodes.retain_mut(|node| match node {
+        Node::Rule(rule) if is_phony_declaration(rule) => {
+            rule.deps.retain(|dep| !absorbed.contains(dep));
+            !rule.deps.is_empty()
+        }
+        _ => true
+    });
+}
+
+fn add<E: Emitter>(nodes: Vec<Node>, emitter: &mut E) {
+    for node in nodes {
+        match node {
+            Node::Blank => emitter.newline(),
+            Node::Comment(text) => emitter.comment(text),
+            Node::Assignment { name, kind, value } => {
+                match kind {
+                    AssignmentKind::Overwrite => emitter.assign(name, value),
+                    AssignmentKind::Underwrite => {
+                        emitter.assign_without_overwrite(name, value)
+                    }
+                    AssignmentKind::Simple => {
+                        emitter.assign_simple(name, value)
+                    }
+                    AssignmentKind::Immediate => {
+                        emitter.assign_immediate(name, value)
+                    }
+                    AssignmentKind::Append => emitter.append(name, value)
+                };
+            }
+            Node::Include { path, is_optional } => {
+                if is_optional {
+                    emitter.include_optional(path);
+                } else {
+                    emitter.include(path);
+                }
+            }
+            Node::Rule(rule) => {
+                let mut builder = emitter.rule(rule.targets.join(" "));
+                if rule.is_phony {
+                    builder = builder.phony();
+                }
+                if let Some(pattern) = rule.static_pattern {
+                    builder = builder.static_pattern(pattern);
+                }
+                for dep in rule.deps {
+                    builder = builder.dep(dep);
+                }
+                for dep in rule.order_only {
+                    builder = builder.order_only_dep(dep);
+                }
+                for command in rule.commands {
+                    builder = builder.cmd(command);
+                }
+            }
+            Node::Conditional(branches) => {
+                add_branches(emitter.branch_tree(), branches);
+            }
+        }
+    }
+}
+
+/// Adds `branches` to `tree`, reading `ifneq` as `ifeq` and `else`.
+fn add_branches(
+    mut tree: ConditionalRef, branches: Vec<(Option<Test>, Vec<Node>)>
+) {
+    let mut branches = branches.into_iter();
+    while let Some((test, body)) = branches.next() {
+        let (lhs, rhs) = match test {
+            None => {
+                tree.otherwise(|branch| add(body, branch));
+                return;
+            }
+            Some(Test::Eq(lhs, rhs)) => {
+                tree = tree.when_eq(lhs, rhs, |branch| add(body, branch));
+                continue;
+            }
+            Some(Test::Def(name)) => {
+                tree = tree.when_def(name, |branch| add(body, branch));
+                continue;
+            }
+            Some(Test::Undef(name)) => {
+                tree = tree.when_undef(name, |branch| add(body, branch));
+                continue;
+            }
+            Some(Test::Ne(lhs, rhs)) => (lhs, rhs)
+        };
+        // `ifneq` holds when `ifeq` does not, so the remaining branches go in
+        // the `ifeq` branch and this one in the `else` branch.
+        let rest = branches.collect::<Vec<_>>();
+        tree = tree.when_eq(lhs, rhs, |branch| match rest.first() {
+            None => {}
+            Some((None, _)) => {
+                let (_, body) = rest.into_iter().next().unwrap();
+                add(body, branch);
+            }
+            Some(_) => add_branches(branch.branch_tree(), rest)
+        });
+        tree.otherwise(|branch| add(body, branch));
+        return;
+    }
+}
+
+/// Reads the Makefile `text`, reporting errors as being in `file`.
+pub fn parse(text: &str, file: &str) -> Result<Makefile> {
+    let mut reader = Reader {
+        file,
+        lines: text.lines().collect(),
+        next: 0
+    };
+    let (mut nodes, _) = reader.block(false)?;
+    absorb_phony(&mut nodes);
+    let mut makefile = Makefile::new();
+    add(nodes, &mut makefile);
+    Ok(makefile)
+}
+
+/// Reads the Makefile in the file at `path`.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Makefile> {
+    let path = path.as_ref();
+    parse(&fs::read_to_string(path)?, &path.display().to_string())
+}
@@ -1,19 +1,63 @@
-use std::fmt::Write;
+use std::{collections::HashMap, fmt::Write};
 
 use crate::{
     emittable::Emittable,
+    error::Error,
+    eval::Env,
     expr::{EmittableVec, Expr},
+    function::{FileOp, Function},
     rrc::{rrc, RRC},
-    symbol_context::SymbolContext
+    symbol_context::SymbolContext,
+    var::Variable
 };
 
+/// The default length, in bytes, of the arguments to a command above which
+/// they are passed through a response file. It is well below the 128 KiB
+/// that Linux allows for the single argument make passes to the shell.
+pub const DEFAULT_RESPONSE_FILE_THRESHOLD: usize = 32 * 1024;
+
+/// A line in the recipe of a rule.
+pub(crate) enum Command {
+    Plain(Expr),
+    /// `cmd args` if the arguments are short enough, otherwise `cmd @file`
+    /// after writing the arguments to a response file with `$(file ...)`.
+    ResponseFile {
+        cmd: Expr,
+        args: Vec<Expr>
+    }
+}
+
+impl Command {
+    pub(crate) fn response_file(cmd: Expr, args: Vec<Expr>) -> Self {
+        Self::ResponseFile { cmd, args }
+    }
+
+    /// Identifies this command in [`ResponseFiles`] by its address, which does
+    /// not change while the Makefile is borrowed to be validated or written.
+    pub(crate) fn key(&self) -> usize {
+        std::ptr::from_ref(self) as usize
+    }
+}
+
+/// Whether the arguments of each command with a response file, by
+/// [`Command::key`], are longer than the threshold once expanded, as decided
+/// by [`Rule::measure_response_files`].
+pub(crate) type ResponseFiles = HashMap<usize, bool>;
+
 /// A Makefile rule.
-struct Rule {
-    is_phony: bool,
-    target: Expr,
-    dependencies: Vec<Expr>,
-    order_only_dependencies: Vec<Expr>,
-    commands: Vec<Expr>
+pub(crate) struct Rule {
+    pub(crate) is_phony: bool,
+    pub(crate) target: Expr,
+    /// The target pattern of a static pattern rule, which each target matches
+    /// to give the stem substituted into the dependencies.
+    pub(crate) static_pattern: Option<Expr>,
+    pub(crate) dependencies: Vec<Expr>,
+    pub(crate) order_only_dependencies: Vec<Expr>,
+    pub(crate) commands: Vec<Command>,
+    pub(crate) response_file_threshold: usize,
+    /// Whether the recipe compiles the first dependency into the target, for
+    /// the compilation database.
+    pub(crate) compiles: bool
 }
 
 impl Rule {
@@ -21,9 +65,96 @@ impl Rule {
         Self {
             is_phony: false,
             target: target.into(),
+            static_pattern: None,
             dependencies: vec![],
             order_only_dependencies: vec![],
-            commands: vec![]
+            commands: vec![],
+            response_file_threshold: DEFAULT_RESPONSE_FILE_THRESHOLD,
+            compiles: false
+        }
+    }
+
+    /// The recipe lines emitted for `command`.
+    pub(crate) fn emit_command(
+        &self, command: &Command, ctx: &mut SymbolContext
+    ) -> Vec<String> {
+        match command {
+            Command::Plain(cmd) => vec![cmd.emit(ctx)],
+            Command::ResponseFile { cmd, args } => {
+                let is_long = ctx.is_long_response_file(command.key());
+                let cmd = cmd.emit(ctx);
+                let args = args.join_emit(" ", ctx);
+                // Unless measured, a reference might expand to anything.
+                let is_long = is_long.unwrap_or_else(|| {
+                    args.contains('$')
+                        || args.len() > self.response_file_threshold
+                });
+                if !is_long {
+                    return vec![format!("{} {}", cmd, args)];
+                }
+                let target = Variable::target(ctx);
+                let path = || Expr::from(target).concat(".rsp");
+                let write = Function::file(
+                    FileOp::Write,
+                    path(),
+                    Some(Expr::from(args))
+                );
+                vec![write.emit(ctx), format!("{} @{}", cmd, path().emit(ctx))]
+            }
+        }
+    }
+
+    /// Decides in `measured` whether the arguments of each command with a
+    /// response file are longer than the threshold once expanded with `env`,
+    /// binding the automatic variables to the targets and dependencies.
+    /// Arguments that cannot be expanded, e.g., without `env`, are taken to be
+    /// longer.
+    pub(crate) fn measure_response_files(
+        &self, env: Option<&Env>, ctx: &RRC<SymbolContext>,
+        measured: &mut ResponseFiles
+    ) {
+        let commands = self
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::ResponseFile { args, .. } => Some((
+                    command.key(),
+                    args.join_emit(" ", &mut ctx.borrow_mut())
+                )),
+                Command::Plain(_) => None
+            })
+            .collect::<Vec<_>>();
+        if commands.is_empty() {
+            return;
+        }
+        let bindings = env.and_then(|env| {
+            let mut ctx = ctx.borrow_mut();
+            let target = self.target.emit(&mut ctx);
+            let dependencies = self.dependencies.join_emit(" ", &mut ctx);
+            let order_only =
+                self.order_only_dependencies.join_emit(" ", &mut ctx);
+            drop(ctx);
+            let dependencies = env.expand(dependencies).ok()?;
+            let first = dependencies.split_whitespace().next();
+            Some(vec![
+                ("@".to_string(), env.expand(target).ok()?),
+                ("<".to_string(), first.unwrap_or_default().to_string()),
+                ("^".to_string(), dependencies.clone()),
+                ("+".to_string(), dependencies.clone()),
+                ("?".to_string(), dependencies),
+                ("|".to_string(), env.expand(order_only).ok()?),
+            ])
+        });
+        for (key, args) in commands {
+            let length =
+                env.zip(bindings.as_ref()).and_then(|(env, bindings)| {
+                    env.expand_with(&args, bindings).ok().map(|args| args.len())
+                });
+            measured.insert(
+                key,
+                length
+                    .is_none_or(|length| length > self.response_file_threshold)
+            );
         }
     }
 }
@@ -34,13 +165,12 @@ impl Emittable for Rule {
         if self.is_phony {
             writeln!(&mut result, ".PHONY: {}", self.target.emit(ctx)).unwrap();
         }
-        write!(
-            &mut result,
-            "{}: {}",
-            self.target.emit(ctx),
-            self.dependencies.join_emit(" ", ctx)
-        )
-        .unwrap();
+        write!(&mut result, "{}: ", self.target.emit(ctx)).unwrap();
+        if let Some(pattern) = &self.static_pattern {
+            write!(&mut result, "{}: ", pattern.emit(ctx)).unwrap();
+        }
+        write!(&mut result, "{}", self.dependencies.join_emit(" ", ctx))
+            .unwrap();
         if !self.order_only_dependencies.is_empty() {
             write!(
                 &mut result,
@@ -50,22 +180,45 @@ impl Emittable for Rule {
             .unwrap();
         }
         for command in &self.commands {
-            result.push('\n');
-            write!(
-                &mut result,
-                "\t{}",
-                command.emit(ctx).replace('\n', "\\\n")
-            )
-            .unwrap();
+            for line in self.emit_command(command, ctx) {
+                result.push('\n');
+                write!(&mut result, "\t{}", line.replace('\n', "\\\n"))
+                    .unwrap();
+            }
         }
         result
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        if self.target.emit(ctx).trim().is_empty() {
+            errors.push(Error::EmptyRuleTarget);
+        }
+        let exprs = [&self.target]
+            .into_iter()
+            .chain(&self.static_pattern)
+            .chain(&self.dependencies)
+            .chain(&self.order_only_dependencies);
+        for expr in exprs {
+            expr.validate(ctx, errors);
+        }
+        for command in &self.commands {
+            match command {
+                Command::Plain(cmd) => cmd.validate(ctx, errors),
+                Command::ResponseFile { cmd, args } => {
+                    cmd.validate(ctx, errors);
+                    for arg in args {
+                        arg.validate(ctx, errors);
+                    }
+                }
+            }
+        }
+    }
 }
 
 /// Builder for a Makefile rule.
 #[derive(Clone)]
 pub struct RuleRef {
-    rule: RRC<Rule>
+    pub(crate) rule: RRC<Rule>
 }
 
 impl RuleRef {
@@ -79,6 +232,10 @@ impl RuleRef {
         self.rule.borrow_mut().is_phony = true;
     }
 
+    pub fn set_static_pattern<E: Into<Expr>>(&self, pattern: E) {
+        self.rule.borrow_mut().static_pattern = Some(pattern.into());
+    }
+
     pub fn add_dep<E: Into<Expr>>(&self, dep: E) {
         self.rule.borrow_mut().dependencies.push(dep.into());
     }
@@ -91,7 +248,27 @@ impl RuleRef {
     }
 
     pub fn add_cmd<E: Into<Expr>>(&self, cmd: E) {
-        self.rule.borrow_mut().commands.push(cmd.into());
+        self.rule
+            .borrow_mut()
+            .commands
+            .push(Command::Plain(cmd.into()));
+    }
+
+    pub fn add_cmd_with_response_file<E: Into<Expr>, A: Into<Vec<Expr>>>(
+        &self, cmd: E, args: A
+    ) {
+        self.rule
+            .borrow_mut()
+            .commands
+            .push(Command::response_file(cmd.into(), args.into()));
+    }
+
+    pub fn set_response_file_threshold(&self, threshold: usize) {
+        self.rule.borrow_mut().response_file_threshold = threshold;
+    }
+
+    pub fn set_compiles(&self) {
+        self.rule.borrow_mut().compiles = true;
     }
 
     /// Marks this rule as "phony".
@@ -100,6 +277,15 @@ impl RuleRef {
         self
     }
 
+    /// Makes this a static pattern rule, in which each target matches the
+    /// target `pattern`, e.g., `%.o`, and the `%` in each dependency stands for
+    /// what the `%` in `pattern` matched. See section
+    /// [4.12](https://www.gnu.org/software/make/manual/make.html#Static-Pattern).
+    pub fn static_pattern<E: Into<Expr>>(self, pattern: E) -> Self {
+        self.set_static_pattern(pattern);
+        self
+    }
+
     /// Adds a dependency in the order specified by the order of calls to this
     /// function.
     pub fn dep<E: Into<Expr>>(self, dep: E) -> Self {
@@ -119,10 +305,50 @@ impl RuleRef {
         self.add_cmd(cmd);
         self
     }
+
+    /// Adds the command `cmd` followed by `args` to the rule. If the arguments
+    /// are longer than the response file threshold, they are instead written to
+    /// `$@.rsp` with `$(file ...)` (GNU make 4.0) and the command is passed
+    /// `@$@.rsp`.
+    ///
+    /// The length is that of the arguments expanded with the values the
+    /// Makefile assigns when it is emitted, with `$@`, `$^`, and the like
+    /// standing for the targets and dependencies of the rule. Arguments that
+    /// cannot be expanded, such as those calling `$(shell ...)`, always use a
+    /// response file.
+    pub fn cmd_with_response_file<E: Into<Expr>, A: Into<Vec<Expr>>>(
+        self, cmd: E, args: A
+    ) -> Self {
+        self.add_cmd_with_response_file(cmd, args);
+        self
+    }
+
+    /// Sets the length, in bytes, of the arguments to a command added with
+    /// [`RuleRef::cmd_with_response_file`] above which they are passed through
+    /// a response file, which is [`DEFAULT_RESPONSE_FILE_THRESHOLD`] by
+    /// default.
+    pub fn response_file_threshold(self, threshold: usize) -> Self {
+        self.set_response_file_threshold(threshold);
+        self
+    }
+
+    /// Marks the recipe of this rule as compiling its first dependency, `$<`,
+    /// into its target, `$@`, so that the recipe line naming that dependency
+    /// is listed for it in [`Makefile::compile_commands`].
+    ///
+    /// [`Makefile::compile_commands`]: crate::makefile::Makefile::compile_commands
+    pub fn compiles(self) -> Self {
+        self.set_compiles();
+        self
+    }
 }
 
 impl Emittable for RuleRef {
     fn emit(&self, ctx: &mut SymbolContext) -> String {
         self.rule.borrow().emit(ctx)
     }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        self.rule.borrow().validate(ctx, errors);
+    }
 }
@@ -0,0 +1,492 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    assignment::{Assignment, AssignmentKind},
+    conditional::{Condition, ConditionalRef},
+    dialect::Dialect,
+    directive::Include,
+    emittable::Emittable,
+    emitter::EmittableContainer,
+    entry::Entry,
+    error::Error,
+    expr::Expr,
+    function::{Function, Substitution, SubstitutionKind},
+    makefile::Makefile,
+    misc::{Comment, Newline},
+    namespace::Namespace,
+    rrc::RRC,
+    rule::{Command, RuleRef, DEFAULT_RESPONSE_FILE_THRESHOLD},
+    symbol_context::{Resolvable, SymbolContext},
+    var::{_Variable, Variable, VariableName}
+};
+
+/// The version of the schema with which a [`Makefile`] is serialized, which is
+/// incremented whenever the schema changes incompatibly. Deserializing a
+/// Makefile serialized with a different version fails.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A serialized [`Makefile`].
+#[derive(Serialize, Deserialize)]
+struct Document {
+    schema: u32,
+    #[serde(default)]
+    dialect: Dialect,
+    #[serde(default)]
+    version_guard: bool,
+    entries: Vec<EntrySchema>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum EntrySchema {
+    Comment {
+        text: String
+    },
+    Newline,
+    Assignment {
+        kind: AssignmentKind,
+        var: String,
+        value: ExprSchema
+    },
+    Include {
+        path: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        optional: bool
+    },
+    Rule(RuleSchema),
+    Conditional {
+        branches: Vec<BranchSchema>
+    },
+    Namespace {
+        entries: Vec<EntrySchema>
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RuleSchema {
+    target: ExprSchema,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    static_pattern: Option<ExprSchema>,
+    #[serde(default)]
+    phony: bool,
+    #[serde(default)]
+    dependencies: Vec<ExprSchema>,
+    #[serde(default)]
+    order_only_dependencies: Vec<ExprSchema>,
+    #[serde(default)]
+    commands: Vec<CommandSchema>,
+    #[serde(
+        default = "default_response_file_threshold",
+        skip_serializing_if = "is_default_response_file_threshold"
+    )]
+    response_file_threshold: usize,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    compiles: bool
+}
+
+fn default_response_file_threshold() -> usize {
+    DEFAULT_RESPONSE_FILE_THRESHOLD
+}
+
+fn is_default_response_file_threshold(threshold: &usize) -> bool {
+    *threshold == DEFAULT_RESPONSE_FILE_THRESHOLD
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CommandSchema {
+    Plain(ExprSchema),
+    ResponseFile {
+        cmd: ExprSchema,
+        args: Vec<ExprSchema>
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BranchSchema {
+    /// `None` for an `else` branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    condition: Option<ConditionSchema>,
+    entries: Vec<EntrySchema>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ConditionSchema {
+    Eq(ExprSchema, ExprSchema),
+    Def(String),
+    Undef(String)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExprSchema {
+    Empty,
+    Raw(String),
+    /// A variable by its name, which is automatic or otherwise defined by make
+    /// if it is `builtin`.
+    Var {
+        name: String,
+        #[serde(default)]
+        builtin: bool
+    },
+    Ref(NameSchema),
+    Concat(Vec<ExprSchema>),
+    Substitution {
+        kind: SubstitutionKind,
+        var: NameSchema,
+        old: Box<ExprSchema>,
+        new: Box<ExprSchema>
+    },
+    Function {
+        name: String,
+        args: Vec<ExprSchema>
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NameSchema {
+    Static(String),
+    Computed(Box<ExprSchema>)
+}
+
+fn expr_to_schema(expr: &Expr, ctx: &mut SymbolContext) -> ExprSchema {
+    match expr {
+        Expr::Empty => ExprSchema::Empty,
+        Expr::Raw(text) => ExprSchema::Raw(text.clone()),
+        Expr::Var(var) => ExprSchema::Var {
+            name: var.name(ctx).to_string(),
+            builtin: matches!(var.value, _Variable::Builtin(_))
+        },
+        Expr::Ref(name) => ExprSchema::Ref(name_to_schema(name, ctx)),
+        Expr::Concat(list) => ExprSchema::Concat(
+            list.iter().map(|expr| expr_to_schema(expr, ctx)).collect()
+        ),
+        Expr::SubstRef(substitution) => ExprSchema::Substitution {
+            kind: substitution.kind,
+            var: name_to_schema(&substitution.var, ctx),
+            old: Box::new(expr_to_schema(&substitution.old, ctx)),
+            new: Box::new(expr_to_schema(&substitution.new, ctx))
+        },
+        Expr::Function(function) => ExprSchema::Function {
+            name: function.name().to_string(),
+            args: function
+                .args()
+                .iter()
+                .map(|arg| expr_to_schema(arg, ctx))
+                .collect()
+        }
+    }
+}
+
+fn name_to_schema(name: &VariableName, ctx: &mut SymbolContext) -> NameSchema {
+    match name {
+        VariableName::Static(promise) => {
+            NameSchema::Static(promise.name(ctx).to_string())
+        }
+        VariableName::Computed(expr) => {
+            NameSchema::Computed(Box::new(expr_to_schema(expr, ctx)))
+        }
+    }
+}
+
+fn entry_to_schema(entry: &Entry, ctx: &mut SymbolContext) -> EntrySchema {
+    let exprs = |list: &[Expr], ctx: &mut SymbolContext| {
+        list.iter().map(|expr| expr_to_schema(expr, ctx)).collect()
+    };
+    match entry {
+        Entry::Comment(comment) => EntrySchema::Comment {
+            text: comment.text.clone()
+        },
+        Entry::Newline(_) => EntrySchema::Newline,
+        Entry::Assignment(assignment) => EntrySchema::Assignment {
+            kind: assignment.kind,
+            var: assignment.var.name(ctx).to_string(),
+            value: expr_to_schema(&assignment.value, ctx)
+        },
+        Entry::Include(include) => EntrySchema::Include {
+            path: include.path().emit(ctx),
+            optional: include.is_optional()
+        },
+        Entry::Rule(rule) => {
+            let rule = rule.rule.borrow();
+            EntrySchema::Rule(RuleSchema {
+                target: expr_to_schema(&rule.target, ctx),
+                static_pattern: rule
+                    .static_pattern
+                    .as_ref()
+                    .map(|pattern| expr_to_schema(pattern, ctx)),
+                phony: rule.is_phony,
+                dependencies: exprs(&rule.dependencies, ctx),
+                order_only_dependencies: exprs(
+                    &rule.order_only_dependencies,
+                    ctx
+                ),
+                commands: rule
+                    .commands
+                    .iter()
+                    .map(|command| match command {
+                        Command::Plain(cmd) => {
+                            CommandSchema::Plain(expr_to_schema(cmd, ctx))
+                        }
+                        Command::ResponseFile { cmd, args } => {
+                            CommandSchema::ResponseFile {
+                                cmd: expr_to_schema(cmd, ctx),
+                                args: exprs(args, ctx)
+                            }
+                        }
+                    })
+                    .collect(),
+                response_file_threshold: rule.response_file_threshold,
+                compiles: rule.compiles
+            })
+        }
+        Entry::Conditional(conditional) => EntrySchema::Conditional {
+            branches: conditional
+                .conditional
+                .borrow()
+                .branches
+                .iter()
+                .map(|branch| BranchSchema {
+                    condition: branch.condition.as_ref().map(|condition| {
+                        match condition {
+                            Condition::Eq(lhs, rhs) => ConditionSchema::Eq(
+                                expr_to_schema(lhs, ctx),
+                                expr_to_schema(rhs, ctx)
+                            ),
+                            Condition::Def(var) => {
+                                ConditionSchema::Def(var.name(ctx).to_string())
+                            }
+                            Condition::Undef(var) => ConditionSchema::Undef(
+                                var.name(ctx).to_string()
+                            )
+                        }
+                    }),
+                    entries: branch
+                        .contents
+                        .iter()
+                        .map(|entry| entry_to_schema(entry, ctx))
+                        .collect()
+                })
+                .collect()
+        },
+        Entry::Namespace(namespace) => EntrySchema::Namespace {
+            entries: namespace
+                .contents
+                .iter()
+                .map(|entry| entry_to_schema(entry, ctx))
+                .collect()
+        }
+    }
+}
+
+/// The variable named exactly `name`, which is not qualified by the current
+/// namespace since serialized names already are.
+fn variable(name: &str, builtin: bool, ctx: &RRC<SymbolContext>) -> Variable {
+    if builtin {
+        ctx.borrow_mut().get_select::<_, true>(name)
+    } else {
+        ctx.borrow_mut().get_select::<_, false>(name)
+    }
+}
+
+fn expr_from_schema(
+    schema: ExprSchema, ctx: &RRC<SymbolContext>
+) -> Result<Expr, String> {
+    Ok(match schema {
+        ExprSchema::Empty => Expr::Empty,
+        ExprSchema::Raw(text) => Expr::Raw(text),
+        ExprSchema::Var { name, builtin } => {
+            Expr::Var(variable(&name, builtin, ctx))
+        }
+        ExprSchema::Ref(name) => {
+            Expr::Ref(Box::new(name_from_schema(name, ctx)?))
+        }
+        ExprSchema::Concat(list) => Expr::Concat(
+            list.into_iter()
+                .map(|expr| expr_from_schema(expr, ctx))
+                .collect::<Result<_, _>>()?
+        ),
+        ExprSchema::Substitution {
+            kind,
+            var,
+            old,
+            new
+        } => Expr::SubstRef(Box::new(Substitution {
+            kind,
+            var: name_from_schema(var, ctx)?,
+            old: expr_from_schema(*old, ctx)?,
+            new: expr_from_schema(*new, ctx)?
+        })),
+        ExprSchema::Function { name, args } => Expr::Function(
+            Function::builtin(
+                name,
+                args.into_iter()
+                    .map(|arg| expr_from_schema(arg, ctx))
+                    .collect::<Result<Vec<_>, _>>()?
+            )
+            .map_err(|error| error.to_string())?
+        )
+    })
+}
+
+fn name_from_schema(
+    schema: NameSchema, ctx: &RRC<SymbolContext>
+) -> Result<VariableName, String> {
+    Ok(match schema {
+        NameSchema::Static(name) => VariableName::from(name),
+        NameSchema::Computed(expr) => {
+            VariableName::Computed(expr_from_schema(*expr, ctx)?)
+        }
+    })
+}
+
+fn entries_from_schema(
+    schemas: Vec<EntrySchema>, ctx: &RRC<SymbolContext>
+) -> Result<Vec<Entry>, String> {
+    schemas
+        .into_iter()
+        .map(|schema| entry_from_schema(schema, ctx))
+        .collect()
+}
+
+fn entry_from_schema(
+    schema: EntrySchema, ctx: &RRC<SymbolContext>
+) -> Result<Entry, String> {
+    let exprs = |list: Vec<ExprSchema>| {
+        list.into_iter()
+            .map(|expr| expr_from_schema(expr, ctx))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(match schema {
+        EntrySchema::Comment { text } => Entry::Comment(Comment::new(text)),
+        EntrySchema::Newline => Entry::Newline(Newline),
+        EntrySchema::Assignment { kind, var, value } => {
+            Entry::Assignment(Assignment::new(
+                kind,
+                variable(&var, false, ctx),
+                expr_from_schema(value, ctx)?
+            ))
+        }
+        EntrySchema::Include { path, optional } => {
+            Entry::Include(if optional {
+                Include::optional(path)
+            } else {
+                Include::new(path)
+            })
+        }
+        EntrySchema::Rule(schema) => {
+            let rule = RuleRef::new(expr_from_schema(schema.target, ctx)?);
+            {
+                let mut rule = rule.rule.borrow_mut();
+                rule.static_pattern = schema
+                    .static_pattern
+                    .map(|pattern| expr_from_schema(pattern, ctx))
+                    .transpose()?;
+                rule.is_phony = schema.phony;
+                rule.dependencies = exprs(schema.dependencies)?;
+                rule.order_only_dependencies =
+                    exprs(schema.order_only_dependencies)?;
+                for command in schema.commands {
+                    rule.commands.push(match command {
+                        CommandSchema::Plain(cmd) => {
+                            Command::Plain(expr_from_schema(cmd, ctx)?)
+                        }
+                        CommandSchema::ResponseFile { cmd, args } => {
+                            Command::response_file(
+                                expr_from_schema(cmd, ctx)?,
+                                exprs(args)?
+                            )
+                        }
+                    });
+                }
+                rule.response_file_threshold = schema.response_file_threshold;
+                rule.compiles = schema.compiles;
+            }
+            Entry::Rule(rule)
+        }
+        EntrySchema::Conditional { branches } => {
+            let mut conditional = ConditionalRef::new(ctx.clone());
+            let mut has_else = false;
+            for branch in branches {
+                if has_else {
+                    return Err(Error::BranchAfterElse.to_string());
+                }
+                has_else = branch.condition.is_none();
+                let condition = match branch.condition {
+                    Some(ConditionSchema::Eq(lhs, rhs)) => Some(Condition::Eq(
+                        expr_from_schema(lhs, ctx)?,
+                        expr_from_schema(rhs, ctx)?
+                    )),
+                    Some(ConditionSchema::Def(name)) => {
+                        Some(Condition::Def(variable(&name, false, ctx)))
+                    }
+                    Some(ConditionSchema::Undef(name)) => {
+                        Some(Condition::Undef(variable(&name, false, ctx)))
+                    }
+                    None => None
+                };
+                let entries = entries_from_schema(branch.entries, ctx)?;
+                conditional =
+                    conditional.build_conditional(condition, |branch| {
+                        for entry in entries {
+                            branch.add(entry);
+                        }
+                    });
+            }
+            Entry::Conditional(conditional)
+        }
+        EntrySchema::Namespace { entries } => {
+            let mut namespace = Namespace::new(ctx.clone());
+            namespace.contents = entries_from_schema(entries, ctx)?;
+            Entry::Namespace(namespace)
+        }
+    })
+}
+
+/// Serializes with the schema [`SCHEMA_VERSION`], in which every variable is
+/// referred to by its name in the Makefile.
+impl Serialize for Makefile {
+    fn serialize<S: Serializer>(
+        &self, serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        let document = {
+            let mut ctx = self.symbols_mut();
+            Document {
+                schema: SCHEMA_VERSION,
+                dialect: ctx.dialect(),
+                version_guard: self.version_guard,
+                entries: self
+                    .contents
+                    .iter()
+                    .map(|entry| entry_to_schema(entry, &mut ctx))
+                    .collect()
+            }
+        };
+        document.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Makefile {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let document = Document::deserialize(deserializer)?;
+        if document.schema != SCHEMA_VERSION {
+            return Err(de::Error::custom(format!(
+                "schema version {} is not supported, only {}",
+                document.schema, SCHEMA_VERSION
+            )));
+        }
+        let mut makefile = Makefile::with_dialect(document.dialect);
+        makefile.set_version_guard(document.version_guard);
+        let ctx = makefile.ctx();
+        for entry in entries_from_schema(document.entries, &ctx)
+            .map_err(de::Error::custom)?
+        {
+            makefile.add(entry);
+        }
+        Ok(makefile)
+    }
+}
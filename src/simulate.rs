@@ -0,0 +1,27 @@
+use std::collections::HashSet;
+
+use crate::{
+    error::Result,
+    eval::Env,
+    makefile::Makefile,
+    plan::{FileSystem, RecipeLine, Rules}
+};
+
+pub(crate) fn simulate(
+    makefile: &Makefile, goal: &str, fs: &dyn FileSystem
+) -> Result<Vec<String>> {
+    let env = Env::new().with_wildcard(fs.wildcard());
+    let rules = Rules::load_with(makefile, env)?;
+    let stale = rules.dry_run(&[goal.to_string()], fs)?;
+    let remade = stale
+        .iter()
+        .map(|(resolved, _)| resolved.target.clone())
+        .collect::<HashSet<_>>();
+    let mut lines = vec![];
+    for (resolved, _) in &stale {
+        for line in rules.expand_recipe(resolved, fs, &remade)? {
+            lines.push(RecipeLine::parse(&line).command.to_string());
+        }
+    }
+    Ok(lines)
+}
@@ -1,6 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::var::{Variable, _Variable};
+use crate::{
+    dialect::Dialect,
+    error::Error,
+    rule::ResponseFiles,
+    var::{_Variable, Variable}
+};
 
 pub type ImmutableString = Box<str>;
 pub type SymbolID = i32;
@@ -13,18 +18,190 @@ struct Symbol {
 #[derive(Default)]
 pub struct SymbolContext {
     store: HashMap<ImmutableString, Variable>,
-    strings: Vec<ImmutableString>
+    strings: Vec<ImmutableString>,
+    origins: HashMap<SymbolID, ImmutableString>,
+    namespaces: Vec<ImmutableString>,
+    /// The name each variable created by [`SymbolContext::fresh`] was derived
+    /// from, so that it can be renamed if a user variable is later requested
+    /// by its name.
+    fresh: HashMap<SymbolID, String>,
+    /// Whether the names of fresh variables are final, as they are once the
+    /// Makefile has been validated or emitted.
+    frozen: bool,
+    /// The user variables referenced by the contents being validated.
+    reachable: HashSet<SymbolID>,
+    /// Which commands with response files are long, while the Makefile is
+    /// being validated or written.
+    response_files: Option<ResponseFiles>,
+    dialect: Dialect
 }
 
 impl SymbolContext {
+    /// Creates or retrieves the user variable named `var`, qualified by the
+    /// namespaces currently entered.
     pub fn get<S: AsRef<str>>(&mut self, var: S) -> Variable {
-        self.get_select::<S, false>(var)
+        let var = var.as_ref();
+        let name = self.qualify(var);
+        if !self.frozen {
+            self.reserve(name.as_deref().unwrap_or(var));
+        }
+        match name {
+            Some(qualified) => {
+                let existed = self.store.contains_key(qualified.as_str());
+                let result = self.get_select::<_, false>(qualified);
+                if !existed {
+                    self.origins.insert(result.id(), var.into());
+                }
+                result
+            }
+            None => self.get_select::<_, false>(var)
+        }
+    }
+
+    /// Creates a user variable whose name, derived from `prefix` and qualified
+    /// by the namespaces currently entered, is distinct from every other
+    /// variable in this context.
+    pub fn fresh<S: AsRef<str>>(&mut self, prefix: S) -> Variable {
+        let prefix = prefix.as_ref();
+        let base = self.qualify(prefix).unwrap_or_else(|| prefix.to_string());
+        let name = self.fresh_name(&base);
+        let result = self.get_select::<_, false>(name);
+        self.origins.insert(result.id(), prefix.into());
+        self.fresh.insert(result.id(), base);
+        result
+    }
+
+    /// The first of `base_0`, `base_1`, and so on that no variable has.
+    fn fresh_name(&self, base: &str) -> String {
+        (0..)
+            .map(|suffix| format!("{}_{}", base, suffix))
+            .find(|candidate| !self.store.contains_key(candidate.as_str()))
+            .unwrap()
+    }
+
+    /// Renames the fresh variable named `name`, if any, so that `name` can be
+    /// given to a user variable.
+    fn reserve(&mut self, name: &str) {
+        let Some(var) = self.store.get(name).copied() else {
+            return;
+        };
+        let Some(base) = self.fresh.get(&var.id()) else {
+            return;
+        };
+        let renamed = self.fresh_name(base);
+        self.store.remove(name);
+        self.strings[var.id() as usize] =
+            ImmutableString::from(renamed.as_str());
+        self.store.insert(renamed.into(), var);
+    }
+
+    /// Stops renaming fresh variables, so that every emission names them the
+    /// same, even if a user variable requested by the name of one is only
+    /// resolved then, in which case it is the fresh variable.
+    pub(crate) fn freeze(&mut self) {
+        self.frozen = true;
     }
 
+    /// Whether the commands with response files have been measured for the
+    /// validation or writing in progress.
+    pub(crate) fn has_response_files(&self) -> bool {
+        self.response_files.is_some()
+    }
+
+    pub(crate) fn set_response_files(
+        &mut self, response_files: Option<ResponseFiles>
+    ) {
+        self.response_files = response_files;
+    }
+
+    /// Whether the arguments of the command with a response file identified by
+    /// `key` are long, if measured.
+    pub(crate) fn is_long_response_file(&self, key: usize) -> Option<bool> {
+        self.response_files.as_ref()?.get(&key).copied()
+    }
+
+    /// The name of `var` as emitted in the Makefile.
     pub fn name(&self, var: Variable) -> &str {
         &self.strings[var.id() as usize]
     }
 
+    /// The name `var` was requested by before it was namespaced or made
+    /// fresh, or its emitted name if it was neither.
+    pub fn original_name(&self, var: Variable) -> &str {
+        self.origins
+            .get(&var.id())
+            .map(|origin| origin.as_ref())
+            .unwrap_or_else(|| self.name(var))
+    }
+
+    /// Every generated name in this context paired with the name it was
+    /// generated from, in order of creation.
+    pub fn generated_names(&self) -> Vec<(&str, &str)> {
+        let mut ids = self.origins.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| {
+                (
+                    self.strings[id as usize].as_ref(),
+                    self.origins[&id].as_ref()
+                )
+            })
+            .collect()
+    }
+
+    /// Qualifies subsequently requested user variables by `namespace` until
+    /// the matching [`SymbolContext::exit_namespace`].
+    pub fn enter_namespace<S: AsRef<str>>(&mut self, namespace: S) {
+        self.namespaces.push(namespace.as_ref().into());
+    }
+
+    /// Leaves the most recently entered namespace.
+    pub fn exit_namespace(&mut self) {
+        self.namespaces.pop();
+    }
+
+    /// The variant of make that the Makefile is emitted for.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    pub(crate) fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
+    /// Records that `var` is referenced by the contents being validated.
+    pub(crate) fn mark_reachable(&mut self, var: Variable) {
+        if let _Variable::User(id) = var.value {
+            self.reachable.insert(id);
+        }
+    }
+
+    /// Reports to `errors` every user variable marked reachable since the last
+    /// validation whose name make does not allow.
+    pub(crate) fn validate(&mut self, errors: &mut Vec<Error>) {
+        let mut ids = self.reachable.drain().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let name = &self.strings[id as usize];
+            if name.is_empty() {
+                errors.push(Error::EmptyVariableName);
+            } else if let Some(character) = invalid_character(name) {
+                errors.push(Error::InvalidVariableName {
+                    name: name.to_string(),
+                    character
+                });
+            }
+        }
+    }
+
+    fn qualify(&self, name: &str) -> Option<String> {
+        if self.namespaces.is_empty() {
+            None
+        } else {
+            Some(format!("{}_{}", self.namespaces.join("_"), name))
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn get_select<S: AsRef<str>, const IS_BUILTIN: bool>(
         &mut self, var: S
@@ -57,6 +234,12 @@ impl SymbolContext {
     }
 }
 
+/// The first character in the variable name `name` that make does not allow.
+pub(crate) fn invalid_character(name: &str) -> Option<char> {
+    name.chars()
+        .find(|c| c.is_whitespace() || matches!(c, '=' | ':' | '#'))
+}
+
 pub trait Resolvable {
     fn resolve(&self, ctx: &mut SymbolContext) -> Variable;
     fn name<'a>(&self, ctx: &'a mut SymbolContext) -> &'a str;
@@ -0,0 +1,334 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display}
+};
+
+use crate::{
+    assignment::AssignmentKind,
+    catalog::{self, FunctionSpec}
+};
+
+/// A piece of make text, as read by make when expanding it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Fragment {
+    /// Text expanded as itself. `$$` is read as a `$`.
+    Text(String),
+    /// `$(name)`, `${name}`, or `$x`, where `name` may itself contain
+    /// references, optionally with a substitution as in `$(name:old=new)`.
+    Reference {
+        name: Vec<Fragment>,
+        substitution: Option<(Vec<Fragment>, Vec<Fragment>)>
+    },
+    /// `$(function args)`, where each argument is read separately.
+    Call {
+        spec: &'static FunctionSpec,
+        args: Vec<Vec<Fragment>>
+    }
+}
+
+/// Text that make would reject while reading it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SyntaxError {
+    pub text: String,
+    pub message: &'static str
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in `{}`", self.message, self.text)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Reads `text` as make would when expanding it.
+pub(crate) fn parse(text: &str) -> Result<Vec<Fragment>, SyntaxError> {
+    let error = |message| SyntaxError {
+        text: text.to_string(),
+        message
+    };
+    let mut fragments = vec![];
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(dollar) = rest.find('$') {
+        literal.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+        let Some(next) = rest.chars().next() else {
+            break;
+        };
+        match next {
+            '$' => {
+                literal.push('$');
+                rest = &rest[1..];
+            }
+            '(' | '{' => {
+                let close = if next == '(' { ')' } else { '}' };
+                let end = matching(&rest[1..], next, close)
+                    .ok_or_else(|| error("unterminated variable reference"))?;
+                let inner = &rest[1..1 + end];
+                rest = &rest[end + 2..];
+                if !literal.is_empty() {
+                    fragments
+                        .push(Fragment::Text(std::mem::take(&mut literal)));
+                }
+                fragments.push(parse_parenthesized(inner)?);
+            }
+            c => {
+                rest = &rest[c.len_utf8()..];
+                if !literal.is_empty() {
+                    fragments
+                        .push(Fragment::Text(std::mem::take(&mut literal)));
+                }
+                fragments.push(Fragment::Reference {
+                    name: vec![Fragment::Text(c.to_string())],
+                    substitution: None
+                });
+            }
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        fragments.push(Fragment::Text(literal));
+    }
+    Ok(fragments)
+}
+
+/// Reads the `inner` text of `$(inner)` or `${inner}`.
+fn parse_parenthesized(inner: &str) -> Result<Fragment, SyntaxError> {
+    let name_end = inner
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .unwrap_or(inner.len());
+    if inner[name_end..].starts_with([' ', '\t']) {
+        if let Some(spec) = catalog::lookup(&inner[..name_end]) {
+            let args = split_args(
+                inner[name_end..].trim_start_matches([' ', '\t']),
+                spec.max_args
+            );
+            return Ok(Fragment::Call {
+                spec,
+                args: args.into_iter().map(parse).collect::<Result<_, _>>()?
+            });
+        }
+    }
+    if let Some(colon) = find_top_level(inner, ':') {
+        if let Some(equals) = find_top_level(&inner[colon + 1..], '=') {
+            let equals = colon + 1 + equals;
+            return Ok(Fragment::Reference {
+                name: parse(&inner[..colon])?,
+                substitution: Some((
+                    parse(&inner[colon + 1..equals])?,
+                    parse(&inner[equals + 1..])?
+                ))
+            });
+        }
+    }
+    Ok(Fragment::Reference {
+        name: parse(inner)?,
+        substitution: None
+    })
+}
+
+/// The index in `text` of the `close` that balances an already-read `open`.
+fn matching(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// The index of the first `target` in `text` outside any parentheses or
+/// braces.
+fn find_top_level(text: &str, target: char) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth = depth.saturating_sub(1),
+            c if c == target && depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the arguments of a function call at commas outside any parentheses
+/// or braces, giving the rest of the call to the last of at most `max`
+/// arguments.
+fn split_args(text: &str, max: Option<usize>) -> Vec<&str> {
+    let mut args = vec![];
+    let mut rest = text;
+    while max.is_none_or(|max| args.len() + 1 < max) {
+        match find_top_level(rest, ',') {
+            Some(comma) => {
+                args.push(&rest[..comma]);
+                rest = &rest[comma + 1..];
+            }
+            None => break
+        }
+    }
+    args.push(rest);
+    args
+}
+
+impl Fragment {
+    /// The name of the variable this fragment references, if it is a reference
+    /// whose name is not computed.
+    pub(crate) fn static_name(&self) -> Option<&str> {
+        match self {
+            Self::Reference { name, .. } => match name.as_slice() {
+                [Fragment::Text(name)] => Some(name),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /// Calls `f` on this fragment and every fragment nested within it.
+    pub(crate) fn walk(&self, f: &mut impl FnMut(&Fragment)) {
+        f(self);
+        match self {
+            Self::Text(_) => {}
+            Self::Reference { name, substitution } => {
+                walk_all(name, f);
+                if let Some((old, new)) = substitution {
+                    walk_all(old, f);
+                    walk_all(new, f);
+                }
+            }
+            Self::Call { args, .. } => {
+                for arg in args {
+                    walk_all(arg, f);
+                }
+            }
+        }
+    }
+}
+
+/// Calls `f` on every fragment in `fragments` and every fragment nested within
+/// them.
+pub(crate) fn walk_all(fragments: &[Fragment], f: &mut impl FnMut(&Fragment)) {
+    for fragment in fragments {
+        fragment.walk(f);
+    }
+}
+
+/// Expands `fragments` without running make, provided they only contain text
+/// and references to variables whose values `lookup` knows.
+pub(crate) fn expand_static(
+    fragments: &[Fragment], lookup: &impl Fn(&str) -> Option<String>
+) -> Option<String> {
+    let mut result = String::new();
+    for fragment in fragments {
+        match fragment {
+            Fragment::Text(text) => result.push_str(text),
+            Fragment::Reference {
+                name,
+                substitution: None
+            } => {
+                let name = expand_static(name, lookup)?;
+                result.push_str(&lookup(&name)?);
+            }
+            _ => return None
+        }
+    }
+    Some(result)
+}
+
+/// The values of the variables in a Makefile that can be known without running
+/// make.
+#[derive(Default)]
+pub(crate) struct StaticValues {
+    texts: HashMap<String, String>,
+    unknown: HashSet<String>
+}
+
+impl StaticValues {
+    /// The deepest chain of references followed when expanding a value.
+    const MAX_DEPTH: usize = 64;
+
+    /// Records an assignment like `kind` of `text` to `name`, whose value is
+    /// unknown from then on if the assignment is `conditional`.
+    pub(crate) fn assign(
+        &mut self, name: &str, kind: AssignmentKind, text: String,
+        conditional: bool
+    ) {
+        if conditional {
+            self.texts.remove(name);
+            self.unknown.insert(name.to_string());
+            return;
+        }
+        match kind {
+            AssignmentKind::Overwrite => {
+                self.texts.insert(name.to_string(), text);
+            }
+            AssignmentKind::Underwrite => {
+                self.texts.entry(name.to_string()).or_insert(text);
+            }
+            AssignmentKind::Simple | AssignmentKind::Immediate => {
+                match self.expand(&text) {
+                    Some(value) => {
+                        self.texts
+                            .insert(name.to_string(), value.replace('$', "$$"));
+                    }
+                    None => {
+                        self.texts.remove(name);
+                        self.unknown.insert(name.to_string());
+                    }
+                }
+            }
+            AssignmentKind::Append => {
+                let value = self.texts.entry(name.to_string()).or_default();
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(&text);
+            }
+        }
+    }
+
+    /// Expands `text`, if its value can be known.
+    pub(crate) fn expand(&self, text: &str) -> Option<String> {
+        self.expand_at(text, 0)
+    }
+
+    fn get_at(&self, name: &str, depth: usize) -> Option<String> {
+        if depth > Self::MAX_DEPTH || self.unknown.contains(name) {
+            return None;
+        }
+        self.expand_at(self.texts.get(name)?, depth + 1)
+    }
+
+    fn expand_at(&self, text: &str, depth: usize) -> Option<String> {
+        let fragments = parse(text).ok()?;
+        expand_static(&fragments, &|name| self.get_at(name, depth))
+    }
+}
+
+/// `words` with the first `%` in each replaced by `stem`.
+pub(crate) fn substitute_stem(words: &[String], stem: &str) -> Vec<String> {
+    words
+        .iter()
+        .map(|word| word.replacen('%', stem, 1))
+        .collect()
+}
+
+/// The stem that `%` stands for when `word` matches `pattern`, which contains
+/// at most one `%`, or the empty stem if `pattern` has no `%` and is `word`.
+pub(crate) fn match_pattern<'a>(
+    pattern: &str, word: &'a str
+) -> Option<&'a str> {
+    match pattern.split_once('%') {
+        Some((prefix, suffix)) => word
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix)),
+        None => (pattern == word).then_some("")
+    }
+}
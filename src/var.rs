@@ -1,6 +1,8 @@
 use crate::{
     emittable::Emittable,
-    symbol_context::{Resolvable, SymbolContext, SymbolID}
+    error::Error,
+    expr::Expr,
+    symbol_context::{Resolvable, SymbolContext, SymbolID, VariablePromise}
 };
 
 #[derive(Clone, Copy)]
@@ -40,8 +42,73 @@ impl Emittable for Variable {
     fn emit(&self, ctx: &mut SymbolContext) -> String {
         let name = self.name(ctx);
         match self.value {
-            _Variable::Builtin(_) => format!("${}", name),
-            _Variable::User(_) => format!("$({})", name)
+            _Variable::Builtin(_) if name.chars().count() == 1 => {
+                format!("${}", name)
+            }
+            _Variable::Builtin(_) | _Variable::User(_) => {
+                format!("$({})", name)
+            }
+        }
+    }
+
+    fn validate(&self, ctx: &mut SymbolContext, _errors: &mut Vec<Error>) {
+        ctx.mark_reachable(*self);
+    }
+}
+
+/// The name of a referenced variable, which may itself be computed by
+/// expanding an expression, e.g., `$($(ARCH)_CFLAGS)` or `$(CFLAGS_$@)`.
+pub enum VariableName {
+    Static(VariablePromise),
+    Computed(Expr)
+}
+
+impl VariableName {
+    /// Constructs the name obtained by expanding `name`.
+    pub fn computed<E: Into<Expr>>(name: E) -> Self {
+        Self::Computed(name.into())
+    }
+
+    /// The textual representation of the name itself, without the surrounding
+    /// `$(...)`.
+    pub(crate) fn emit_name(&self, ctx: &mut SymbolContext) -> String {
+        match self {
+            Self::Static(promise) => promise.name(ctx).to_string(),
+            Self::Computed(name) => name.emit(ctx)
+        }
+    }
+}
+
+impl<S: AsRef<str>> From<S> for VariableName {
+    fn from(value: S) -> Self {
+        Self::Static(value.into())
+    }
+}
+
+impl From<Variable> for VariableName {
+    fn from(value: Variable) -> Self {
+        Self::Static(value.into())
+    }
+}
+
+impl From<VariablePromise> for VariableName {
+    fn from(value: VariablePromise) -> Self {
+        Self::Static(value)
+    }
+}
+
+impl Emittable for VariableName {
+    fn emit(&self, ctx: &mut SymbolContext) -> String {
+        match self {
+            Self::Static(promise) => promise.resolve(ctx).emit(ctx),
+            Self::Computed(name) => format!("$({})", name.emit(ctx))
+        }
+    }
+
+    fn validate(&self, ctx: &mut SymbolContext, errors: &mut Vec<Error>) {
+        match self {
+            Self::Static(promise) => promise.resolve(ctx).validate(ctx, errors),
+            Self::Computed(name) => name.validate(ctx, errors)
         }
     }
 }
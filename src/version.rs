@@ -0,0 +1,228 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    assignment::AssignmentKind,
+    dialect::Dialect,
+    emittable::Emittable,
+    entry::Entry,
+    makefile::Makefile,
+    symbol_context::{Resolvable, SymbolContext},
+    syntax::{self, Fragment}
+};
+
+/// A GNU make version, e.g., `4.4`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32
+}
+
+impl Version {
+    /// The oldest version of GNU make that `makemake` distinguishes; features
+    /// that predate it are treated as having been introduced in it.
+    pub const OLDEST: Version = Version::new(3, 78);
+
+    /// Constructs version `major.minor`.
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Self::OLDEST
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// A feature of GNU make used by a Makefile, along with the first version of
+/// GNU make providing it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Requirement {
+    pub feature: String,
+    pub since: Version
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} requires GNU make {}", self.feature, self.since)
+    }
+}
+
+/// Special targets that GNU make has not always recognized. See section
+/// [4.9](https://www.gnu.org/software/make/manual/make.html#Special-Targets).
+const SPECIAL_TARGETS: &[(&str, Version)] = &[
+    (".ONESHELL", Version::new(3, 82)),
+    (".NOTINTERMEDIATE", Version::new(4, 4)),
+    (".WAIT", Version::new(4, 4))
+];
+
+/// Special variables that GNU make has not always recognized. See section
+/// [6.14](https://www.gnu.org/software/make/manual/make.html#Special-Variables).
+const SPECIAL_VARIABLES: &[(&str, Version)] = &[
+    (".RECIPEPREFIX", Version::new(3, 82)),
+    (".SHELLFLAGS", Version::new(3, 82)),
+    (".EXTRA_PREREQS", Version::new(4, 3))
+];
+
+/// The requirements found so far, each for a distinct feature.
+#[derive(Default)]
+struct Requirements(Vec<Requirement>);
+
+impl Requirements {
+    fn add(&mut self, feature: String, since: Version) {
+        if since > Version::OLDEST
+            && !self.0.iter().any(|other| other.feature == feature)
+        {
+            self.0.push(Requirement { feature, since });
+        }
+    }
+
+    /// Adds the requirement of each function called in `text`.
+    fn add_text(&mut self, text: &str) {
+        let Ok(fragments) = syntax::parse(text) else {
+            return;
+        };
+        syntax::walk_all(&fragments, &mut |fragment| {
+            if let Fragment::Call { spec, .. } = fragment {
+                self.add(format!("the function `{}`", spec.name), spec.since);
+            }
+        });
+    }
+
+    /// Adds the requirement of each special target in `text`.
+    fn add_targets(&mut self, text: &str) {
+        for word in text.split_whitespace() {
+            if let Some((name, since)) =
+                SPECIAL_TARGETS.iter().find(|(name, _)| *name == word)
+            {
+                self.add(format!("the special target `{}`", name), *since);
+            }
+        }
+    }
+
+    fn add_entry(&mut self, entry: &Entry, ctx: &mut SymbolContext) {
+        match entry {
+            Entry::Assignment(assignment) => {
+                let name = assignment.var.name(ctx).to_string();
+                if let Some((name, since)) =
+                    SPECIAL_VARIABLES.iter().find(|(other, _)| *other == name)
+                {
+                    self.add(
+                        format!("the special variable `{}`", name),
+                        *since
+                    );
+                }
+                let posix = ctx.dialect() == Dialect::Posix2024;
+                match assignment.shell_command(ctx) {
+                    Some(command) if posix => {
+                        self.add(
+                            "the assignment `!=`".into(),
+                            Version::new(4, 0)
+                        );
+                        self.add_text(&command);
+                        return;
+                    }
+                    _ => {}
+                }
+                match assignment.kind {
+                    AssignmentKind::Simple if posix => {
+                        self.add(
+                            "the assignment `::=`".into(),
+                            Version::new(4, 0)
+                        );
+                    }
+                    AssignmentKind::Immediate => {
+                        self.add(
+                            "the assignment `:::=`".into(),
+                            Version::new(4, 4)
+                        );
+                    }
+                    _ => {}
+                }
+                self.add_text(&assignment.value.emit(ctx));
+            }
+            Entry::Include(include) => self.add_text(&include.path().emit(ctx)),
+            Entry::Rule(rule) => {
+                let rule = rule.rule.borrow();
+                let target = rule.target.emit(ctx);
+                self.add_text(&target);
+                self.add_targets(&target);
+                if target.trim_end().ends_with('&') {
+                    self.add("grouped targets".into(), Version::new(4, 3));
+                }
+                if !rule.order_only_dependencies.is_empty() {
+                    self.add(
+                        "order-only prerequisites".into(),
+                        Version::new(3, 80)
+                    );
+                }
+                for dependency in rule
+                    .dependencies
+                    .iter()
+                    .chain(&rule.order_only_dependencies)
+                {
+                    let text = dependency.emit(ctx);
+                    self.add_text(&text);
+                    self.add_targets(&text);
+                }
+                for command in &rule.commands {
+                    for line in rule.emit_command(command, ctx) {
+                        self.add_text(&line);
+                    }
+                }
+            }
+            Entry::Conditional(conditional) => {
+                let conditional = conditional.conditional.borrow();
+                if conditional
+                    .branches
+                    .iter()
+                    .skip(1)
+                    .any(|branch| branch.condition.is_some())
+                {
+                    self.add(
+                        "an `else` with a condition".into(),
+                        Version::new(3, 81)
+                    );
+                }
+                for branch in &conditional.branches {
+                    if let Some(condition) = &branch.condition {
+                        self.add_text(&condition.emit(ctx));
+                    }
+                }
+            }
+            Entry::Comment(_) | Entry::Newline(_) | Entry::Namespace(_) => {}
+        }
+    }
+}
+
+/// Every feature used by `makefile` that not all versions of GNU make
+/// provide.
+pub(crate) fn requirements(makefile: &Makefile) -> Vec<Requirement> {
+    let mut requirements = Requirements::default();
+    let mut ctx = makefile.symbols_mut();
+    for entry in &makefile.contents {
+        entry.walk(false, &mut |entry, _| {
+            requirements.add_entry(entry, &mut ctx)
+        });
+    }
+    requirements.0
+}
+
+/// Lines that stop GNU make older than `requirement.since` with an error.
+pub(crate) fn guard(requirement: &Requirement) -> String {
+    // Sorting compares the versions as text, which orders every released
+    // version correctly since no minor version has had two digits.
+    format!(
+        "ifeq ($(filter {0},$(firstword $(sort $(MAKE_VERSION) {0}))),)\n\
+         $(error GNU make {0} or later is required for {1}, but this is \
+         $(MAKE_VERSION))\n\
+         endif",
+        requirement.since, requirement.feature
+    )
+}
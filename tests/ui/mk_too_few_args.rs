@@ -0,0 +1,5 @@
+use makemake::mk;
+
+fn main() {
+    let _ = mk!("{} {} {}", "a", "b");
+}